@@ -51,6 +51,10 @@ impl<T, P: Push<T>> Push<T> for Pusher<T, P> {
 
         self.pusher.push(element)
     }
+    #[inline]
+    fn over_budget(&self) -> bool {
+        self.pusher.over_budget()
+    }
 }
 
 use std::sync::mpsc::Sender;
@@ -99,6 +103,10 @@ impl<T, P: Push<T>> Push<T> for ArcPusher<T, P> {
 
         self.pusher.push(element)
     }
+    #[inline]
+    fn over_budget(&self) -> bool {
+        self.pusher.over_budget()
+    }
 }
 
 /// The pull half of an intra-thread channel.
@@ -0,0 +1,168 @@
+//! Push and Pull wrappers for credit-based flow control.
+//!
+//! A channel allocated with a budget tracks the messages each sender sent to each target but
+//! not yet pulled by it. Senders spend a credit for each message pushed, and the target
+//! refunds the credit when it pulls the message; for targets in other processes, refunds
+//! travel back over the network. A pusher whose target holds `budget` or more unreceived messages
+//! reports this through `Push::over_budget`.
+//!
+//! Flow control is advisory: pushers continue to accept messages when over budget, and it
+//! is up to producers to observe the report and yield.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use logging_core::Logger;
+
+use crate::{Push, Pull};
+use crate::logging::{CommunicationEvent, CommunicationSetup, CreditEvent};
+
+/// A count of messages sent to a target and not yet received, shared by sender and receiver.
+#[derive(Clone, Debug)]
+pub struct Credits {
+    outstanding: Arc<AtomicUsize>,
+    budget: usize,
+}
+
+impl Credits {
+    /// Creates a new count, which is over budget when `budget` messages are outstanding.
+    pub fn new(budget: usize) -> Self {
+        Credits {
+            outstanding: Arc::new(AtomicUsize::new(0)),
+            budget,
+        }
+    }
+    /// Records the sending of `count` messages.
+    #[inline]
+    pub fn spend(&self, count: usize) {
+        self.outstanding.fetch_add(count, Ordering::SeqCst);
+    }
+    /// Records the receipt of `count` messages.
+    #[inline]
+    pub fn refund(&self, count: usize) {
+        self.outstanding.fetch_sub(count, Ordering::SeqCst);
+    }
+    /// The number of messages sent and not yet received.
+    #[inline]
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.load(Ordering::SeqCst)
+    }
+    /// The number of outstanding messages at which senders are over budget.
+    #[inline]
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+    /// Indicates that the outstanding messages have reached the budget.
+    #[inline]
+    pub fn over_budget(&self) -> bool {
+        self.outstanding() >= self.budget
+    }
+}
+
+/// Spends credits for messages pushed.
+pub struct Pusher<T, P: Push<T>> {
+    pusher: P,
+    credits: Credits,
+    phantom: ::std::marker::PhantomData<T>,
+}
+
+impl<T, P: Push<T>> Pusher<T, P> {
+    /// Wraps a pusher with credit accounting.
+    pub fn new(pusher: P, credits: Credits) -> Self {
+        Pusher {
+            pusher,
+            credits,
+            phantom: ::std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T, P: Push<T>> Push<T> for Pusher<T, P> {
+    #[inline]
+    fn push(&mut self, element: &mut Option<T>) {
+        if element.is_some() {
+            self.credits.spend(1);
+        }
+        self.pusher.push(element)
+    }
+    #[inline]
+    fn over_budget(&self) -> bool {
+        self.credits.over_budget() || self.pusher.over_budget()
+    }
+}
+
+/// Refunds credits for messages pulled.
+pub struct Puller<T, P: Pull<T>> {
+    puller: P,
+    credits: Credits,
+    // The logger, channel, sending worker, and receiving worker for refunds, if they are logged.
+    logging: Option<(Logger<CommunicationEvent, CommunicationSetup>, usize, usize, usize)>,
+    phantom: ::std::marker::PhantomData<T>,
+}
+
+impl<T, P: Pull<T>> Puller<T, P> {
+    /// Wraps a puller with credit accounting.
+    pub fn new(puller: P, credits: Credits) -> Self {
+        Puller {
+            puller,
+            credits,
+            logging: None,
+            phantom: ::std::marker::PhantomData,
+        }
+    }
+    /// Logs each refund as a `CreditEvent` to `logger`, for `channel` from worker `sender` pulled by worker `receiver`.
+    pub fn with_logger(mut self, logger: Option<Logger<CommunicationEvent, CommunicationSetup>>, channel: usize, sender: usize, receiver: usize) -> Self {
+        self.logging = logger.map(|logger| (logger, channel, sender, receiver));
+        self
+    }
+}
+
+impl<T, P: Pull<T>> Pull<T> for Puller<T, P> {
+    #[inline]
+    fn pull(&mut self) -> &mut Option<T> {
+        let result = self.puller.pull();
+        if result.is_some() {
+            self.credits.refund(1);
+            if let Some((logger, channel, sender, receiver)) = self.logging.as_mut() {
+                logger.log(CreditEvent { is_send: false, channel: *channel, source: *receiver, target: *sender, count: 1 });
+            }
+        }
+        result
+    }
+}
+
+/// Pulls from the pullers of several senders in turn, starting after the last to yield a message.
+///
+/// Allocators use this to give each pair of sender and receiver its own credits.
+pub struct Merge<T, P: Pull<T>> {
+    pullers: Vec<P>,
+    next: usize,
+    current: Option<T>,
+}
+
+impl<T, P: Pull<T>> Merge<T, P> {
+    /// Merges the messages of `pullers`.
+    pub fn new(pullers: Vec<P>) -> Self {
+        Merge {
+            pullers,
+            next: 0,
+            current: None,
+        }
+    }
+}
+
+impl<T, P: Pull<T>> Pull<T> for Merge<T, P> {
+    #[inline]
+    fn pull(&mut self) -> &mut Option<T> {
+        self.current = None;
+        for _ in 0 .. self.pullers.len() {
+            let index = self.next;
+            self.next = (self.next + 1) % self.pullers.len();
+            self.current = self.pullers[index].pull().take();
+            if self.current.is_some() {
+                break;
+            }
+        }
+        &mut self.current
+    }
+}
@@ -9,11 +9,11 @@ use std::collections::VecDeque;
 
 use crate::allocator::thread::ThreadBuilder;
 use crate::allocator::process::ProcessBuilder as TypedProcessBuilder;
-use crate::allocator::{Allocate, AllocateBuilder, Event, Thread, Process};
+use crate::allocator::{Allocate, AllocateBuilder, Event, Priority, Thread, Process};
 use crate::allocator::zero_copy::allocator_process::{ProcessBuilder, ProcessAllocator};
 use crate::allocator::zero_copy::allocator::{TcpBuilder, TcpAllocator};
 
-use crate::{Push, Pull, Data, Message};
+use crate::{Push, Pull, Data, Message, WorkerPanic};
 
 /// Enumerates known implementors of `Allocate`.
 /// Passes trait method calls on to members.
@@ -25,7 +25,7 @@ pub enum Generic {
     /// Inter-thread, intra-process serializing allocator.
     ProcessBinary(ProcessAllocator),
     /// Inter-process allocator.
-    ZeroCopy(TcpAllocator<Process>),
+    ZeroCopy(Box<TcpAllocator<Process>>),
 }
 
 impl Generic {
@@ -56,6 +56,24 @@ impl Generic {
             &mut Generic::ZeroCopy(ref mut z) => z.allocate(identifier),
         }
     }
+    /// Constructs several send endpoints and one receive endpoint, with flow control.
+    fn allocate_with_budget<T: Data>(&mut self, identifier: usize, budget: Option<usize>) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        match *self {
+            Generic::Thread(ref mut t) => t.allocate_with_budget(identifier, budget),
+            Generic::Process(ref mut p) => p.allocate_with_budget(identifier, budget),
+            Generic::ProcessBinary(ref mut pb) => pb.allocate_with_budget(identifier, budget),
+            Generic::ZeroCopy(ref mut z) => z.allocate_with_budget(identifier, budget),
+        }
+    }
+    /// Constructs several send endpoints and one receive endpoint, for traffic of `priority`.
+    fn allocate_with_priority<T: Data>(&mut self, identifier: usize, priority: Priority) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        match *self {
+            Generic::Thread(ref mut t) => t.allocate_with_priority(identifier, priority),
+            Generic::Process(ref mut p) => p.allocate_with_priority(identifier, priority),
+            Generic::ProcessBinary(ref mut pb) => pb.allocate_with_priority(identifier, priority),
+            Generic::ZeroCopy(ref mut z) => z.allocate_with_priority(identifier, priority),
+        }
+    }
     /// Perform work before scheduling operators.
     fn receive(&mut self) {
         match self {
@@ -82,6 +100,51 @@ impl Generic {
             &Generic::ZeroCopy(ref z) => z.events(),
         }
     }
+    /// Indices of remote processes whose connections have been lost.
+    pub fn lost_processes(&self) -> &[usize] {
+        match *self {
+            Generic::Thread(ref t) => t.lost_processes(),
+            Generic::Process(ref p) => p.lost_processes(),
+            Generic::ProcessBinary(ref pb) => pb.lost_processes(),
+            Generic::ZeroCopy(ref z) => z.lost_processes(),
+        }
+    }
+    /// Announces the failure of a worker to the workers of other processes.
+    pub fn announce_failure(&mut self, failure: &WorkerPanic) {
+        match *self {
+            Generic::Thread(ref mut t) => t.announce_failure(failure),
+            Generic::Process(ref mut p) => p.announce_failure(failure),
+            Generic::ProcessBinary(ref mut pb) => pb.announce_failure(failure),
+            Generic::ZeroCopy(ref mut z) => z.announce_failure(failure),
+        }
+    }
+    /// The first failure announced by a worker of another process, if any.
+    pub fn remote_failure(&self) -> Option<&WorkerPanic> {
+        match *self {
+            Generic::Thread(ref t) => t.remote_failure(),
+            Generic::Process(ref p) => p.remote_failure(),
+            Generic::ProcessBinary(ref pb) => pb.remote_failure(),
+            Generic::ZeroCopy(ref z) => z.remote_failure(),
+        }
+    }
+    /// Indicates that no further messages will arrive for `channels`.
+    pub fn retire_channels(&mut self, channels: &[usize]) {
+        match *self {
+            Generic::Thread(ref mut t) => t.retire_channels(channels),
+            Generic::Process(ref mut p) => p.retire_channels(channels),
+            Generic::ProcessBinary(ref mut pb) => pb.retire_channels(channels),
+            Generic::ZeroCopy(ref mut z) => z.retire_channels(channels),
+        }
+    }
+    /// Bytes queued between the worker and each other process, as `(process, sent, received)`.
+    pub fn queued_bytes(&self) -> Vec<(usize, usize, usize)> {
+        match *self {
+            Generic::Thread(ref t) => t.queued_bytes(),
+            Generic::Process(ref p) => p.queued_bytes(),
+            Generic::ProcessBinary(ref pb) => pb.queued_bytes(),
+            Generic::ZeroCopy(ref z) => z.queued_bytes(),
+        }
+    }
 }
 
 impl Allocate for Generic {
@@ -90,10 +153,21 @@ impl Allocate for Generic {
     fn allocate<T: Data>(&mut self, identifier: usize) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
         self.allocate(identifier)
     }
+    fn allocate_with_budget<T: Data>(&mut self, identifier: usize, budget: Option<usize>) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate_with_budget(identifier, budget)
+    }
+    fn allocate_with_priority<T: Data>(&mut self, identifier: usize, priority: Priority) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate_with_priority(identifier, priority)
+    }
 
     fn receive(&mut self) { self.receive(); }
     fn release(&mut self) { self.release(); }
     fn events(&self) -> &Rc<RefCell<VecDeque<(usize, Event)>>> { self.events() }
+    fn lost_processes(&self) -> &[usize] { self.lost_processes() }
+    fn announce_failure(&mut self, failure: &WorkerPanic) { self.announce_failure(failure) }
+    fn remote_failure(&self) -> Option<&WorkerPanic> { self.remote_failure() }
+    fn retire_channels(&mut self, channels: &[usize]) { self.retire_channels(channels) }
+    fn queued_bytes(&self) -> Vec<(usize, usize, usize)> { self.queued_bytes() }
     fn await_events(&self, _duration: Option<std::time::Duration>) {
         match self {
             &Generic::Thread(ref t) => t.await_events(_duration),
@@ -128,7 +202,7 @@ impl AllocateBuilder for GenericBuilder {
             GenericBuilder::Thread(t) => Generic::Thread(t.build()),
             GenericBuilder::Process(p) => Generic::Process(p.build()),
             GenericBuilder::ProcessBinary(pb) => Generic::ProcessBinary(pb.build()),
-            GenericBuilder::ZeroCopy(z) => Generic::ZeroCopy(z.build()),
+            GenericBuilder::ZeroCopy(z) => Generic::ZeroCopy(Box::new(z.build())),
         }
     }
 }
@@ -12,13 +12,16 @@ pub use self::generic::{Generic, GenericBuilder};
 pub mod thread;
 pub mod process;
 pub mod generic;
+pub mod simulation;
 
 pub mod canary;
 pub mod counters;
+pub mod credit;
 
 pub mod zero_copy;
 
 use crate::{Data, Push, Pull, Message};
+use crate::initialize::WorkerPanic;
 
 /// A proto-allocator, which implements `Send` and can be completed with `build`.
 ///
@@ -33,6 +36,20 @@ pub trait AllocateBuilder : Send {
     fn build(self) -> Self::Allocator;
 }
 
+/// The class of traffic a channel carries.
+///
+/// Allocators that multiplex channels over connections to other processes send the messages of
+/// `Control` channels ahead of those of `Data` channels, so that coordination, for example the
+/// exchange of progress updates, is not delayed behind bulk data. Messages of each channel remain
+/// in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// Coordination among workers, such as progress updates.
+    Control,
+    /// Bulk data, which is the default.
+    Data,
+}
+
 /// A type capable of allocating channels.
 ///
 /// There is some feature creep, in that this contains several convenience methods about the nature
@@ -44,6 +61,23 @@ pub trait Allocate {
     fn peers(&self) -> usize;
     /// Constructs several send endpoints and one receive endpoint.
     fn allocate<T: Data>(&mut self, identifier: usize) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>);
+    /// Constructs several send endpoints and one receive endpoint, with flow control.
+    ///
+    /// If `budget` is set, each send endpoint reports through `Push::over_budget` when its
+    /// target has at least `budget` messages sent but not yet received. All workers should
+    /// supply the same budget for the same identifier. Each pair of sender and target has its
+    /// own budget, so a target may hold up to `budget` messages from each sender. By default,
+    /// the budget is ignored.
+    fn allocate_with_budget<T: Data>(&mut self, identifier: usize, _budget: Option<usize>) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate(identifier)
+    }
+    /// Constructs several send endpoints and one receive endpoint, for traffic of `priority`.
+    ///
+    /// All workers should supply the same priority for the same identifier. By default, the
+    /// priority is ignored.
+    fn allocate_with_priority<T: Data>(&mut self, identifier: usize, _priority: Priority) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate(identifier)
+    }
     /// A shared queue of communication events with channel identifier.
     ///
     /// It is expected that users of the channel allocator will regularly
@@ -81,6 +115,36 @@ pub trait Allocate {
     /// buffers, and can be a performance problem if invoked casually.
     fn release(&mut self) { }
 
+    /// Indices of remote processes whose connections have been lost.
+    ///
+    /// Lost processes are only reported by allocators configured to tolerate
+    /// failures; others panic instead. Once reported, a process remains in the
+    /// list, and data sent to its workers are discarded.
+    fn lost_processes(&self) -> &[usize] { &[] }
+
+    /// Announces the failure of a worker to the workers of other processes.
+    ///
+    /// Workers of the same process learn of failures through the computation's
+    /// `CancellationToken` instead. Announcements are best effort, and are sent
+    /// along with other outgoing data. By default, nothing is announced.
+    fn announce_failure(&mut self, _failure: &WorkerPanic) { }
+
+    /// The first failure announced by a worker of another process, if any.
+    fn remote_failure(&self) -> Option<&WorkerPanic> { None }
+
+    /// Indicates that no further messages will arrive for `channels`.
+    ///
+    /// Allocators that discard messages for channels whose pullers have been dropped may then
+    /// forget those channels. Channels whose pullers have not yet been dropped are forgotten
+    /// once they are. By default, nothing is done.
+    fn retire_channels(&mut self, _channels: &[usize]) { }
+
+    /// Bytes queued between the worker and each other process, as `(process, sent, received)`.
+    ///
+    /// Sent bytes await the network threads, and received bytes await the worker. Allocators
+    /// that do not exchange bytes with other processes report nothing, which is the default.
+    fn queued_bytes(&self) -> Vec<(usize, usize, usize)> { Vec::new() }
+
     /// Constructs a pipeline channel from the worker to itself.
     ///
     /// By default, this method uses the thread-local channel constructor
@@ -10,8 +10,15 @@ use std::collections::{HashMap, VecDeque};
 
 use crate::allocator::thread::{ThreadBuilder};
 use crate::allocator::{Allocate, AllocateBuilder, Event, Thread};
+use crate::allocator::credit::{Credits, Merge, Pusher as CreditPusher, Puller as CreditPuller};
 use crate::{Push, Pull, Message};
 use crate::buzzer::Buzzer;
+use crate::logging::{CommunicationEvent, CommunicationSetup};
+
+use logging_core::Logger;
+
+/// Creates a logger for communication events, if they are to be logged.
+type LogFn = Arc<dyn Fn(CommunicationSetup)->Option<Logger<CommunicationEvent, CommunicationSetup>>+Send+Sync>;
 
 /// An allocator for inter-thread, intra-process communication
 pub struct ProcessBuilder {
@@ -27,6 +34,19 @@ pub struct ProcessBuilder {
 
     counters_send: Vec<Sender<(usize, Event)>>,
     counters_recv: Receiver<(usize, Event)>,
+
+    // The index of the process, and where to obtain a logger for credits returned within it.
+    logging: Option<(usize, LogFn)>,
+}
+
+impl ProcessBuilder {
+    /// Logs the credits returned within process `process` to a logger obtained from `log_fn`.
+    ///
+    /// Worker indices in the logged events are offset by those of workers in lower processes.
+    pub fn with_logging(mut self, process: usize, log_fn: LogFn) -> Self {
+        self.logging = Some((process, log_fn));
+        self
+    }
 }
 
 impl AllocateBuilder for ProcessBuilder {
@@ -43,6 +63,11 @@ impl AllocateBuilder for ProcessBuilder {
             buzzers.push(worker.recv().expect("Failed to recv buzzer"));
         }
 
+        let (offset, logger) = match self.logging {
+            Some((process, log_fn)) => (process * self.peers, log_fn(CommunicationSetup { sender: false, process, remote: None })),
+            None => (0, None),
+        };
+
         Process {
             inner: self.inner.build(),
             index: self.index,
@@ -51,6 +76,8 @@ impl AllocateBuilder for ProcessBuilder {
             buzzers,
             counters_send: self.counters_send,
             counters_recv: self.counters_recv,
+            offset,
+            logger,
         }
     }
 }
@@ -65,6 +92,9 @@ pub struct Process {
     buzzers: Vec<Buzzer>,
     counters_send: Vec<Sender<(usize, Event)>>,
     counters_recv: Receiver<(usize, Event)>,
+    // The index of the first worker of the process, and the logger for credits returned within it.
+    offset: usize,
+    logger: Option<Logger<CommunicationEvent, CommunicationSetup>>,
 }
 
 impl Process {
@@ -101,6 +131,7 @@ impl Process {
                     channels: channels.clone(),
                     counters_send: counters_send.clone(),
                     counters_recv: recv,
+                    logging: None,
                 }
             })
             .collect()
@@ -111,6 +142,31 @@ impl Allocate for Process {
     fn index(&self) -> usize { self.index }
     fn peers(&self) -> usize { self.peers }
     fn allocate<T: Any+Send+Sync+'static>(&mut self, identifier: usize) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate_channel(identifier, None)
+    }
+    fn allocate_with_budget<T: Any+Send+Sync+'static>(&mut self, identifier: usize, budget: Option<usize>) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate_channel(identifier, budget)
+    }
+
+    fn events(&self) -> &Rc<RefCell<VecDeque<(usize, Event)>>> {
+        self.inner.events()
+    }
+
+    fn await_events(&self, duration: Option<Duration>) {
+        self.inner.await_events(duration);
+    }
+
+    fn receive(&mut self) {
+        let mut events = self.inner.events().borrow_mut();
+        while let Ok((index, event)) = self.counters_recv.try_recv() {
+            events.push_back((index, event));
+        }
+    }
+}
+
+impl Process {
+    /// Allocates a channel, whose pushers report when targets exceed `budget`, if supplied.
+    fn allocate_channel<T: Any+Send+Sync+'static>(&mut self, identifier: usize, budget: Option<usize>) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
 
         // this is race-y global initialisation of all channels for all workers, performed by the
         // first worker that enters this critical section
@@ -118,23 +174,44 @@ impl Allocate for Process {
         // ensure exclusive access to shared list of channels
         let mut channels = self.channels.lock().ok().expect("mutex error?");
 
-        let (sends, recv, empty) = {
+        let (sends, recvs, empty) = {
 
             // we may need to alloc a new channel ...
             let entry = channels.entry(identifier).or_insert_with(|| {
 
-                let mut pushers = Vec::new();
-                let mut pullers = Vec::new();
-                for index in 0 .. self.peers {
-
-                    let (s, r): (Sender<Message<T>>, Receiver<Message<T>>) = channel();
-                    pushers.push(Pusher { target: s, buzzer: self.buzzers[index].clone() });
-                    pullers.push(Puller { source: r, current: None });
-                }
-
+                // With a budget, each pair of sender and receiver has its own channel and
+                // credits, so that the receiver knows to whom each credit is returned.
                 let mut to_box = Vec::new();
-                for recv in pullers.into_iter() {
-                    to_box.push(Some((pushers.clone(), recv)));
+                match budget {
+                    Some(budget) => {
+                        let mut sends = (0 .. self.peers).map(|_| Vec::new()).collect::<Vec<_>>();
+                        let mut recvs = (0 .. self.peers).map(|_| Vec::new()).collect::<Vec<_>>();
+                        for pushers in sends.iter_mut() {
+                            for (pullers, buzzer) in recvs.iter_mut().zip(self.buzzers.iter()) {
+                                let (s, r): (Sender<Message<T>>, Receiver<Message<T>>) = channel();
+                                let credits = Credits::new(budget);
+                                pushers.push((Pusher { target: s, buzzer: buzzer.clone() }, Some(credits.clone())));
+                                pullers.push((Puller { source: r, current: None }, Some(credits)));
+                            }
+                        }
+                        for (sends, recvs) in sends.into_iter().zip(recvs) {
+                            to_box.push(Some((sends, recvs)));
+                        }
+                    },
+                    None => {
+                        let mut pushers = Vec::new();
+                        let mut pullers = Vec::new();
+                        for index in 0 .. self.peers {
+
+                            let (s, r): (Sender<Message<T>>, Receiver<Message<T>>) = channel();
+                            pushers.push((Pusher { target: s, buzzer: self.buzzers[index].clone() }, None));
+                            pullers.push((Puller { source: r, current: None }, None));
+                        }
+
+                        for recv in pullers.into_iter() {
+                            to_box.push(Some((pushers.clone(), vec![recv])));
+                        }
+                    },
                 }
 
                 Box::new(to_box)
@@ -142,20 +219,20 @@ impl Allocate for Process {
 
             let vector =
             entry
-                .downcast_mut::<(Vec<Option<(Vec<Pusher<Message<T>>>, Puller<Message<T>>)>>)>()
+                .downcast_mut::<Vec<Option<(Vec<(Pusher<Message<T>>, Option<Credits>)>, Vec<(Puller<Message<T>>, Option<Credits>)>)>>>()
                 .expect("failed to correctly cast channel");
 
-            let (sends, recv) =
+            let (sends, recvs) =
             vector[self.index]
                 .take()
                 .expect("channel already consumed");
 
             let empty = vector.iter().all(|x| x.is_none());
 
-            (sends, recv, empty)
+            (sends, recvs, empty)
         };
 
-        // send is a vec of all senders, recv is this worker's receiver
+        // send is a vec of all senders, recvs are this worker's receivers from each sender
 
         if empty { channels.remove(&identifier); }
 
@@ -165,29 +242,32 @@ impl Allocate for Process {
         let sends =
         sends.into_iter()
              .enumerate()
-             .map(|(i,s)| CountPusher::new(s, identifier, self.counters_send[i].clone()))
-             .map(|s| Box::new(s) as Box<dyn Push<super::Message<T>>>)
+             .map(|(i,(s, credits))| (CountPusher::new(s, identifier, self.counters_send[i].clone()), credits))
+             .map(|(s, credits)| match credits {
+                 Some(credits) => Box::new(CreditPusher::new(s, credits)) as Box<dyn Push<super::Message<T>>>,
+                 None => Box::new(s) as Box<dyn Push<super::Message<T>>>,
+             })
              .collect::<Vec<_>>();
 
-        let recv = Box::new(CountPuller::new(recv, identifier, self.inner.events().clone())) as Box<dyn Pull<super::Message<T>>>;
+        let recv = if budget.is_none() {
+            let (recv, _) = recvs.into_iter().next().expect("receiver missing");
+            Box::new(CountPuller::new(recv, identifier, self.inner.events().clone())) as Box<dyn Pull<super::Message<T>>>
+        }
+        else {
+            let target = self.offset + self.index;
+            let pullers =
+            recvs.into_iter()
+                 .enumerate()
+                 .map(|(i, (recv, credits))| {
+                     let credits = credits.expect("credits missing");
+                     CreditPuller::new(recv, credits).with_logger(self.logger.clone(), identifier, self.offset + i, target)
+                 })
+                 .collect::<Vec<_>>();
+            Box::new(CountPuller::new(Merge::new(pullers), identifier, self.inner.events().clone())) as Box<dyn Pull<super::Message<T>>>
+        };
 
         (sends, recv)
     }
-
-    fn events(&self) -> &Rc<RefCell<VecDeque<(usize, Event)>>> {
-        self.inner.events()
-    }
-
-    fn await_events(&self, duration: Option<Duration>) {
-        self.inner.await_events(duration);
-    }
-
-    fn receive(&mut self) {
-        let mut events = self.inner.events().borrow_mut();
-        while let Ok((index, event)) = self.counters_recv.try_recv() {
-            events.push_back((index, event));
-        }
-    }
 }
 
 /// The push half of an intra-process channel.
@@ -208,8 +288,11 @@ impl<T> Clone for Pusher<T> {
 impl<T> Push<T> for Pusher<T> {
     #[inline] fn push(&mut self, element: &mut Option<T>) {
         if let Some(element) = element.take() {
-            self.target.send(element).unwrap();
-            self.buzzer.buzz();
+            // An error indicates that the receiver has been dropped, for example
+            // because its dataflow was dropped; the element is then discarded.
+            if self.target.send(element).is_ok() {
+                self.buzzer.buzz();
+            }
         }
     }
 }
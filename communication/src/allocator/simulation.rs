@@ -0,0 +1,370 @@
+//! Simulated communication between workers sharing a single thread.
+//!
+//! A `Network` connects several `Simulated` allocators, whose workers are stepped in turn by
+//! a single thread. Messages pushed to a worker are held in flight on the link from their
+//! source, and are only delivered when the target worker next calls `receive`. At that point
+//! a `Chooser` decides, for each link with messages in flight, how many of them to deliver:
+//! all of them, some prefix of them, or none. Messages are thereby delayed, and deliveries
+//! are split and interleaved differently across links, while each link remains FIFO as the
+//! links of the other allocators are.
+//!
+//! All decisions are drawn from the chooser, so that a schedule is reproduced exactly by
+//! supplying the same choices. A `Seeded` chooser explores schedules at random from a seed,
+//! and an `Exhaustive` chooser enumerates the schedules that differ in their first choices.
+
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+
+use crate::allocator::{Allocate, Event};
+use crate::allocator::credit::{Credits, Merge, Pusher as CreditPusher, Puller as CreditPuller};
+use crate::{Data, Push, Pull, Message};
+
+/// A source of scheduling decisions.
+pub trait Chooser {
+    /// Chooses one of `options` alternatives, numbered from zero.
+    ///
+    /// The alternative zero is always the most eager one, for example delivering all messages.
+    fn choose(&mut self, options: usize) -> usize;
+}
+
+impl<C: Chooser> Chooser for Rc<RefCell<C>> {
+    fn choose(&mut self, options: usize) -> usize { self.borrow_mut().choose(options) }
+}
+
+/// Choices drawn uniformly at random from a seeded generator.
+#[derive(Clone, Debug)]
+pub struct Seeded {
+    state: u64,
+}
+
+impl Seeded {
+    /// Creates a chooser whose choices are determined by `seed`.
+    pub fn new(seed: u64) -> Self {
+        Seeded { state: seed }
+    }
+    // The `splitmix64` generator, which is small and has no poor seeds.
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl Chooser for Seeded {
+    fn choose(&mut self, options: usize) -> usize {
+        if options <= 1 { 0 } else { (self.next() % options as u64) as usize }
+    }
+}
+
+/// Choices that enumerate schedules in depth-first order.
+///
+/// The first `depth` choices of each run with more than one alternative are enumerated, and
+/// later choices are always zero. Each run must be started afresh and make the same choices
+/// given the same prefix of decisions, after which `next_schedule` advances to the next one.
+///
+/// # Examples
+/// ```
+/// use timely_communication::allocator::simulation::{Chooser, Exhaustive};
+///
+/// let mut exhaustive = Exhaustive::new(2);
+/// let mut schedules = Vec::new();
+/// loop {
+///     schedules.push((exhaustive.choose(2), exhaustive.choose(3), exhaustive.choose(2)));
+///     if !exhaustive.next_schedule() { break; }
+/// }
+/// assert_eq!(schedules, vec![(0, 0, 0), (0, 1, 0), (0, 2, 0), (1, 0, 0), (1, 1, 0), (1, 2, 0)]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Exhaustive {
+    // The choices of the current run, with their numbers of alternatives.
+    trail: Vec<(usize, usize)>,
+    position: usize,
+    depth: usize,
+}
+
+impl Exhaustive {
+    /// Creates a chooser that enumerates the first `depth` choices of each run.
+    pub fn new(depth: usize) -> Self {
+        Exhaustive { trail: Vec::new(), position: 0, depth }
+    }
+    /// Advances to the next schedule, returning false once all have been enumerated.
+    pub fn next_schedule(&mut self) -> bool {
+        self.trail.truncate(self.position);
+        self.position = 0;
+        while let Some((choice, options)) = self.trail.pop() {
+            if choice + 1 < options {
+                self.trail.push((choice + 1, options));
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Chooser for Exhaustive {
+    fn choose(&mut self, options: usize) -> usize {
+        if options <= 1 || self.position >= self.depth {
+            return 0;
+        }
+        if self.position == self.trail.len() {
+            self.trail.push((0, options));
+        }
+        let choice = self.trail[self.position].0;
+        self.position += 1;
+        ::std::cmp::min(choice, options - 1)
+    }
+}
+
+/// Messages in flight from one worker to another on one channel.
+trait Link {
+    /// The number of messages in flight.
+    fn in_flight(&self) -> usize;
+    /// Delivers the first `count` messages in flight, or discards them if the target is gone.
+    fn deliver(&mut self, count: usize);
+}
+
+struct Queue<T> {
+    identifier: usize,
+    in_flight: VecDeque<T>,
+    inbox: Weak<RefCell<VecDeque<T>>>,
+    events: Events,
+}
+
+impl<T> Link for Queue<T> {
+    fn in_flight(&self) -> usize { self.in_flight.len() }
+    fn deliver(&mut self, count: usize) {
+        let delivered = self.in_flight.drain(.. count);
+        if let Some(inbox) = self.inbox.upgrade() {
+            if count > 0 {
+                inbox.borrow_mut().extend(delivered);
+                self.events.borrow_mut().push_back((self.identifier, Event::Pushed(count)));
+            }
+        }
+    }
+}
+
+/// A worker's queue of communication events.
+type Events = Rc<RefCell<VecDeque<(usize, Event)>>>;
+
+/// The send endpoints and receive endpoint of a channel.
+type Endpoints<T> = (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>);
+
+struct State {
+    peers: usize,
+    chooser: Box<dyn Chooser>,
+    events: Vec<Events>,
+    // The links into each worker, in the order they were allocated.
+    links: Vec<Vec<Rc<RefCell<dyn Link>>>>,
+    // Endpoints allocated by some workers, awaiting the others: a
+    // `Vec<Option<(Vec<Pusher<T>>, Puller<T>)>>` and credits for each target.
+    channels: HashMap<usize, Box<dyn Any>>,
+}
+
+/// A simulated network between workers sharing a single thread.
+///
+/// The network is a handle to shared state, and clones refer to the same network.
+#[derive(Clone)]
+pub struct Network {
+    state: Rc<RefCell<State>>,
+}
+
+impl Network {
+    /// Creates a network between `peers` workers, scheduled by `chooser`.
+    pub fn new<C: Chooser+'static>(peers: usize, chooser: C) -> Self {
+        let state = State {
+            peers,
+            chooser: Box::new(chooser),
+            events: (0 .. peers).map(|_| Default::default()).collect(),
+            links: (0 .. peers).map(|_| Vec::new()).collect(),
+            channels: HashMap::new(),
+        };
+        Network { state: Rc::new(RefCell::new(state)) }
+    }
+    /// Creates the allocators of the network's workers.
+    pub fn allocators(&self) -> Vec<Simulated> {
+        let peers = self.state.borrow().peers;
+        (0 .. peers)
+            .map(|index| Simulated {
+                index,
+                peers,
+                events: self.state.borrow().events[index].clone(),
+                network: self.clone(),
+            })
+            .collect()
+    }
+    /// Draws a decision from the network's chooser.
+    pub fn choose(&self, options: usize) -> usize {
+        self.state.borrow_mut().chooser.choose(options)
+    }
+    /// The number of messages in flight to any worker.
+    pub fn in_flight(&self) -> usize {
+        self.state.borrow().links.iter().flat_map(|links| links.iter()).map(|link| link.borrow().in_flight()).sum()
+    }
+    /// Delivers some of the messages in flight to worker `index`, as the chooser decides.
+    fn deliver(&self, index: usize) {
+        let mut state = self.state.borrow_mut();
+        let State { chooser, links, .. } = &mut *state;
+        let links = &mut links[index];
+        // Discard links whose pushers are gone and that have nothing left to deliver.
+        links.retain(|link| Rc::strong_count(link) > 1 || link.borrow().in_flight() > 0);
+        // Links are visited from a chosen starting point, which varies the order of deliveries
+        // from different sources into the same channel.
+        let start = chooser.choose(links.len());
+        for offset in 0 .. links.len() {
+            let mut link = links[(start + offset) % links.len()].borrow_mut();
+            let in_flight = link.in_flight();
+            if in_flight > 0 {
+                let withheld = chooser.choose(in_flight + 1);
+                link.deliver(in_flight - withheld);
+            }
+        }
+    }
+}
+
+/// An allocator for one worker of a simulated `Network`.
+pub struct Simulated {
+    index: usize,
+    peers: usize,
+    events: Events,
+    network: Network,
+}
+
+impl Simulated {
+    /// The network the allocator belongs to.
+    pub fn network(&self) -> &Network { &self.network }
+
+    /// Allocates a channel, whose pushers report when targets exceed `budget`, if supplied.
+    fn allocate_channel<T: Data>(&mut self, identifier: usize, budget: Option<usize>) -> Endpoints<T> {
+
+        let mut state = self.network.state.borrow_mut();
+        let state = &mut *state;
+
+        // The first worker to allocate the channel creates the endpoints of all workers.
+        let (sends, recvs, credits, empty) = {
+
+            let peers = self.peers;
+            let links = &mut state.links;
+            let events = &state.events;
+            let entry = state.channels.entry(identifier).or_insert_with(|| {
+
+                // With a budget, each pair of sender and target has its own inbox and credits,
+                // so that the target knows to whom each credit is returned.
+                let pairs = if budget.is_some() { peers } else { 1 };
+                let inboxes = (0 .. peers).map(|_| (0 .. pairs).map(|_| Rc::new(RefCell::new(VecDeque::<Message<T>>::new()))).collect::<Vec<_>>()).collect::<Vec<_>>();
+                let mut endpoints = Vec::new();
+                for source in 0 .. peers {
+                    let mut pushers = Vec::new();
+                    for (target, inboxes) in inboxes.iter().enumerate() {
+                        let queue = Rc::new(RefCell::new(Queue {
+                            identifier,
+                            in_flight: VecDeque::new(),
+                            inbox: Rc::downgrade(&inboxes[source % pairs]),
+                            events: events[target].clone(),
+                        }));
+                        links[target].push(queue.clone());
+                        pushers.push(Pusher { queue });
+                    }
+                    endpoints.push(pushers);
+                }
+                let to_box = endpoints
+                    .into_iter()
+                    .zip(inboxes)
+                    .map(|(pushers, inboxes)| Some((pushers, inboxes.into_iter().map(|inbox| Puller { inbox, current: None }).collect::<Vec<_>>())))
+                    .collect::<Vec<_>>();
+
+                // Messages outstanding from each sender at each target.
+                let credits = budget.map(|budget| (0 .. peers).map(|_| (0 .. peers).map(|_| Credits::new(budget)).collect::<Vec<_>>()).collect::<Vec<_>>());
+
+                Box::new((to_box, credits))
+            });
+
+            let (vector, credits) =
+            entry
+                .downcast_mut::<(Vec<Option<(Vec<Pusher<Message<T>>>, Vec<Puller<Message<T>>>)>>, Option<Vec<Vec<Credits>>>)>()
+                .expect("failed to correctly cast channel");
+
+            let (sends, recvs) =
+            vector[self.index]
+                .take()
+                .expect("channel already consumed");
+
+            let empty = vector.iter().all(|x| x.is_none());
+
+            (sends, recvs, credits.clone(), empty)
+        };
+
+        if empty { state.channels.remove(&identifier); }
+
+        let sends =
+        sends.into_iter()
+             .enumerate()
+             .map(|(i,s)| match credits {
+                 Some(ref credits) => Box::new(CreditPusher::new(s, credits[self.index][i].clone())) as Box<dyn Push<Message<T>>>,
+                 None => Box::new(s) as Box<dyn Push<Message<T>>>,
+             })
+             .collect::<Vec<_>>();
+
+        let recv = match credits {
+            Some(ref credits) => {
+                let pullers =
+                recvs.into_iter()
+                     .enumerate()
+                     .map(|(i, recv)| CreditPuller::new(recv, credits[i][self.index].clone()))
+                     .collect::<Vec<_>>();
+                Box::new(Merge::new(pullers)) as Box<dyn Pull<Message<T>>>
+            },
+            None => Box::new(recvs.into_iter().next().expect("receiver missing")) as Box<dyn Pull<Message<T>>>,
+        };
+
+        (sends, recv)
+    }
+}
+
+impl Allocate for Simulated {
+    fn index(&self) -> usize { self.index }
+    fn peers(&self) -> usize { self.peers }
+    fn allocate<T: Data>(&mut self, identifier: usize) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate_channel(identifier, None)
+    }
+    fn allocate_with_budget<T: Data>(&mut self, identifier: usize, budget: Option<usize>) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate_channel(identifier, budget)
+    }
+    fn events(&self) -> &Rc<RefCell<VecDeque<(usize, Event)>>> {
+        &self.events
+    }
+    fn receive(&mut self) {
+        self.network.deliver(self.index);
+    }
+}
+
+/// The push half of a simulated channel, placing messages in flight.
+struct Pusher<T> {
+    queue: Rc<RefCell<Queue<T>>>,
+}
+
+impl<T> Push<T> for Pusher<T> {
+    #[inline]
+    fn push(&mut self, element: &mut Option<T>) {
+        if let Some(element) = element.take() {
+            self.queue.borrow_mut().in_flight.push_back(element);
+        }
+    }
+}
+
+/// The pull half of a simulated channel, receiving delivered messages.
+struct Puller<T> {
+    current: Option<T>,
+    inbox: Rc<RefCell<VecDeque<T>>>,
+}
+
+impl<T> Pull<T> for Puller<T> {
+    #[inline]
+    fn pull(&mut self) -> &mut Option<T> {
+        self.current = self.inbox.borrow_mut().pop_front();
+        &mut self.current
+    }
+}
@@ -8,6 +8,7 @@ use std::collections::VecDeque;
 use crate::allocator::{Allocate, AllocateBuilder, Event};
 use crate::allocator::counters::Pusher as CountPusher;
 use crate::allocator::counters::Puller as CountPuller;
+use crate::allocator::credit::{Credits, Pusher as CreditPusher, Puller as CreditPuller};
 use crate::{Push, Pull, Message};
 
 /// Builder for single-threaded allocator.
@@ -32,6 +33,16 @@ impl Allocate for Thread {
         let (pusher, puller) = Thread::new_from(identifier, self.events.clone());
         (vec![Box::new(pusher)], Box::new(puller))
     }
+    fn allocate_with_budget<T: 'static>(&mut self, identifier: usize, budget: Option<usize>) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        let (pusher, puller) = Thread::new_from(identifier, self.events.clone());
+        if let Some(budget) = budget {
+            let credits = Credits::new(budget);
+            (vec![Box::new(CreditPusher::new(pusher, credits.clone()))], Box::new(CreditPuller::new(puller, credits)))
+        }
+        else {
+            (vec![Box::new(pusher)], Box::new(puller))
+        }
+    }
     fn events(&self) -> &Rc<RefCell<VecDeque<(usize, Event)>>> {
         &self.events
     }
@@ -1,21 +1,54 @@
 //! Zero-copy allocator based on TCP.
 use std::rc::Rc;
 use std::cell::RefCell;
-use std::collections::{VecDeque, HashMap};
+use std::collections::{VecDeque, HashMap, HashSet};
 use std::sync::mpsc::{Sender, Receiver};
 
 use bytes::arc::Bytes;
 
 use crate::networking::MessageHeader;
 
-use crate::{Allocate, Message, Data, Push, Pull};
-use crate::allocator::AllocateBuilder;
+use crate::{Allocate, Message, Data, Push, Pull, WorkerPanic};
+use crate::allocator::{AllocateBuilder, Priority};
 use crate::allocator::Event;
 use crate::allocator::canary::Canary;
+use crate::allocator::credit::{Credits, Pusher as CreditPusher};
 
 use super::bytes_exchange::{BytesPull, SendEndpoint, MergeQueue};
 use super::push_pull::{Pusher, PullerInner};
 
+/// The channel identifier reserved for returning credits to the senders of received messages.
+///
+/// The payload of each such message is a `(channel, count)` pair, as written by `encode_credit`.
+pub const CREDIT_CHANNEL: usize = usize::MAX;
+
+/// Writes the payload of a message returning `count` credits for `channel`.
+pub fn encode_credit<W: ::std::io::Write>(channel: usize, count: usize, writer: &mut W) -> ::std::io::Result<()> {
+    unsafe { ::abomonation::encode(&(channel, count), writer) }
+}
+
+/// Reads the `(channel, count)` payload of a message returning credits.
+pub fn decode_credit(payload: &mut [u8]) -> Option<(usize, usize)> {
+    unsafe { ::abomonation::decode::<(usize, usize)>(payload) }.map(|(credit, _)| *credit)
+}
+
+/// The channel identifier reserved for announcing the failure of a worker to remote workers.
+///
+/// The payload of each such message is a `(worker, message)` pair, as written by `encode_abort`.
+/// Processes that have announced a failure may end their streams without a clean shutdown.
+pub const ABORT_CHANNEL: usize = usize::MAX - 1;
+
+/// Writes the payload of a message announcing the failure of a worker.
+pub fn encode_abort<W: ::std::io::Write>(failure: &WorkerPanic, writer: &mut W) -> ::std::io::Result<()> {
+    unsafe { ::abomonation::encode(&(failure.worker, failure.message.clone()), writer) }
+}
+
+/// Reads the `(worker, message)` payload of a message announcing the failure of a worker.
+pub fn decode_abort(payload: &mut [u8]) -> Option<WorkerPanic> {
+    unsafe { ::abomonation::decode::<(usize, String)>(payload) }
+        .map(|((worker, message), _)| WorkerPanic { worker: *worker, message: message.clone() })
+}
+
 /// Builds an instance of a TcpAllocator.
 ///
 /// Builders are required because some of the state in a `TcpAllocator` cannot be sent between
@@ -26,7 +59,7 @@ pub struct TcpBuilder<A: AllocateBuilder> {
     inner:  A,
     index:  usize,                      // number out of peers
     peers:  usize,                      // number of peer allocators.
-    futures:   Vec<Receiver<MergeQueue>>,  // to receive queues to each network thread.
+    futures:   Vec<Receiver<MergeQueue>>,  // to receive queues to each network thread, control then data.
     promises:   Vec<Sender<MergeQueue>>,    // to send queues from each network thread.
 }
 
@@ -89,9 +122,12 @@ impl<A: AllocateBuilder> TcpBuilder<A> {
             recvs.push(queue.clone());
         }
 
-        // Extract pusher commitments.
+        // Extract pusher commitments, for control and then for data traffic.
+        let mut control_sends = Vec::with_capacity(self.peers);
         let mut sends = Vec::with_capacity(self.peers);
         for pusher in self.futures.into_iter() {
+            let control = pusher.recv().expect("Failed to receive push queue");
+            control_sends.push(Rc::new(RefCell::new(SendEndpoint::new(control))));
             let queue = pusher.recv().expect("Failed to receive push queue");
             let sendpoint = SendEndpoint::new(queue);
             sends.push(Rc::new(RefCell::new(sendpoint)));
@@ -107,8 +143,15 @@ impl<A: AllocateBuilder> TcpBuilder<A> {
             canaries: Rc::new(RefCell::new(Vec::new())),
             staged: Vec::new(),
             sends,
+            control_sends,
             recvs,
             to_local: HashMap::new(),
+            dropped: HashSet::new(),
+            retired: HashSet::new(),
+            lost: Vec::new(),
+            failure: None,
+            credits: HashMap::new(),
+            acks: Rc::new(RefCell::new(HashMap::new())),
         }
     }
 }
@@ -126,21 +169,30 @@ pub struct TcpAllocator<A: Allocate> {
 
     // sending, receiving, and responding to binary buffers.
     sends:      Vec<Rc<RefCell<SendEndpoint<MergeQueue>>>>,     // sends[x] -> goes to process x.
+    control_sends: Vec<Rc<RefCell<SendEndpoint<MergeQueue>>>>,  // as `sends`, for `Priority::Control`.
     recvs:      Vec<MergeQueue>,                                // recvs[x] <- from process x.
     to_local:   HashMap<usize, Rc<RefCell<VecDeque<Bytes>>>>,   // to worker-local typed pullers.
+    dropped:    HashSet<usize>,                                 // channels whose pullers have been dropped.
+    retired:    HashSet<usize>,                                 // channels retired before their pullers were dropped.
+    lost:       Vec<usize>,                                     // processes whose connections have failed.
+    failure:    Option<WorkerPanic>,                            // the first failure announced by a remote worker.
+
+    // flow control for channels allocated with a budget.
+    credits:    HashMap<(usize, usize), Credits>,               // (channel, remote target) -> messages outstanding.
+    acks:       Rc<RefCell<HashMap<(usize, usize), usize>>>,    // (channel, remote source) -> credits to return.
 }
 
-impl<A: Allocate> Allocate for TcpAllocator<A> {
-    fn index(&self) -> usize { self.index }
-    fn peers(&self) -> usize { self.peers }
-    fn allocate<T: Data>(&mut self, identifier: usize) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+impl<A: Allocate> TcpAllocator<A> {
+    /// Allocates a channel, with flow control if `budget` is supplied, whose messages to remote
+    /// processes are sent with `priority`.
+    fn allocate_channel<T: Data>(&mut self, identifier: usize, budget: Option<usize>, priority: Priority) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
 
         // Result list of boxed pushers.
         let mut pushes = Vec::<Box<dyn Push<Message<T>>>>::new();
 
         // Inner exchange allocations.
         let inner_peers = self.inner.peers();
-        let (mut inner_sends, inner_recv) = self.inner.allocate(identifier);
+        let (mut inner_sends, inner_recv) = self.inner.allocate_with_budget(identifier, budget);
 
         for target_index in 0 .. self.peers() {
 
@@ -162,7 +214,22 @@ impl<A: Allocate> Allocate for TcpAllocator<A> {
 
                 // create, box, and stash new process_binary pusher.
                 if process_id > self.index / inner_peers { process_id -= 1; }
-                pushes.push(Box::new(Pusher::new(header, self.sends[process_id].clone())));
+                let sends = match priority {
+                    Priority::Control => &self.control_sends,
+                    Priority::Data => &self.sends,
+                };
+                let pusher = Pusher::new(header, sends[process_id].clone());
+                if let Some(budget) = budget {
+                    let credits =
+                    self.credits
+                        .entry((identifier, target_index))
+                        .or_insert_with(|| Credits::new(budget))
+                        .clone();
+                    pushes.push(Box::new(CreditPusher::new(pusher, credits)));
+                }
+                else {
+                    pushes.push(Box::new(pusher));
+                }
             }
         }
 
@@ -174,10 +241,28 @@ impl<A: Allocate> Allocate for TcpAllocator<A> {
 
         use crate::allocator::counters::Puller as CountPuller;
         let canary = Canary::new(identifier, self.canaries.clone());
-        let puller = Box::new(CountPuller::new(PullerInner::new(inner_recv, channel, canary), identifier, self.events().clone()));
+        let mut puller = PullerInner::new(inner_recv, channel, canary);
+        if budget.is_some() {
+            puller = puller.with_acks(identifier, self.acks.clone());
+        }
+        let puller = Box::new(CountPuller::new(puller, identifier, self.events().clone()));
 
         (pushes, puller, )
     }
+}
+
+impl<A: Allocate> Allocate for TcpAllocator<A> {
+    fn index(&self) -> usize { self.index }
+    fn peers(&self) -> usize { self.peers }
+    fn allocate<T: Data>(&mut self, identifier: usize) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate_with_budget(identifier, None)
+    }
+    fn allocate_with_budget<T: Data>(&mut self, identifier: usize, budget: Option<usize>) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate_channel(identifier, budget, Priority::Data)
+    }
+    fn allocate_with_priority<T: Data>(&mut self, identifier: usize, priority: Priority) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate_channel(identifier, None, priority)
+    }
 
     // Perform preparatory work, most likely reading binary buffers from self.recv.
     #[inline(never)]
@@ -185,19 +270,30 @@ impl<A: Allocate> Allocate for TcpAllocator<A> {
 
         // Check for channels whose `Puller` has been dropped.
         let mut canaries = self.canaries.borrow_mut();
+        // The channel may still hold data, for example if its dataflow was dropped
+        // while peers were still sending. We discard it, and any later arrivals.
         for dropped_channel in canaries.drain(..) {
-            let dropped =
             self.to_local
                 .remove(&dropped_channel)
                 .expect("non-existent channel dropped");
-            assert!(dropped.borrow().is_empty());
+            // Channels already retired will see no further arrivals.
+            if !self.retired.remove(&dropped_channel) {
+                self.dropped.insert(dropped_channel);
+            }
+            self.credits.retain(|&(channel, _), _| channel != dropped_channel);
         }
         ::std::mem::drop(canaries);
 
         self.inner.receive();
 
-        for recv in self.recvs.iter_mut() {
+        let my_process = self.index / self.inner.peers();
+        for (index, recv) in self.recvs.iter_mut().enumerate() {
             recv.drain_into(&mut self.staged);
+            // Note processes whose connections have failed; `recvs` skips our own process.
+            let process = if index < my_process { index } else { index + 1 };
+            if recv.is_disconnected() && !self.lost.contains(&process) {
+                self.lost.push(process);
+            }
         }
 
         let mut events = self.inner.events().borrow_mut();
@@ -210,9 +306,33 @@ impl<A: Allocate> Allocate for TcpAllocator<A> {
 
                 if let Some(header) = MessageHeader::try_read(&mut bytes[..]) {
 
-                    // Get the header and payload, ditch the header.
+                    // Get the header and payload; pullers ditch the header.
                     let mut peel = bytes.extract_to(header.required_bytes());
-                    let _ = peel.extract_to(40);
+
+                    // Return credits to pushers of channels with a budget.
+                    if header.channel == CREDIT_CHANNEL {
+                        let payload = &mut peel[::std::mem::size_of::<MessageHeader>() ..];
+                        if let Some((channel, count)) = decode_credit(payload) {
+                            if let Some(credits) = self.credits.get(&(channel, header.source)) {
+                                credits.refund(count);
+                            }
+                        }
+                        continue;
+                    }
+
+                    // Note the first failure announced by a remote worker.
+                    if header.channel == ABORT_CHANNEL {
+                        if self.failure.is_none() {
+                            let payload = &mut peel[::std::mem::size_of::<MessageHeader>() ..];
+                            self.failure = decode_abort(payload);
+                        }
+                        continue;
+                    }
+
+                    // Discard messages for channels that have been dropped.
+                    if self.dropped.contains(&header.channel) {
+                        continue;
+                    }
 
                     // Increment message count for channel.
                     events.push_back((header.channel, Event::Pushed(1)));
@@ -234,8 +354,31 @@ impl<A: Allocate> Allocate for TcpAllocator<A> {
 
     // Perform postparatory work, most likely sending un-full binary buffers.
     fn release(&mut self) {
+
+        // Return credits for messages pulled from other processes.
+        let inner_peers = self.inner.peers();
+        for ((channel, source), count) in self.acks.borrow_mut().drain() {
+            let mut process_id = source / inner_peers;
+            if process_id > self.index / inner_peers { process_id -= 1; }
+            let header = MessageHeader {
+                channel:    CREDIT_CHANNEL,
+                source:     self.index,
+                target:     source,
+                length:     ::std::mem::size_of::<(usize, usize)>(),
+                seqno:      0,
+            };
+            let mut send = self.control_sends[process_id].borrow_mut();
+            {
+                let mut bytes = send.reserve(header.required_bytes());
+                let writer = &mut bytes;
+                header.write_to(writer).expect("failed to write header!");
+                encode_credit(channel, count, writer).expect("failed to write credit!");
+            }
+            send.make_valid(header.required_bytes());
+        }
+
         // Publish outgoing byte ledgers.
-        for send in self.sends.iter_mut() {
+        for send in self.control_sends.iter_mut().chain(self.sends.iter_mut()) {
             send.borrow_mut().publish();
         }
 
@@ -254,4 +397,58 @@ impl<A: Allocate> Allocate for TcpAllocator<A> {
     fn await_events(&self, duration: Option<std::time::Duration>) {
         self.inner.await_events(duration);
     }
+    fn lost_processes(&self) -> &[usize] {
+        &self.lost[..]
+    }
+    fn announce_failure(&mut self, failure: &WorkerPanic) {
+        let length = ::abomonation::measure(&(failure.worker, failure.message.clone()));
+        let inner_peers = self.inner.peers();
+        let my_process = self.index / inner_peers;
+        for target in 0 .. self.peers {
+            let mut process_id = target / inner_peers;
+            if process_id == my_process { continue; }
+            if process_id > my_process { process_id -= 1; }
+            let header = MessageHeader {
+                channel:    ABORT_CHANNEL,
+                source:     self.index,
+                target,
+                length,
+                seqno:      0,
+            };
+            let mut send = self.control_sends[process_id].borrow_mut();
+            {
+                let mut bytes = send.reserve(header.required_bytes());
+                let writer = &mut bytes;
+                header.write_to(writer).expect("failed to write header!");
+                encode_abort(failure, writer).expect("failed to write failure!");
+            }
+            send.make_valid(header.required_bytes());
+        }
+        for send in self.control_sends.iter_mut().chain(self.sends.iter_mut()) {
+            send.borrow_mut().publish();
+        }
+    }
+    fn remote_failure(&self) -> Option<&WorkerPanic> {
+        self.failure.as_ref()
+    }
+    fn retire_channels(&mut self, channels: &[usize]) {
+        for channel in channels.iter() {
+            if !self.dropped.remove(channel) && self.to_local.contains_key(channel) {
+                self.retired.insert(*channel);
+            }
+        }
+    }
+    fn queued_bytes(&self) -> Vec<(usize, usize, usize)> {
+        // Each of `sends`, `control_sends`, and `recvs` skips our own process.
+        let my_process = self.index / self.inner.peers();
+        self.recvs
+            .iter()
+            .enumerate()
+            .map(|(index, recv)| {
+                let process = if index < my_process { index } else { index + 1 };
+                let sent = self.sends[index].borrow().queued_bytes() + self.control_sends[index].borrow().queued_bytes();
+                (process, sent, recv.queued_bytes())
+            })
+            .collect()
+    }
 }
\ No newline at end of file
@@ -2,7 +2,7 @@
 
 use std::rc::Rc;
 use std::cell::RefCell;
-use std::collections::{VecDeque, HashMap};
+use std::collections::{VecDeque, HashMap, HashSet};
 use std::sync::mpsc::{Sender, Receiver};
 
 use bytes::arc::Bytes;
@@ -84,6 +84,8 @@ impl ProcessBuilder {
             sends,
             recvs,
             to_local: HashMap::new(),
+            dropped: HashSet::new(),
+            retired: HashSet::new(),
             // _signal: self.signal,
         }
     }
@@ -114,6 +116,8 @@ pub struct ProcessAllocator {
     sends:      Vec<Rc<RefCell<SendEndpoint<MergeQueue>>>>, // sends[x] -> goes to thread x.
     recvs:      Vec<MergeQueue>,                            // recvs[x] <- from thread x.
     to_local:   HashMap<usize, Rc<RefCell<VecDeque<Bytes>>>>,          // to worker-local typed pullers.
+    dropped:    HashSet<usize>,                                        // channels whose pullers have been dropped.
+    retired:    HashSet<usize>,                                        // channels retired before their pullers were dropped.
 }
 
 impl Allocate for ProcessAllocator {
@@ -157,12 +161,16 @@ impl Allocate for ProcessAllocator {
 
         // Check for channels whose `Puller` has been dropped.
         let mut canaries = self.canaries.borrow_mut();
+        // The channel may still hold data, for example if its dataflow was dropped
+        // while peers were still sending. We discard it, and any later arrivals.
         for dropped_channel in canaries.drain(..) {
-            let dropped =
             self.to_local
                 .remove(&dropped_channel)
                 .expect("non-existent channel dropped");
-            assert!(dropped.borrow().is_empty());
+            // Channels already retired will see no further arrivals.
+            if !self.retired.remove(&dropped_channel) {
+                self.dropped.insert(dropped_channel);
+            }
         }
         std::mem::drop(canaries);
 
@@ -184,6 +192,11 @@ impl Allocate for ProcessAllocator {
                     let mut peel = bytes.extract_to(header.required_bytes());
                     let _ = peel.extract_to(40);
 
+                    // Discard messages for channels that have been dropped.
+                    if self.dropped.contains(&header.channel) {
+                        continue;
+                    }
+
                     // Increment message count for channel.
                     events.push_back((header.channel, Event::Pushed(1)));
 
@@ -219,6 +232,14 @@ impl Allocate for ProcessAllocator {
         // }
     }
 
+    fn retire_channels(&mut self, channels: &[usize]) {
+        for channel in channels.iter() {
+            if !self.dropped.remove(channel) && self.to_local.contains_key(channel) {
+                self.retired.insert(*channel);
+            }
+        }
+    }
+
     fn events(&self) -> &Rc<RefCell<VecDeque<(usize, Event)>>> {
         &self.events
     }
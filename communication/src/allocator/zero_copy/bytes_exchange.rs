@@ -21,7 +21,7 @@ pub trait BytesPull {
     fn drain_into(&mut self, vec: &mut Vec<Bytes>);
 }
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 /// An unbounded queue of bytes intended for point-to-point communication
 /// between threads. Cloning returns another handle to the same queue.
 ///
@@ -31,6 +31,8 @@ pub struct MergeQueue {
     queue: Arc<Mutex<VecDeque<Bytes>>>, // queue of bytes.
     buzzer: crate::buzzer::Buzzer,  // awakens receiver thread.
     panic: Arc<AtomicBool>,
+    disconnected: Arc<AtomicBool>,  // remote source of data lost.
+    bytes: Arc<AtomicUsize>,        // bytes in the queue.
 }
 
 impl MergeQueue {
@@ -40,19 +42,70 @@ impl MergeQueue {
             queue: Arc::new(Mutex::new(VecDeque::new())),
             buzzer,
             panic: Arc::new(AtomicBool::new(false)),
+            disconnected: Arc::new(AtomicBool::new(false)),
+            bytes: Arc::new(AtomicUsize::new(0)),
         }
     }
+    /// The number of bytes in the queue, awaiting its receiver.
+    pub fn queued_bytes(&self) -> usize {
+        self.bytes.load(Ordering::SeqCst)
+    }
     /// Indicates that all input handles to the queue have dropped.
     pub fn is_complete(&self) -> bool {
         if self.panic.load(Ordering::SeqCst) { panic!("MergeQueue poisoned."); }
         Arc::strong_count(&self.queue) == 1 && self.queue.lock().expect("Failed to acquire lock").is_empty()
     }
+    /// Marks the queue as disconnected from the remote process it represents.
+    ///
+    /// Unlike a panic, this does not poison the queue; data already in the queue
+    /// remain available, and users can test for the condition with `is_disconnected`.
+    pub fn disconnect(&self) {
+        self.disconnected.store(true, Ordering::SeqCst);
+        self.buzzer.buzz();
+    }
+    /// Indicates that the remote process the queue represents has been lost.
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected.load(Ordering::SeqCst)
+    }
+    /// Indicates that a handle to the queue was dropped by a panicking thread.
+    pub fn is_poisoned(&self) -> bool {
+        self.panic.load(Ordering::SeqCst)
+    }
+    /// Moves the contents of the queue into `vec`, even if the queue is poisoned.
+    ///
+    /// This allows data enqueued before a panic, for example announcing the panic, to be delivered.
+    pub fn drain_remaining(&mut self, vec: &mut Vec<Bytes>) {
+        // try to acquire lock without going to sleep (Rust's lock() might yield)
+        let mut lock_ok = self.queue.try_lock();
+        while let Result::Err(::std::sync::TryLockError::WouldBlock) = lock_ok {
+            lock_ok = self.queue.try_lock();
+        }
+        let mut queue = lock_ok.expect("MergeQueue mutex poisoned.");
+
+        let drained = queue.iter().map(|bytes| bytes.len()).sum();
+        self.bytes.fetch_sub(drained, Ordering::SeqCst);
+        vec.extend(queue.drain(..));
+    }
 }
 
 impl BytesPush for MergeQueue {
     fn extend<I: IntoIterator<Item=Bytes>>(&mut self, iterator: I) {
-
         if self.panic.load(Ordering::SeqCst) { panic!("MergeQueue poisoned."); }
+        self.append(iterator);
+    }
+}
+
+impl MergeQueue {
+    /// Appends to the queue, unless it is poisoned, in which case the data are discarded.
+    ///
+    /// This allows data to be offered to a thread that may have panicked, without panicking.
+    pub fn extend_or_discard<I: IntoIterator<Item=Bytes>>(&mut self, iterator: I) {
+        if !self.is_poisoned() {
+            self.append(iterator);
+        }
+    }
+
+    fn append<I: IntoIterator<Item=Bytes>>(&mut self, iterator: I) {
 
         // try to acquire lock without going to sleep (Rust's lock() might yield)
         let mut lock_ok = self.queue.try_lock();
@@ -61,7 +114,7 @@ impl BytesPush for MergeQueue {
         }
         let mut queue = lock_ok.expect("MergeQueue mutex poisoned.");
 
-        let mut iterator = iterator.into_iter();
+        let mut iterator = iterator.into_iter().inspect(|bytes| { self.bytes.fetch_add(bytes.len(), Ordering::SeqCst); });
         let mut should_ping = false;
         if let Some(bytes) = iterator.next() {
             let mut tail = if let Some(mut tail) = queue.pop_back() {
@@ -94,15 +147,7 @@ impl BytesPush for MergeQueue {
 impl BytesPull for MergeQueue {
     fn drain_into(&mut self, vec: &mut Vec<Bytes>) {
         if self.panic.load(Ordering::SeqCst) { panic!("MergeQueue poisoned."); }
-
-        // try to acquire lock without going to sleep (Rust's lock() might yield)
-        let mut lock_ok = self.queue.try_lock();
-        while let Result::Err(::std::sync::TryLockError::WouldBlock) = lock_ok {
-            lock_ok = self.queue.try_lock();
-        }
-        let mut queue = lock_ok.expect("MergeQueue mutex poisoned.");
-
-        vec.extend(queue.drain(..));
+        self.drain_remaining(vec);
     }
 }
 
@@ -111,13 +156,11 @@ impl BytesPull for MergeQueue {
 impl Drop for MergeQueue {
     fn drop(&mut self) {
         // Propagate panic information, to distinguish between clean and unclean shutdown.
+        // Other handles observe the panic when they next use the queue, rather than on drop,
+        // so that communication threads can retire the queues of panicked workers.
         if ::std::thread::panicking() {
             self.panic.store(true, Ordering::SeqCst);
         }
-        else {
-            // TODO: Perhaps this aggressive ordering can relax orderings elsewhere.
-            if self.panic.load(Ordering::SeqCst) { panic!("MergeQueue poisoned."); }
-        }
         // Drop the queue before pinging.
         self.queue = Arc::new(Mutex::new(VecDeque::new()));
         self.buzzer.buzz();
@@ -172,6 +215,13 @@ impl<P: BytesPush> SendEndpoint<P> {
     }
 }
 
+impl SendEndpoint<MergeQueue> {
+    /// The number of bytes written but not yet taken by the receiver of the queue.
+    pub fn queued_bytes(&self) -> usize {
+        self.buffer.valid_len() + self.send.queued_bytes()
+    }
+}
+
 impl<P: BytesPush> Drop for SendEndpoint<P> {
     fn drop(&mut self) {
         self.send_buffer();
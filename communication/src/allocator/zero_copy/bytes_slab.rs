@@ -34,6 +34,10 @@ impl BytesSlab {
     pub fn valid(&mut self) -> &mut [u8] {
         &mut self.buffer[..self.valid]
     }
+    /// The length of the valid region of the slab.
+    pub fn valid_len(&self) -> usize {
+        self.valid
+    }
     /// Marks the next `bytes` bytes as valid.
     pub fn make_valid(&mut self, bytes: usize) {
         self.valid += bytes;
@@ -0,0 +1,192 @@
+//! Compression of the data exchanged between processes.
+//!
+//! Each process proposes the compression it is configured with as part of its handshake, and a
+//! connection is compressed only if the processes at both of its ends propose the same method.
+//! On a compressed connection, each batch of messages written by the send thread becomes one
+//! frame: the length of the compressed data and the length of the batch, each a little-endian
+//! `u64`, followed by the compressed data. Batches larger than `MAX_FRAME` bytes are split
+//! across frames. The receive thread decompresses frames as it reads them, before carving out
+//! messages, and rejects frames that claim to be larger.
+//!
+//! The `Compression` type is always available; compressing connections requires the
+//! `compression` feature.
+
+/// Methods of compressing the data exchanged between processes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// The LZ4 block format, which favors speed over compression ratio.
+    Lz4,
+}
+
+impl Compression {
+    /// The code with which a process proposes `compression` in its handshake.
+    pub fn code(compression: Option<Compression>) -> u64 {
+        match compression {
+            None => 0,
+            Some(Compression::Lz4) => 1,
+        }
+    }
+    /// The compression proposed with `code`, if any is known.
+    pub fn from_code(code: u64) -> Option<Compression> {
+        match code {
+            1 => Some(Compression::Lz4),
+            _ => None,
+        }
+    }
+    /// The compression of a connection, given the proposals of the processes at its ends.
+    pub fn agree(mine: Option<Compression>, theirs: Option<Compression>) -> Option<Compression> {
+        if mine == theirs { mine } else { None }
+    }
+}
+
+/// The largest number of uncompressed bytes in a frame.
+///
+/// This is sixteen times the size of the buffers into which receive threads read messages, and
+/// bounds the allocation a corrupt or hostile frame header can cause.
+pub const MAX_FRAME: usize = 1 << 24;
+
+#[cfg(feature = "compression")]
+pub use self::stream::Compressed;
+
+#[cfg(feature = "compression")]
+mod stream {
+
+    use std::io::{self, Read, Write};
+    use std::net::Shutdown;
+
+    use logging_core::Logger;
+
+    use crate::logging::{CommunicationEvent, CommunicationSetup, CompressionEvent};
+    use crate::networking::Stream;
+    use super::{Compression, MAX_FRAME};
+
+    /// The bytes preceding the compressed data of each frame.
+    const FRAME_HEADER: usize = 16;
+
+    /// A stream that compresses the data written to it and decompresses the data read from it.
+    ///
+    /// Each call to `write` is compressed as one frame, so writes should be batched, for example
+    /// by a `BufWriter`. Each frame written or read is logged as a `CompressionEvent`.
+    pub struct Compressed<S: Stream> {
+        stream: S,
+        compression: Compression,
+        // Compressed frame being written.
+        frame: Vec<u8>,
+        // Data read but not yet decompressed, which may end with a partial frame.
+        pending: Vec<u8>,
+        // Data decompressed but not yet read, from `decompressed[consumed..]`.
+        decompressed: Vec<u8>,
+        consumed: usize,
+        logger: Option<Logger<CommunicationEvent, CommunicationSetup>>,
+    }
+
+    fn invalid<E: ::std::fmt::Display>(error: E) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+    }
+
+    fn read_length(bytes: &[u8]) -> usize {
+        let mut length = [0u8; 8];
+        length.copy_from_slice(&bytes[.. 8]);
+        u64::from_le_bytes(length) as usize
+    }
+
+    impl<S: Stream> Compressed<S> {
+        /// Compresses the data exchanged over `stream`, logging frames to `logger`.
+        pub fn new(stream: S, compression: Compression, logger: Option<Logger<CommunicationEvent, CommunicationSetup>>) -> Self {
+            Compressed {
+                stream,
+                compression,
+                frame: Vec::new(),
+                pending: Vec::new(),
+                decompressed: Vec::new(),
+                consumed: 0,
+                logger,
+            }
+        }
+
+        /// Decompresses the first frame of `pending`, if it is complete.
+        fn decompress_frame(&mut self) -> io::Result<bool> {
+            if self.pending.len() < FRAME_HEADER { return Ok(false); }
+            let compressed = read_length(&self.pending[.. 8]);
+            let length = read_length(&self.pending[8 .. 16]);
+            let bound = match self.compression {
+                Compression::Lz4 => ::lz4_flex::block::get_maximum_output_size(MAX_FRAME),
+            };
+            if length > MAX_FRAME || compressed > bound {
+                return Err(invalid(format!("frame of {} bytes compressed to {} exceeds the limit of {}", length, compressed, MAX_FRAME)));
+            }
+            if self.pending.len() < FRAME_HEADER + compressed { return Ok(false); }
+
+            self.decompressed.clear();
+            self.decompressed.resize(length, 0);
+            self.consumed = 0;
+            let data = &self.pending[FRAME_HEADER .. FRAME_HEADER + compressed];
+            let written = match self.compression {
+                Compression::Lz4 => ::lz4_flex::block::decompress_into(data, &mut self.decompressed[..]).map_err(invalid)?,
+            };
+            if written != length {
+                return Err(invalid(format!("frame decompressed to {} bytes rather than {}", written, length)));
+            }
+            self.pending.drain(.. FRAME_HEADER + compressed);
+            if let Some(logger) = self.logger.as_mut() {
+                logger.log(CompressionEvent { is_send: false, compressed: FRAME_HEADER + compressed, uncompressed: length });
+            }
+            Ok(true)
+        }
+    }
+
+    impl<S: Stream> Read for Compressed<S> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while self.consumed == self.decompressed.len() {
+                if !self.decompress_frame()? {
+                    let start = self.pending.len();
+                    self.pending.resize(start + (1 << 16), 0);
+                    let read = self.stream.read(&mut self.pending[start ..]);
+                    self.pending.truncate(start + *read.as_ref().unwrap_or(&0));
+                    // The stream has ended, perhaps within a frame.
+                    if read? == 0 { return Ok(0); }
+                }
+            }
+            let available = &self.decompressed[self.consumed ..];
+            let length = ::std::cmp::min(available.len(), buf.len());
+            buf[.. length].copy_from_slice(&available[.. length]);
+            self.consumed += length;
+            Ok(length)
+        }
+    }
+
+    impl<S: Stream> Write for Compressed<S> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.is_empty() { return Ok(0); }
+            // Larger writes are completed by subsequent calls, as by `write_all`.
+            let buf = &buf[.. ::std::cmp::min(buf.len(), MAX_FRAME)];
+            let bound = match self.compression {
+                Compression::Lz4 => ::lz4_flex::block::get_maximum_output_size(buf.len()),
+            };
+            self.frame.clear();
+            self.frame.resize(FRAME_HEADER + bound, 0);
+            let compressed = match self.compression {
+                Compression::Lz4 => ::lz4_flex::block::compress_into(buf, &mut self.frame[FRAME_HEADER ..]).map_err(invalid)?,
+            };
+            self.frame[.. 8].copy_from_slice(&(compressed as u64).to_le_bytes());
+            self.frame[8 .. 16].copy_from_slice(&(buf.len() as u64).to_le_bytes());
+            self.stream.write_all(&self.frame[.. FRAME_HEADER + compressed])?;
+            if let Some(logger) = self.logger.as_mut() {
+                logger.log(CompressionEvent { is_send: true, compressed: FRAME_HEADER + compressed, uncompressed: buf.len() });
+            }
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            self.stream.flush()
+        }
+    }
+
+    impl<S: Stream> Stream for Compressed<S> {
+        fn try_clone(&self) -> io::Result<Self> {
+            Ok(Compressed::new(self.stream.try_clone()?, self.compression, self.logger.clone()))
+        }
+        fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+            self.stream.shutdown(how)
+        }
+    }
+}
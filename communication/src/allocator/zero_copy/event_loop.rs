@@ -0,0 +1,355 @@
+//! Networking threads that each serve many connections, through an event loop.
+//!
+//! By default, each connection to a remote process is served by a send thread and a receive
+//! thread (see `tcp`). Here, connections are instead divided among a few threads, each of which
+//! waits on a `mio::Poll` for its sockets to become readable or writable, or for local workers to
+//! enqueue data. Workers interact with these threads through the same `MergeQueue`s, and the
+//! same protocol is spoken over the sockets, so processes using either approach interoperate.
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Sender, Receiver};
+
+use mio::{Events, Interest, Poll, Token, Waker};
+
+use bytes::arc::Bytes;
+use logging_core::Logger;
+
+use crate::allocator::Priority;
+use crate::buzzer::Buzzer;
+use crate::logging::{CommunicationEvent, CommunicationSetup, MessageEvent, StateEvent};
+use crate::networking::MessageHeader;
+
+use super::allocator::{ABORT_CHANNEL, CREDIT_CHANNEL};
+use super::bytes_exchange::MergeQueue;
+use super::bytes_slab::BytesSlab;
+use super::tcp::log_credit;
+
+/// Identifies wakeups from local workers, rather than from sockets.
+const WAKER: Token = Token(usize::MAX);
+
+/// The number of unwritten bytes above which data traffic is left in its queues, so that
+/// control traffic enqueued later may still be written ahead of it.
+const BACKLOG: usize = 1 << 20;
+
+/// A connection to a remote process, and the means to exchange queues with local workers.
+pub struct Peer {
+    /// The index of the remote process.
+    pub remote: usize,
+    /// The connection to the remote process.
+    pub stream: TcpStream,
+    /// Where to send the queue through which each local worker sends to the remote process.
+    pub sources: Vec<Sender<MergeQueue>>,
+    /// Where to receive the queue through which each local worker receives from the remote process.
+    pub targets: Vec<Receiver<MergeQueue>>,
+}
+
+/// The state of a connection served by an event loop.
+struct Connection {
+    process: usize,
+    remote: usize,
+    stream: mio::net::TcpStream,
+    send_logger: Option<Logger<CommunicationEvent, CommunicationSetup>>,
+    recv_logger: Option<Logger<CommunicationEvent, CommunicationSetup>>,
+
+    // Queues from local workers, until they complete.
+    sources: Vec<(Priority, MergeQueue)>,
+    stash: Vec<Bytes>,
+    // Data drained from `sources`, from `outgoing[written..]`, not yet written.
+    outgoing: Vec<u8>,
+    written: usize,
+    // Set if writing failed, in which case we discard all data.
+    lost: bool,
+    // Set if a local worker has panicked, in which case we end the stream without a clean shutdown.
+    failed: bool,
+    // Set once the zero length header has been staged in `outgoing`.
+    farewell: bool,
+    // Set once the stream has been shut down for writing.
+    sent: bool,
+
+    // Queues to local workers, indexed by target worker less `worker_offset`.
+    targets: Vec<MergeQueue>,
+    worker_offset: usize,
+    buffer: BytesSlab,
+    stageds: Vec<Vec<Bytes>>,
+    // Set once the zero length header has been read.
+    ended: bool,
+    // Set once the stream has ended.
+    received: bool,
+}
+
+impl Connection {
+
+    /// Moves data from local workers to the stream, until the stream would block.
+    ///
+    /// Follows `tcp::send_loop`: once all sources are complete, the zero length header is
+    /// written and the stream shut down for writing, unless a local worker has panicked.
+    /// Control traffic is drained first, and data traffic only while the unwritten backlog
+    /// is small, except from the sources of panicked workers, which are drained and retired.
+    fn send(&mut self, tolerant: bool, closing: &AtomicBool) {
+
+        if self.sent { return; }
+
+        let mut retired = Vec::new();
+        for (index, (priority, source)) in self.sources.iter_mut().enumerate() {
+            if source.is_poisoned() { retired.push(index); }
+            if *priority == Priority::Control || source.is_poisoned() {
+                source.drain_remaining(&mut self.stash);
+            }
+        }
+        if self.outgoing.len() - self.written < BACKLOG {
+            for (_, source) in self.sources.iter_mut().filter(|(priority, _)| *priority == Priority::Data) {
+                source.drain_remaining(&mut self.stash);
+            }
+        }
+        for index in retired.into_iter().rev() {
+            self.sources.remove(index);
+            self.failed = true;
+        }
+
+        if self.lost {
+            self.stash.clear();
+        }
+        else {
+            for mut bytes in self.stash.drain(..) {
+                if let Some(logger) = self.send_logger.as_mut() {
+                    let mut offset = 0;
+                    while let Some(header) = MessageHeader::try_read(&mut bytes[offset..]) {
+                        logger.log(MessageEvent { is_send: true, header, });
+                        if header.channel == CREDIT_CHANNEL {
+                            log_credit(logger, true, header, &mut bytes[offset..]);
+                        }
+                        offset += header.required_bytes();
+                    }
+                }
+                self.outgoing.extend_from_slice(&bytes[..]);
+            }
+        }
+        // Sources complete once drained and dropped. This is tested after each drain, as the
+        // wakeups of a worker's last data and of its drop may both be consumed by one poll.
+        self.sources.retain(|(_, source)| source.is_poisoned() || !source.is_complete());
+
+        if self.sources.is_empty() && !self.lost && !self.failed && !self.farewell {
+            let header = MessageHeader { channel: 0, source: 0, target: 0, length: 0, seqno: 0 };
+            header.write_to(&mut self.outgoing).expect("failed to write to Vec<u8>");
+            if let Some(logger) = self.send_logger.as_mut() {
+                logger.log(MessageEvent { is_send: true, header });
+            }
+            self.farewell = true;
+        }
+
+        while !self.lost && self.written < self.outgoing.len() {
+            match self.stream.write(&self.outgoing[self.written ..]) {
+                Ok(0) => self.fail_write(tolerant, closing, "wrote zero bytes".to_owned()),
+                Ok(written) => self.written += written,
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => { },
+                Err(error) => self.fail_write(tolerant, closing, error.to_string()),
+            }
+        }
+        if self.written == self.outgoing.len() {
+            self.outgoing.clear();
+            self.written = 0;
+        }
+
+        if self.sources.is_empty() && (self.lost || self.outgoing.is_empty()) {
+            if !self.lost {
+                // Errors are reported by the remote process, which sees the end of the stream.
+                let _ = self.stream.shutdown(Shutdown::Write);
+            }
+            self.sent = true;
+            if let Some(logger) = self.send_logger.as_mut() {
+                logger.log(StateEvent { send: true, process: self.process, remote: self.remote, start: false, });
+            }
+        }
+    }
+
+    fn fail_write(&mut self, tolerant: bool, closing: &AtomicBool, error: String) {
+        if !tolerant && !closing.load(Ordering::SeqCst) {
+            panic!("Write failure to process {}: {}", self.remote, error);
+        }
+        self.lost = true;
+        self.outgoing.clear();
+        self.written = 0;
+        // Also end the receiving half, which will report the lost process.
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+
+    /// Moves data from the stream to local workers, until the stream would block.
+    ///
+    /// Follows `tcp::recv_loop`, including in how the end of the stream is handled.
+    fn recv(&mut self, tolerant: bool, closing: &AtomicBool) {
+
+        while !self.received {
+
+            self.buffer.ensure_capacity(1);
+            let read = match self.stream.read(self.buffer.empty()) {
+                Ok(read) => read,
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    if !closing.load(Ordering::SeqCst) {
+                        println!("Error: {:?}", error);
+                    }
+                    0
+                },
+            };
+
+            if read == 0 {
+                if !self.ended {
+                    // The stream ended without a clean shutdown.
+                    if !tolerant && !closing.load(Ordering::SeqCst) {
+                        panic!("Stream from process {} ended without clean shutdown.", self.remote);
+                    }
+                    for target in self.targets.iter() {
+                        target.disconnect();
+                    }
+                }
+                self.received = true;
+                if let Some(logger) = self.recv_logger.as_mut() {
+                    logger.log(StateEvent { send: false, process: self.process, remote: self.remote, start: false, });
+                }
+                break;
+            }
+            if self.ended {
+                panic!("Clean shutdown followed by data.");
+            }
+            self.buffer.make_valid(read);
+
+            while let Some(header) = MessageHeader::try_read(self.buffer.valid()) {
+
+                let mut bytes = self.buffer.extract(header.required_bytes());
+                if let Some(logger) = self.recv_logger.as_mut() {
+                    logger.log(MessageEvent { is_send: false, header, });
+                    if header.channel == CREDIT_CHANNEL {
+                        log_credit(logger, false, header, &mut bytes[..]);
+                    }
+                }
+
+                if header.channel == ABORT_CHANNEL {
+                    closing.store(true, Ordering::SeqCst);
+                }
+
+                if header.length > 0 {
+                    self.stageds[header.target - self.worker_offset].push(bytes);
+                }
+                else {
+                    self.ended = true;
+                    if !self.buffer.valid().is_empty() {
+                        panic!("Clean shutdown followed by data.");
+                    }
+                }
+            }
+
+            for (index, staged) in self.stageds.iter_mut().enumerate() {
+                self.targets[index].extend_or_discard(staged.drain(..));
+            }
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.sent && self.received
+    }
+}
+
+/// Serves `peers` until each has been shut down in both directions.
+///
+/// Each process has `threads` workers. A panic, for example on the unexpected end of a stream
+/// when `closing` is not set and the loss of processes is not `tolerant`, takes down all of the
+/// connections the event loop serves.
+pub fn event_loop(
+    peers: Vec<Peer>,
+    process: usize,
+    threads: usize,
+    tolerant: bool,
+    closing: Arc<AtomicBool>,
+    log_sender: &dyn Fn(CommunicationSetup)->Option<Logger<CommunicationEvent, CommunicationSetup>>)
+-> io::Result<()>
+{
+    let mut poll = Poll::new()?;
+    let waker = Arc::new(Waker::new(poll.registry(), WAKER)?);
+
+    // Offer send queues to all workers before awaiting any of their receive queues.
+    let sources: Vec<Vec<(Priority, MergeQueue)>> = peers.iter().map(|peer| {
+        peer.sources.iter().flat_map(|source| {
+            [Priority::Control, Priority::Data].iter().map(|&priority| {
+                let queue = MergeQueue::new(Buzzer::from_waker(waker.clone()));
+                source.send(queue.clone()).expect("failed to send MergeQueue");
+                (priority, queue)
+            }).collect::<Vec<_>>()
+        }).collect()
+    }).collect();
+
+    let mut connections = Vec::with_capacity(peers.len());
+    for (index, (peer, sources)) in peers.into_iter().zip(sources).enumerate() {
+
+        let remote = peer.remote;
+        let targets: Vec<MergeQueue> = peer.targets.into_iter().map(|x| x.recv().expect("Failed to receive MergeQueue")).collect();
+
+        peer.stream.set_nonblocking(true)?;
+        let mut stream = mio::net::TcpStream::from_std(peer.stream);
+        poll.registry().register(&mut stream, Token(index), Interest::READABLE | Interest::WRITABLE)?;
+
+        let mut send_logger = log_sender(CommunicationSetup { process, sender: true, remote: Some(remote) });
+        let mut recv_logger = log_sender(CommunicationSetup { process, sender: false, remote: Some(remote) });
+        if let Some(logger) = send_logger.as_mut() {
+            logger.log(StateEvent { send: true, process, remote, start: true, });
+        }
+        if let Some(logger) = recv_logger.as_mut() {
+            logger.log(StateEvent { send: false, process, remote, start: true, });
+        }
+
+        connections.push(Connection {
+            process,
+            remote,
+            stream,
+            send_logger,
+            recv_logger,
+            sources,
+            stash: Vec::new(),
+            outgoing: Vec::new(),
+            written: 0,
+            lost: false,
+            failed: false,
+            farewell: false,
+            sent: false,
+            stageds: (0 .. targets.len()).map(|_| Vec::new()).collect(),
+            worker_offset: threads * process,
+            targets,
+            buffer: BytesSlab::new(20),
+            ended: false,
+            received: false,
+        });
+    }
+
+    let mut events = Events::with_capacity(1024);
+    // Initially, every connection may have data to read.
+    let mut readable: Vec<usize> = (0 .. connections.len()).collect();
+    while !connections.iter().all(|connection| connection.done()) {
+
+        for index in readable.drain(..) {
+            connections[index].recv(tolerant, &closing);
+        }
+        // Local workers may have enqueued data for any connection, and sending is cheap to attempt.
+        for connection in connections.iter_mut() {
+            connection.send(tolerant, &closing);
+        }
+
+        if !connections.iter().all(|connection| connection.done()) {
+            match poll.poll(&mut events, None) {
+                Ok(()) => { },
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => { },
+                Err(error) => return Err(error),
+            }
+            for event in events.iter() {
+                if event.token() != WAKER && (event.is_readable() || event.is_read_closed() || event.is_error()) {
+                    readable.push(event.token().0);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
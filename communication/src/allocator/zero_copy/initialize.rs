@@ -1,12 +1,24 @@
 //! Network initialization.
 
+use std::net::Shutdown;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 // use crate::allocator::Process;
 use crate::allocator::process::ProcessBuilder;
-use crate::networking::create_sockets;
+use crate::networking::{create_sockets, Retry, Stream, Transport};
 use super::tcp::{send_loop, recv_loop};
+use super::tls::TlsConfig;
+use super::compression::Compression;
+#[cfg(feature = "compression")]
+use super::compression::Compressed;
 use super::allocator::{TcpBuilder, new_vector};
 
+/// The time `CommsGuard::shutdown` allows remote processes to end their streams.
+pub const SHUTDOWN_GRACE: Duration = Duration::from_secs(1);
+
 /// Join handles for send and receive threads.
 ///
 /// On drop, the guard joins with each of the threads to ensure that they complete
@@ -14,6 +26,38 @@ use super::allocator::{TcpBuilder, new_vector};
 pub struct CommsGuard {
     send_guards: Vec<::std::thread::JoinHandle<()>>,
     recv_guards: Vec<::std::thread::JoinHandle<()>>,
+    // Indicates to communication threads that the ends of their streams, and failures to write
+    // to them, are expected. Set on shutdown, and by receive threads told of a remote failure.
+    closing: Arc<AtomicBool>,
+    // The number of receive threads that have exited.
+    finished: Arc<AtomicUsize>,
+    // Shut down the read halves of the receive threads' streams.
+    recv_shutdowns: Vec<Box<dyn Fn()+Send>>,
+}
+
+impl CommsGuard {
+    /// Tears down connections, once local workers have stopped.
+    ///
+    /// Send threads are joined once they have sent their remaining data. Receive threads then
+    /// have `SHUTDOWN_GRACE` to observe the ends of their streams, which remote processes that
+    /// are also shutting down should provide, after which their streams are shut down and the
+    /// threads joined regardless. Streams that end are not reported as failures.
+    pub fn shutdown(&mut self) {
+        for handle in self.send_guards.drain(..) {
+            handle.join().expect("Send thread panic");
+        }
+        self.closing.store(true, Ordering::SeqCst);
+        let deadline = Instant::now() + SHUTDOWN_GRACE;
+        while self.finished.load(Ordering::SeqCst) < self.recv_guards.len() && Instant::now() < deadline {
+            ::std::thread::sleep(Duration::from_millis(10));
+        }
+        for shutdown in self.recv_shutdowns.drain(..) {
+            shutdown();
+        }
+        for handle in self.recv_guards.drain(..) {
+            handle.join().expect("Recv thread panic");
+        }
+    }
 }
 
 impl Drop for CommsGuard {
@@ -32,23 +76,172 @@ impl Drop for CommsGuard {
 use crate::logging::{CommunicationSetup, CommunicationEvent};
 use logging_core::Logger;
 
-/// Initializes network connections
+/// How connections to other processes are established and served.
+///
+/// Options are most easily set by overriding those of `ConnectionOptions::default()`, which
+/// describes plain TCP connections, attempted indefinitely.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionOptions {
+    /// The means by which processes exchange data, which determines the form of addresses.
+    pub transport: Transport,
+    /// Verbosely report the connection process.
+    pub report: bool,
+    /// Report the loss of a remote process to workers through `Allocate::lost_processes`,
+    /// rather than by panicking the communication threads.
+    pub tolerate_failures: bool,
+    /// Compress each connection whose remote process proposes the same compression, which
+    /// requires the `compression` feature.
+    pub compression: Option<Compression>,
+    /// How persistently to attempt connections, and for how long.
+    pub retry: Retry,
+    /// A directory through which processes exchange the addresses they bound, which is only
+    /// supported for TCP connections; see `networking::rendezvous`.
+    pub rendezvous: Option<PathBuf>,
+    /// The host name or IP address by which other processes reach this one, published with
+    /// the bound port through a rendezvous. By default, the bound address is published, with
+    /// the name of this host in place of an unspecified IP address such as `0.0.0.0`.
+    pub advertised_host: Option<String>,
+    /// Secure TCP connections with mutually authenticated TLS, which requires the `tls` feature.
+    pub tls: Option<TlsConfig>,
+    /// Serve TCP connections from this many event loop threads, rather than from a send and
+    /// a receive thread per connection, which requires the `event-loop` feature. Connections
+    /// served this way can be neither secured with TLS nor compressed.
+    pub network_threads: Option<NonZeroUsize>,
+}
+
+impl ConnectionOptions {
+    /// Checks that the options are supported by the enabled features, and by each other.
+    fn validate(&self) -> ::std::io::Result<()> {
+        #[cfg(not(feature = "tls"))]
+        {
+            if self.tls.is_some() {
+                return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidInput,
+                    "TLS requires the `tls` feature of timely_communication"));
+            }
+        }
+        #[cfg(not(feature = "compression"))]
+        {
+            if self.compression.is_some() {
+                return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidInput,
+                    "compression requires the `compression` feature of timely_communication"));
+            }
+        }
+        #[cfg(not(feature = "event-loop"))]
+        {
+            if self.network_threads.is_some() {
+                return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidInput,
+                    "network threads require the `event-loop` feature of timely_communication"));
+            }
+        }
+        if self.network_threads.is_some() && self.compression.is_some() {
+            return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidInput,
+                "connections served by network threads cannot be compressed"));
+        }
+        if self.network_threads.is_some() && self.tls.is_some() {
+            return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidInput,
+                "connections served by network threads cannot be secured with TLS"));
+        }
+        if self.transport != Transport::Tcp {
+            let unsupported =
+                if self.tls.is_some() { Some("TLS") }
+                else if self.rendezvous.is_some() { Some("rendezvous") }
+                else if self.network_threads.is_some() { Some("network threads") }
+                else { None };
+            if let Some(unsupported) = unsupported {
+                return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidInput,
+                    format!("{} is only supported for TCP connections", unsupported)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Initializes TCP connections
+///
+/// If `options.tls` is supplied, connections are secured with mutually authenticated TLS,
+/// which requires the `tls` feature. The `options.transport` should be `Transport::Tcp`.
 pub fn initialize_networking(
     addresses: Vec<String>,
     my_index: usize,
     threads: usize,
-    noisy: bool,
+    options: ConnectionOptions,
     log_sender: Box<dyn Fn(CommunicationSetup)->Option<Logger<CommunicationEvent, CommunicationSetup>>+Send+Sync>)
 -> ::std::io::Result<(Vec<TcpBuilder<ProcessBuilder>>, CommsGuard)>
 {
-    let log_sender = Arc::new(log_sender);
-    let processes = addresses.len();
+    options.validate()?;
 
     // one per process (including local, which would be None)
-    let mut results: Vec<Option<::std::net::TcpStream>> =
-        create_sockets(addresses, my_index, noisy)?;
+    let (sockets, compression) = create_sockets(addresses.clone(), my_index, options.report, options.compression, options.retry, options.rendezvous.as_deref(), options.advertised_host.as_deref())?;
+
+    #[cfg(feature = "tls")]
+    {
+        if let Some(config) = options.tls.as_ref() {
+            let streams = super::tls::secure(sockets, &addresses, my_index, config)?;
+            return initialize_networking_from(streams, compression, my_index, threads, options.tolerate_failures, log_sender);
+        }
+    }
+
+    #[cfg(feature = "event-loop")]
+    {
+        if let Some(network_threads) = options.network_threads {
+            return initialize_event_loops(sockets, my_index, threads, network_threads.get(), options.tolerate_failures, log_sender);
+        }
+    }
+
+    initialize_networking_from(sockets, compression, my_index, threads, options.tolerate_failures, log_sender)
+}
 
-    let process_allocators = crate::allocator::process::Process::new_vector(threads);
+/// Initializes Unix domain socket connections between processes on the same host.
+///
+/// Addresses name socket files. If `options.transport` is `Transport::SharedMemory`, data are
+/// exchanged through shared-memory rings set up over the sockets, rather than through the
+/// sockets themselves.
+#[cfg(unix)]
+pub fn initialize_networking_unix(
+    addresses: Vec<String>,
+    my_index: usize,
+    threads: usize,
+    options: ConnectionOptions,
+    log_sender: Box<dyn Fn(CommunicationSetup)->Option<Logger<CommunicationEvent, CommunicationSetup>>+Send+Sync>)
+-> ::std::io::Result<(Vec<TcpBuilder<ProcessBuilder>>, CommsGuard)>
+{
+    options.validate()?;
+    let shared_memory = options.transport == Transport::SharedMemory;
+    let (sockets, compression) = crate::networking::create_unix_sockets(addresses.clone(), my_index, options.report, options.compression, options.retry)?;
+    let tolerant = options.tolerate_failures;
+    if shared_memory {
+        let streams = super::shm::map_rings(sockets, &addresses, my_index)?;
+        initialize_networking_from(streams, compression, my_index, threads, tolerant, log_sender)
+    }
+    else {
+        initialize_networking_from(sockets, compression, my_index, threads, tolerant, log_sender)
+    }
+}
+
+/// Initializes communication over established connections to other processes.
+///
+/// The connection at index `i` of `streams` leads to process `i`, except at `my_index`,
+/// which should be `None`, and is compressed as indicated at index `i` of `compression`.
+pub fn initialize_networking_from<S: Stream+Send>(
+    mut streams: Vec<Option<S>>,
+    compression: Vec<Option<Compression>>,
+    my_index: usize,
+    threads: usize,
+    tolerant: bool,
+    log_sender: Box<dyn Fn(CommunicationSetup)->Option<Logger<CommunicationEvent, CommunicationSetup>>+Send+Sync>)
+-> ::std::io::Result<(Vec<TcpBuilder<ProcessBuilder>>, CommsGuard)>
+{
+    let log_sender = Arc::new(log_sender);
+    let processes = streams.len();
+
+    let process_allocators =
+    crate::allocator::process::Process::new_vector(threads)
+        .into_iter()
+        .map(|builder| {
+            let log_sender = log_sender.clone();
+            builder.with_logging(my_index, Arc::new(move |setup| log_sender(setup)))
+        })
+        .collect();
     let (builders, promises, futures) = new_vector(process_allocators, my_index, processes);
 
     let mut promises_iter = promises.into_iter();
@@ -56,18 +249,24 @@ pub fn initialize_networking(
 
     let mut send_guards = Vec::new();
     let mut recv_guards = Vec::new();
+    let closing = Arc::new(AtomicBool::new(false));
+    let finished = Arc::new(AtomicUsize::new(0));
+    let mut recv_shutdowns = Vec::new();
 
     // for each process, if a stream exists (i.e. not local) ...
-    for index in 0..results.len() {
+    for index in 0..streams.len() {
 
-        if let Some(stream) = results[index].take() {
+        if let Some(stream) = streams[index].take() {
             // remote process
+            let compression = compression[index];
+            debug_assert!(cfg!(feature = "compression") || compression.is_none());
 
             let remote_recv = promises_iter.next().unwrap();
 
             {
                 let log_sender = log_sender.clone();
                 let stream = stream.try_clone()?;
+                let closing = closing.clone();
                 let join_guard =
                 ::std::thread::Builder::new()
                     .name(format!("send thread {}", index))
@@ -79,7 +278,14 @@ pub fn initialize_networking(
                             remote: Some(index),
                         });
 
-                        send_loop(stream, remote_recv, my_index, index, logger);
+                        #[cfg(feature = "compression")]
+                        {
+                            if let Some(compression) = compression {
+                                let stream = Compressed::new(stream, compression, logger.clone());
+                                return send_loop(stream, remote_recv, my_index, index, tolerant, closing, logger);
+                            }
+                        }
+                        send_loop(stream, remote_recv, my_index, index, tolerant, closing, logger);
                     })?;
 
                 send_guards.push(join_guard);
@@ -91,6 +297,10 @@ pub fn initialize_networking(
                 // let remote_sends = remote_sends.clone();
                 let log_sender = log_sender.clone();
                 let stream = stream.try_clone()?;
+                let closing = closing.clone();
+                let finished = finished.clone();
+                let shutdown = stream.try_clone()?;
+                recv_shutdowns.push(Box::new(move || { let _ = shutdown.shutdown(Shutdown::Read); }) as Box<dyn Fn()+Send>);
                 let join_guard =
                 ::std::thread::Builder::new()
                     .name(format!("recv thread {}", index))
@@ -100,7 +310,17 @@ pub fn initialize_networking(
                             sender: false,
                             remote: Some(index),
                         });
-                        recv_loop(stream, remote_send, threads * my_index, my_index, index, logger);
+                        #[cfg(feature = "compression")]
+                        {
+                            if let Some(compression) = compression {
+                                let stream = Compressed::new(stream, compression, logger.clone());
+                                recv_loop(stream, remote_send, my_index, index, tolerant, closing, logger);
+                                finished.fetch_add(1, Ordering::SeqCst);
+                                return;
+                            }
+                        }
+                        recv_loop(stream, remote_send, my_index, index, tolerant, closing, logger);
+                        finished.fetch_add(1, Ordering::SeqCst);
                     })?;
 
                 recv_guards.push(join_guard);
@@ -109,5 +329,64 @@ pub fn initialize_networking(
         }
     }
 
-    Ok((builders, CommsGuard { send_guards, recv_guards }))
-}
\ No newline at end of file
+    Ok((builders, CommsGuard { send_guards, recv_guards, closing, finished, recv_shutdowns }))
+}
+/// Initializes communication over established TCP connections, served by `network_threads`
+/// event loops, to which connections are assigned round-robin.
+///
+/// Each event loop thread counts as a receive thread of the resulting `CommsGuard`, as it
+/// completes only once its connections have ended in both directions.
+#[cfg(feature = "event-loop")]
+pub fn initialize_event_loops(
+    mut streams: Vec<Option<::std::net::TcpStream>>,
+    my_index: usize,
+    threads: usize,
+    network_threads: usize,
+    tolerant: bool,
+    log_sender: Box<dyn Fn(CommunicationSetup)->Option<Logger<CommunicationEvent, CommunicationSetup>>+Send+Sync>)
+-> ::std::io::Result<(Vec<TcpBuilder<ProcessBuilder>>, CommsGuard)>
+{
+    use super::event_loop::{event_loop, Peer};
+
+    let log_sender = Arc::new(log_sender);
+    let processes = streams.len();
+
+    let process_allocators =
+    crate::allocator::process::Process::new_vector(threads)
+        .into_iter()
+        .map(|builder| {
+            let log_sender = log_sender.clone();
+            builder.with_logging(my_index, Arc::new(move |setup| log_sender(setup)))
+        })
+        .collect();
+    let (builders, promises, futures) = new_vector(process_allocators, my_index, processes);
+
+    let mut groups: Vec<Vec<Peer>> = (0 .. network_threads).map(|_| Vec::new()).collect();
+    let mut recv_shutdowns = Vec::new();
+    let remotes = (0 .. processes).filter(|index| *index != my_index);
+    for (position, ((remote, sources), targets)) in remotes.zip(promises).zip(futures).enumerate() {
+        let stream = streams[remote].take().expect("missing connection to remote process");
+        let shutdown = stream.try_clone()?;
+        recv_shutdowns.push(Box::new(move || { let _ = shutdown.shutdown(Shutdown::Read); }) as Box<dyn Fn()+Send>);
+        groups[position % network_threads].push(Peer { remote, stream, sources, targets });
+    }
+
+    let closing = Arc::new(AtomicBool::new(false));
+    let finished = Arc::new(AtomicUsize::new(0));
+    let mut recv_guards = Vec::new();
+    for (index, peers) in groups.into_iter().enumerate().filter(|(_, peers)| !peers.is_empty()) {
+        let log_sender = log_sender.clone();
+        let closing = closing.clone();
+        let finished = finished.clone();
+        let join_guard =
+        ::std::thread::Builder::new()
+            .name(format!("network thread {}", index))
+            .spawn(move || {
+                event_loop(peers, my_index, threads, tolerant, closing, &**log_sender).expect("event loop failed");
+                finished.fetch_add(1, Ordering::SeqCst);
+            })?;
+        recv_guards.push(join_guard);
+    }
+
+    Ok((builders, CommsGuard { send_guards: Vec::new(), recv_guards, closing, finished, recv_shutdowns }))
+}
@@ -7,6 +7,12 @@
 pub mod bytes_slab;
 pub mod bytes_exchange;
 pub mod tcp;
+pub mod tls;
+pub mod compression;
+#[cfg(feature = "event-loop")]
+pub mod event_loop;
+#[cfg(unix)]
+pub mod shm;
 pub mod allocator;
 pub mod allocator_process;
 pub mod initialize;
@@ -2,7 +2,7 @@
 
 use std::rc::Rc;
 use std::cell::RefCell;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 
 use bytes::arc::Bytes;
 
@@ -102,11 +102,14 @@ impl<T:Data> Pull<Message<T>> for Puller<T> {
 /// not the most efficient thing possible, which would probably instead be something
 /// like the `bytes` crate (../bytes/) which provides an exclusive view of a shared
 /// allocation.
+///
+/// Unlike `Puller`, the serialized buffers are expected to start with their `MessageHeader`.
 pub struct PullerInner<T> {
     inner: Box<dyn Pull<Message<T>>>,               // inner pullable (e.g. intra-process typed queue)
     _canary: Canary,
     current: Option<Message<T>>,
     receiver: Rc<RefCell<VecDeque<Bytes>>>,     // source of serialized buffers
+    acks: Option<(usize, Rc<RefCell<HashMap<(usize, usize), usize>>>)>,  // channel, and credits owed to sources.
 }
 
 impl<T:Data> PullerInner<T> {
@@ -117,8 +120,15 @@ impl<T:Data> PullerInner<T> {
             _canary,
             current: None,
             receiver,
+            acks: None,
         }
     }
+
+    /// Records pulled messages in `acks`, keyed by `(channel, source)`, so that credits can be returned.
+    pub fn with_acks(mut self, channel: usize, acks: Rc<RefCell<HashMap<(usize, usize), usize>>>) -> Self {
+        self.acks = Some((channel, acks));
+        self
+    }
 }
 
 impl<T:Data> Pull<Message<T>> for PullerInner<T> {
@@ -130,11 +140,19 @@ impl<T:Data> Pull<Message<T>> for PullerInner<T> {
             inner
         }
         else {
+            let acks = &self.acks;
             self.current =
             self.receiver
                 .borrow_mut()
                 .pop_front()
-                .map(|bytes| unsafe { Message::from_bytes(bytes) });
+                .map(|mut bytes| {
+                    let header = MessageHeader::try_read(&mut bytes[..]).expect("failed to read header!");
+                    if let Some((channel, ref acks)) = *acks {
+                        *acks.borrow_mut().entry((channel, header.source)).or_insert(0) += 1;
+                    }
+                    let _ = bytes.extract_to(::std::mem::size_of::<MessageHeader>());
+                    unsafe { Message::from_bytes(bytes) }
+                });
 
             &mut self.current
         }
@@ -0,0 +1,228 @@
+//! Shared-memory ring buffers between processes on the same host.
+//!
+//! Each pair of processes shares two single-producer, single-consumer rings, one for each
+//! direction, mapped from files that the receiving process creates. The rings are set up
+//! over a Unix domain socket, which remains open for two further purposes: a reader that
+//! finds its ring empty blocks reading the socket until the writer sends a wake-up byte,
+//! and the socket closing without the ring being closed reveals the loss of the peer.
+//!
+//! A `ShmStream` implements `Read` and `Write` over the rings, so that the send and receive
+//! loops, and the `MergeQueue` and `BytesSlab` machinery behind them, are unchanged.
+
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use memmap2::MmapMut;
+
+use bytes::arc::Bytes;
+
+use crate::networking::Stream;
+
+/// Bytes reserved at the start of each mapping for the control block.
+const CONTROL_BYTES: usize = 4096;
+/// Bytes of data each ring can hold.
+const RING_CAPACITY: usize = 1 << 22;
+
+/// State shared by the writing and reading processes, at the start of each mapping.
+///
+/// A zero-filled file is a valid, empty, open ring.
+#[repr(C)]
+struct Control {
+    /// Total bytes read; only advanced by the reader.
+    head: AtomicU64,
+    _pad0: [u8; 56],
+    /// Total bytes written; only advanced by the writer.
+    tail: AtomicU64,
+    _pad1: [u8; 56],
+    /// Set by the reader before it blocks on the socket.
+    waiting: AtomicBool,
+    /// Set by the writer once it will write no more.
+    closed: AtomicBool,
+}
+
+/// One direction of communication, backed by a shared mapping.
+struct Ring {
+    control: Bytes,
+    data: Bytes,
+}
+
+impl Ring {
+    /// Maps the ring in `path`, first creating and sizing the file if `create` is set.
+    fn map(path: &str, create: bool) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).create(create).truncate(create).open(path)?;
+        if create {
+            file.set_len((CONTROL_BYTES + RING_CAPACITY) as u64)?;
+        }
+        let mmap = unsafe { MmapMut::map_mut(&file)? };
+        if mmap.len() != CONTROL_BYTES + RING_CAPACITY {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}: unexpected ring size", path)));
+        }
+        let mut data = Bytes::from(mmap);
+        let control = data.extract_to(CONTROL_BYTES);
+        Ok(Ring { control, data })
+    }
+
+    fn control(&self) -> &Control {
+        // The mapping is page aligned, and lives as long as `self.control`.
+        unsafe { &*(self.control.as_ptr() as *const Control) }
+    }
+
+    /// The control block and the data region, borrowed separately.
+    fn parts(&mut self) -> (&Control, &mut [u8]) {
+        let control = unsafe { &*(self.control.as_ptr() as *const Control) };
+        (control, &mut self.data[..])
+    }
+}
+
+/// A connection to another process through shared-memory rings.
+pub struct ShmStream {
+    incoming: Arc<Mutex<Ring>>,
+    outgoing: Arc<Mutex<Ring>>,
+    socket: UnixStream,
+    // Set by the reader if the socket closed before the peer closed its ring.
+    lost: Arc<AtomicBool>,
+    // Set by the reader once the socket has closed.
+    ended: bool,
+}
+
+/// Replaces each Unix domain socket connection with shared-memory rings.
+///
+/// The input and output are indexed by process, as in `networking::create_unix_sockets`.
+/// The ring carrying data from process `j` to process `i` is mapped from the file
+/// `"{addresses[i]}.ring-{j}"`, which is removed once both processes have mapped it.
+pub fn map_rings(
+    sockets: Vec<Option<UnixStream>>,
+    addresses: &[String],
+    my_index: usize) -> io::Result<Vec<Option<ShmStream>>>
+{
+    let mut results = Vec::with_capacity(sockets.len());
+    for (index, socket) in sockets.into_iter().enumerate() {
+        let stream = match socket {
+            Some(mut socket) => {
+                let incoming_path = format!("{}.ring-{}", addresses[my_index], index);
+                let incoming = Ring::map(&incoming_path, true)?;
+                // Announce our ring, and await the peer's.
+                socket.write_all(&[1])?;
+                socket.read_exact(&mut [0])?;
+                let outgoing = Ring::map(&format!("{}.ring-{}", addresses[index], my_index), false)?;
+                // Confirm the mapping, and await the peer's confirmation before removing our file.
+                socket.write_all(&[2])?;
+                socket.read_exact(&mut [0])?;
+                ::std::fs::remove_file(&incoming_path)?;
+                Some(ShmStream {
+                    incoming: Arc::new(Mutex::new(incoming)),
+                    outgoing: Arc::new(Mutex::new(outgoing)),
+                    socket,
+                    lost: Arc::new(AtomicBool::new(false)),
+                    ended: false,
+                })
+            },
+            None => None,
+        };
+        results.push(stream);
+    }
+    Ok(results)
+}
+
+impl Read for ShmStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut ring = self.incoming.lock().expect("ring lock poisoned");
+        let (control, data) = ring.parts();
+        loop {
+            let head = control.head.load(Ordering::SeqCst);
+            let tail = control.tail.load(Ordering::SeqCst);
+            if tail > head {
+                let length = ::std::cmp::min((tail - head) as usize, buf.len());
+                let offset = (head % RING_CAPACITY as u64) as usize;
+                let first = ::std::cmp::min(length, RING_CAPACITY - offset);
+                buf[.. first].copy_from_slice(&data[offset .. offset + first]);
+                buf[first .. length].copy_from_slice(&data[.. length - first]);
+                control.head.store(head + length as u64, Ordering::SeqCst);
+                return Ok(length);
+            }
+            if self.ended {
+                return Ok(0);
+            }
+            // Announce that we are waiting, then check once more before blocking.
+            control.waiting.store(true, Ordering::SeqCst);
+            if control.tail.load(Ordering::SeqCst) > head {
+                control.waiting.store(false, Ordering::SeqCst);
+                continue;
+            }
+            let mut wakeups = [0u8; 64];
+            if self.socket.read(&mut wakeups)? == 0 {
+                self.ended = true;
+                if !control.closed.load(Ordering::SeqCst) {
+                    self.lost.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+    }
+}
+
+impl Write for ShmStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut ring = self.outgoing.lock().expect("ring lock poisoned");
+        let (control, data) = ring.parts();
+        let mut spins = 0;
+        loop {
+            let head = control.head.load(Ordering::SeqCst);
+            let tail = control.tail.load(Ordering::SeqCst);
+            let free = RING_CAPACITY - (tail - head) as usize;
+            if free > 0 || buf.is_empty() {
+                let length = ::std::cmp::min(free, buf.len());
+                let offset = (tail % RING_CAPACITY as u64) as usize;
+                let first = ::std::cmp::min(length, RING_CAPACITY - offset);
+                data[offset .. offset + first].copy_from_slice(&buf[.. first]);
+                data[.. length - first].copy_from_slice(&buf[first .. length]);
+                control.tail.store(tail + length as u64, Ordering::SeqCst);
+                if control.waiting.load(Ordering::SeqCst) && control.waiting.swap(false, Ordering::SeqCst) {
+                    self.socket.write_all(&[0])?;
+                }
+                return Ok(length);
+            }
+            // The ring is full; wait for the reader, unless it has gone.
+            if self.lost.load(Ordering::SeqCst) {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "shared-memory peer lost"));
+            }
+            if spins < 100 {
+                spins += 1;
+                ::std::thread::yield_now();
+            }
+            else {
+                ::std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Data are visible to the reader as soon as they are written.
+        Ok(())
+    }
+}
+
+impl Stream for ShmStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(ShmStream {
+            incoming: self.incoming.clone(),
+            outgoing: self.outgoing.clone(),
+            socket: self.socket.try_clone()?,
+            lost: self.lost.clone(),
+            ended: false,
+        })
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        if how != Shutdown::Read {
+            let ring = self.outgoing.lock().expect("ring lock poisoned");
+            ring.control().closed.store(true, Ordering::SeqCst);
+        }
+        // Closing the socket wakes the peer's reader, and any of our own blocked readers.
+        self.socket.shutdown(how)
+    }
+}
@@ -1,30 +1,48 @@
 //!
 
-use std::io::{Read, Write};
-use std::net::TcpStream;
+use std::io::Write;
+use std::net::Shutdown;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Sender, Receiver};
 
-use crate::networking::MessageHeader;
+use bytes::arc::Bytes;
+
+use crate::allocator::Priority;
+use crate::networking::{MessageHeader, Stream};
 
 use super::bytes_slab::BytesSlab;
 use super::bytes_exchange::MergeQueue;
+use super::allocator::{CREDIT_CHANNEL, ABORT_CHANNEL, decode_credit};
 
 use logging_core::Logger;
 
-use crate::logging::{CommunicationEvent, CommunicationSetup, MessageEvent, StateEvent};
+use crate::logging::{CommunicationEvent, CommunicationSetup, MessageEvent, StateEvent, CreditEvent};
+
+/// Logs the credits returned by a message on the `CREDIT_CHANNEL`.
+pub(crate) fn log_credit(logger: &mut Logger<CommunicationEvent, CommunicationSetup>, is_send: bool, header: MessageHeader, message: &mut [u8]) {
+    let payload = &mut message[::std::mem::size_of::<MessageHeader>() .. header.required_bytes()];
+    if let Some((channel, count)) = decode_credit(payload) {
+        logger.log(CreditEvent { is_send, channel, source: header.source, target: header.target, count });
+    }
+}
 
-/// Repeatedly reads from a TcpStream and carves out messages.
+/// Repeatedly reads from a stream and carves out messages.
 ///
 /// The intended communication pattern is a sequence of (header, message)^* for valid
 /// messages, followed by a header for a zero length message indicating the end of stream.
 /// If the stream ends without being shut down, the receive thread panics in an attempt to
-/// take down the computation and cause the failures to cascade.
-pub fn recv_loop(
-    mut reader: TcpStream,
+/// take down the computation and cause the failures to cascade, unless `tolerant` is set,
+/// in which case the targets are marked as disconnected and the thread exits. Once `closing`
+/// is set, because the local computation is shutting down, the end of the stream is expected.
+/// The thread sets `closing` itself once the remote process announces the failure of a worker.
+pub fn recv_loop<S: Stream>(
+    mut reader: S,
     targets: Vec<Receiver<MergeQueue>>,
-    worker_offset: usize,
     process: usize,
     remote: usize,
+    tolerant: bool,
+    closing: Arc<AtomicBool>,
     mut logger: Option<Logger<CommunicationEvent, CommunicationSetup>>)
 {
     // Log the receive thread's start.
@@ -32,6 +50,9 @@ pub fn recv_loop(
 
     let mut targets: Vec<MergeQueue> = targets.into_iter().map(|x| x.recv().expect("Failed to receive MergeQueue")).collect();
 
+    // Each process has as many workers as there are targets.
+    let worker_offset = targets.len() * process;
+
     let mut buffer = BytesSlab::new(20);
 
     // Where we stash Bytes before handing them off.
@@ -59,12 +80,23 @@ pub fn recv_loop(
             Ok(n) => n,
             Err(x) => {
                 // We don't expect this, as socket closure results in Ok(0) reads.
-                println!("Error: {:?}", x);
+                if !closing.load(Ordering::SeqCst) {
+                    println!("Error: {:?}", x);
+                }
                 0
             },
         };
 
-        assert!(read > 0);
+        if read == 0 {
+            // The stream ended without a clean shutdown.
+            if tolerant || closing.load(Ordering::SeqCst) {
+                for target in targets.iter() {
+                    target.disconnect();
+                }
+                break;
+            }
+            panic!("Stream from process {} ended without clean shutdown.", remote);
+        }
         buffer.make_valid(read);
 
         // Consume complete messages from the front of self.buffer.
@@ -72,13 +104,21 @@ pub fn recv_loop(
 
             // TODO: Consolidate message sequences sent to the same worker?
             let peeled_bytes = header.required_bytes();
-            let bytes = buffer.extract(peeled_bytes);
+            let mut bytes = buffer.extract(peeled_bytes);
 
             // Record message receipt.
             logger.as_mut().map(|logger| {
                 logger.log(MessageEvent { is_send: false, header, });
+                if header.channel == CREDIT_CHANNEL {
+                    log_credit(logger, false, header, &mut bytes[..]);
+                }
             });
 
+            // The local computation will abort, and the remote process may end its stream abruptly.
+            if header.channel == ABORT_CHANNEL {
+                closing.store(true, Ordering::SeqCst);
+            }
+
             if header.length > 0 {
                 stageds[header.target - worker_offset].push(bytes);
             }
@@ -95,11 +135,10 @@ pub fn recv_loop(
             }
         }
 
-        // Pass bytes along to targets.
+        // Pass bytes along to targets, discarding those for workers that have panicked.
         for (index, staged) in stageds.iter_mut().enumerate() {
             // FIXME: try to merge `staged` before handing it to BytesPush::extend
-            use crate::allocator::zero_copy::bytes_exchange::BytesPush;
-            targets[index].extend(staged.drain(..));
+            targets[index].extend_or_discard(staged.drain(..));
         }
     }
 
@@ -107,67 +146,112 @@ pub fn recv_loop(
     logger.as_mut().map(|l| l.log(StateEvent { send: false, process, remote, start: false, }));
 }
 
-/// Repeatedly sends messages into a TcpStream.
+/// Repeatedly sends messages into a stream.
 ///
 /// The intended communication pattern is a sequence of (header, message)^* for valid
 /// messages, followed by a header for a zero length message indicating the end of stream.
-pub fn send_loop(
+/// If writing fails and `tolerant` is set, subsequent messages are discarded rather than
+/// causing the send thread to panic, as they are once `closing` is set. If a local worker
+/// panics, the stream ends without the zero length header, once the data sent before the
+/// panic have been written.
+///
+/// Each worker is offered two queues, for `Priority::Control` and then `Priority::Data`
+/// traffic. Control traffic is written ahead of data, and the control queues are checked
+/// again after each batch of data is written.
+pub fn send_loop<S: Stream>(
     // TODO: Maybe we don't need BufWriter with consolidation in writes.
-    writer: TcpStream,
+    writer: S,
     sources: Vec<Sender<MergeQueue>>,
     process: usize,
     remote: usize,
+    tolerant: bool,
+    closing: Arc<AtomicBool>,
     mut logger: Option<Logger<CommunicationEvent, CommunicationSetup>>)
 {
 
     // Log the receive thread's start.
     logger.as_mut().map(|l| l.log(StateEvent { send: true, process, remote, start: true, }));
 
-    let mut sources: Vec<MergeQueue> = sources.into_iter().map(|x| {
-        let buzzer = crate::buzzer::Buzzer::new();
-        let queue = MergeQueue::new(buzzer);
-        x.send(queue.clone()).expect("failed to send MergeQueue");
-        queue
+    let mut sources: Vec<(Priority, MergeQueue)> = sources.into_iter().flat_map(|x| {
+        [Priority::Control, Priority::Data].iter().map(|&priority| {
+            let buzzer = crate::buzzer::Buzzer::new();
+            let queue = MergeQueue::new(buzzer);
+            x.send(queue.clone()).expect("failed to send MergeQueue");
+            (priority, queue)
+        }).collect::<Vec<_>>()
     }).collect();
 
     let mut writer = ::std::io::BufWriter::with_capacity(1 << 16, writer);
+    let mut urgent = Vec::new();
     let mut stash = Vec::new();
 
+    // Set if the connection has failed, in which case we discard all data.
+    let mut lost = false;
+    // Set if a local worker has panicked, in which case we end the stream without a clean shutdown.
+    let mut failed = false;
+
     while !sources.is_empty() {
 
         // TODO: Round-robin better, to release resources fairly when overloaded.
-        for source in sources.iter_mut() {
-            use crate::allocator::zero_copy::bytes_exchange::BytesPull;
-            source.drain_into(&mut stash);
+        // Sources poisoned by panicking workers are retired once drained of what the workers
+        // sent before panicking, which may announce the failure to the remote process.
+        let mut retired = Vec::new();
+        for (index, (priority, source)) in sources.iter_mut().enumerate() {
+            if source.is_poisoned() { retired.push(index); }
+            match priority {
+                Priority::Control => source.drain_remaining(&mut urgent),
+                Priority::Data => source.drain_remaining(&mut stash),
+            }
+        }
+        for index in retired.into_iter().rev() {
+            sources.remove(index);
+            failed = true;
         }
 
-        if stash.is_empty() {
+        if urgent.is_empty() && stash.is_empty() {
             // No evidence of records to read, but sources not yet empty (at start of loop).
             // We are going to flush our writer (to move buffered data), double check on the
             // sources for emptiness and wait on a signal only if we are sure that there will
             // still be a signal incoming.
             //
             // We could get awoken by more data, a channel closing, or spuriously perhaps.
-            writer.flush().expect("Failed to flush writer.");
-            sources.retain(|source| !source.is_complete());
+            if !lost && writer.flush().is_err() {
+                if !tolerant && !closing.load(Ordering::SeqCst) { panic!("Failed to flush writer."); }
+                lost = true;
+                // Also stop the receive thread, which will report the lost process.
+                let _ = writer.get_ref().shutdown(Shutdown::Both);
+            }
+            sources.retain(|(_, source)| source.is_poisoned() || !source.is_complete());
             if !sources.is_empty() {
                 std::thread::park();
             }
         }
+        else if lost {
+            // Nowhere to send the data; discard it.
+            urgent.clear();
+            stash.clear();
+        }
         else {
             // TODO: Could do scatter/gather write here.
-            for mut bytes in stash.drain(..) {
-
-                // Record message sends.
-                logger.as_mut().map(|logger| {
-                    let mut offset = 0;
-                    while let Some(header) = MessageHeader::try_read(&mut bytes[offset..]) {
-                        logger.log(MessageEvent { is_send: true, header, });
-                        offset += header.required_bytes();
-                    }
-                });
-
-                writer.write_all(&bytes[..]).expect("Write failure in send_loop.");
+            let mut written = urgent.drain(..).try_for_each(|bytes| write_bytes(&mut writer, bytes, &mut logger));
+            let mut data = stash.drain(..);
+            while let (true, Some(bytes)) = (written.is_ok(), data.next()) {
+                written = write_bytes(&mut writer, bytes, &mut logger);
+                // Control traffic enqueued in the meantime overtakes the remaining data.
+                for (_, source) in sources.iter_mut().filter(|(priority, _)| *priority == Priority::Control) {
+                    source.drain_remaining(&mut urgent);
+                }
+                if written.is_ok() {
+                    written = urgent.drain(..).try_for_each(|bytes| write_bytes(&mut writer, bytes, &mut logger));
+                }
+            }
+            ::std::mem::drop(data);
+            if written.is_err() {
+                if !tolerant && !closing.load(Ordering::SeqCst) { panic!("Write failure in send_loop."); }
+                lost = true;
+                urgent.clear();
+                stash.clear();
+                let _ = writer.get_ref().shutdown(Shutdown::Both);
             }
         }
     }
@@ -182,11 +266,40 @@ pub fn send_loop(
         length:     0,
         seqno:      0,
     };
-    header.write_to(&mut writer).expect("Failed to write header!");
-    writer.flush().expect("Failed to flush writer.");
-    writer.get_mut().shutdown(::std::net::Shutdown::Write).expect("Write shutdown failed");
-    logger.as_mut().map(|logger| logger.log(MessageEvent { is_send: true, header }));
+    // There is no point attempting a clean shutdown of a failed connection.
+    if !lost && failed {
+        // The remote process expects an abrupt end once told of the failure.
+        let _ = writer.flush().and_then(|_| writer.get_ref().shutdown(Shutdown::Write));
+    }
+    else if !lost {
+        let shutdown =
+        header.write_to(&mut writer)
+              .and_then(|_| writer.flush())
+              .and_then(|_| writer.get_ref().shutdown(Shutdown::Write));
+        if let Err(error) = shutdown {
+            if !tolerant && !closing.load(Ordering::SeqCst) { panic!("Clean shutdown failed: {}", error); }
+        }
+        logger.as_mut().map(|logger| logger.log(MessageEvent { is_send: true, header }));
+    }
 
     // Log the receive thread's start.
     logger.as_mut().map(|l| l.log(StateEvent { send: true, process, remote, start: false, }));
 }
+
+/// Logs the messages in `bytes`, and writes them to `writer`.
+fn write_bytes<W: Write>(writer: &mut W, mut bytes: Bytes, logger: &mut Option<Logger<CommunicationEvent, CommunicationSetup>>) -> ::std::io::Result<()> {
+
+    // Record message sends.
+    logger.as_mut().map(|logger| {
+        let mut offset = 0;
+        while let Some(header) = MessageHeader::try_read(&mut bytes[offset..]) {
+            logger.log(MessageEvent { is_send: true, header, });
+            if header.channel == CREDIT_CHANNEL {
+                log_credit(logger, true, header, &mut bytes[offset..]);
+            }
+            offset += header.required_bytes();
+        }
+    });
+
+    writer.write_all(&bytes[..])
+}
@@ -0,0 +1,282 @@
+//! Mutually authenticated TLS for connections between processes.
+//!
+//! When a `TlsConfig` is supplied, each TCP connection is wrapped in a TLS session once the
+//! connection is established, and before any worker data is exchanged. Both ends present a
+//! certificate, and each end rejects peers whose certificates do not chain to the configured
+//! certificate authority. The process with the lower index acts as the TLS client, and checks
+//! the certificate of its peer against the host portion of the peer's address.
+//!
+//! The types describing the configuration are always available; establishing TLS sessions
+//! requires the `tls` feature.
+
+use std::path::PathBuf;
+
+/// Locations of PEM-encoded files used to secure connections between processes.
+///
+/// Certificates must be valid for the host names or IP addresses that appear in the list of
+/// process addresses, and must be usable for both client and server authentication.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    /// Certificates of the authorities trusted to sign the certificates of peers.
+    pub ca: PathBuf,
+    /// Certificate chain presented by this process.
+    pub cert: PathBuf,
+    /// Private key for the first certificate in `cert`.
+    pub key: PathBuf,
+}
+
+impl TlsConfig {
+    /// Creates a configuration from the paths of the authority, certificate, and key files.
+    pub fn new<P: Into<PathBuf>>(ca: P, cert: P, key: P) -> Self {
+        TlsConfig { ca: ca.into(), cert: cert.into(), key: key.into() }
+    }
+}
+
+#[cfg(feature = "tls")]
+pub use self::stream::{TlsStream, secure};
+
+#[cfg(feature = "tls")]
+mod stream {
+
+    use std::convert::TryFrom;
+    use std::io::{self, Read, Write};
+    use std::net::{Shutdown, TcpStream};
+    use std::sync::{Arc, Mutex};
+
+    use rustls::{ClientConfig, ClientConnection, Connection, RootCertStore, ServerConfig, ServerConnection};
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+    use rustls::pki_types::pem::PemObject;
+    use rustls::server::WebPkiClientVerifier;
+
+    use crate::networking::Stream;
+    use super::TlsConfig;
+
+    fn invalid<E: ::std::fmt::Display>(error: E) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+    }
+
+    fn load_certs(path: &::std::path::Path) -> io::Result<Vec<CertificateDer<'static>>> {
+        let certs = CertificateDer::pem_file_iter(path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|error| invalid(format!("{}: {}", path.display(), error)))?;
+        if certs.is_empty() {
+            return Err(invalid(format!("{}: no certificates found", path.display())));
+        }
+        Ok(certs)
+    }
+
+    fn load_key(path: &::std::path::Path) -> io::Result<PrivateKeyDer<'static>> {
+        PrivateKeyDer::from_pem_file(path)
+            .map_err(|error| invalid(format!("{}: {}", path.display(), error)))
+    }
+
+    /// Client and server configurations derived from a `TlsConfig`.
+    struct Configs {
+        client: Arc<ClientConfig>,
+        server: Arc<ServerConfig>,
+    }
+
+    impl Configs {
+        fn load(config: &TlsConfig) -> io::Result<Self> {
+
+            // Use `ring` explicitly, rather than relying on a process-wide default provider.
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(&config.ca)? {
+                roots.add(cert).map_err(invalid)?;
+            }
+            let roots = Arc::new(roots);
+            let certs = load_certs(&config.cert)?;
+            let key = load_key(&config.key)?;
+
+            let client =
+            ClientConfig::builder_with_provider(provider.clone())
+                .with_safe_default_protocol_versions()
+                .map_err(invalid)?
+                .with_root_certificates(roots.clone())
+                .with_client_auth_cert(certs.clone(), key.clone_key())
+                .map_err(invalid)?;
+
+            let verifier =
+            WebPkiClientVerifier::builder_with_provider(roots, provider.clone())
+                .build()
+                .map_err(invalid)?;
+
+            let server =
+            ServerConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()
+                .map_err(invalid)?
+                .with_client_cert_verifier(verifier)
+                .with_single_cert(certs, key)
+                .map_err(invalid)?;
+
+            Ok(Configs { client: Arc::new(client), server: Arc::new(server) })
+        }
+    }
+
+    /// Wraps each established connection in a TLS session.
+    ///
+    /// The input and output are indexed by process, as in `networking::create_sockets`.
+    /// Connections to processes with a lower index were initiated by this process, which
+    /// then acts as the TLS client. Handshakes complete before this method returns, so that
+    /// misconfigured certificates are reported during initialization.
+    pub fn secure(
+        sockets: Vec<Option<TcpStream>>,
+        addresses: &[String],
+        my_index: usize,
+        config: &TlsConfig) -> io::Result<Vec<Option<TlsStream>>>
+    {
+        let configs = Configs::load(config)?;
+        let mut results = Vec::with_capacity(sockets.len());
+        for (index, socket) in sockets.into_iter().enumerate() {
+            let stream = match socket {
+                Some(socket) if index < my_index => {
+                    Some(TlsStream::connect(socket, server_name(&addresses[index])?, configs.client.clone())?)
+                },
+                Some(socket) => Some(TlsStream::accept(socket, configs.server.clone())?),
+                None => None,
+            };
+            results.push(stream);
+        }
+        Ok(results)
+    }
+
+    /// Extracts the host from an address of the form `host:port`.
+    fn server_name(address: &str) -> io::Result<ServerName<'static>> {
+        let host = match address.rfind(':') {
+            Some(position) => &address[.. position],
+            None => address,
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        ServerName::try_from(host.to_owned()).map_err(|error| invalid(format!("{}: {}", address, error)))
+    }
+
+    /// State shared by all handles to a TLS session.
+    struct Shared {
+        connection: Connection,
+        // All writes to the socket happen under the lock, so that records are not interleaved.
+        socket: TcpStream,
+    }
+
+    impl Shared {
+        /// Writes any pending TLS records to the socket.
+        fn send_pending(&mut self) -> io::Result<()> {
+            while self.connection.wants_write() {
+                self.connection.write_tls(&mut self.socket)?;
+            }
+            Ok(())
+        }
+    }
+
+    /// A TLS session over a `TcpStream`, whose handles may be used from different threads.
+    ///
+    /// Handles share the session state behind a lock, which is held while encrypting and
+    /// decrypting data but not while waiting to read from the socket, so that a blocked
+    /// reader does not prevent writes.
+    pub struct TlsStream {
+        shared: Arc<Mutex<Shared>>,
+        socket: TcpStream,
+        buffer: Vec<u8>,
+        offset: usize,
+        length: usize,
+    }
+
+    impl TlsStream {
+        /// Establishes a session as the client, verifying the server against `name`.
+        pub fn connect(socket: TcpStream, name: ServerName<'static>, config: Arc<ClientConfig>) -> io::Result<Self> {
+            let connection = ClientConnection::new(config, name).map_err(invalid)?;
+            Self::handshake(socket, connection.into())
+        }
+
+        /// Establishes a session as the server, requiring a certificate from the client.
+        pub fn accept(socket: TcpStream, config: Arc<ServerConfig>) -> io::Result<Self> {
+            let connection = ServerConnection::new(config).map_err(invalid)?;
+            Self::handshake(socket, connection.into())
+        }
+
+        fn handshake(mut socket: TcpStream, mut connection: Connection) -> io::Result<Self> {
+            while connection.is_handshaking() {
+                connection.complete_io(&mut socket)?;
+            }
+            let reader = socket.try_clone()?;
+            Ok(TlsStream {
+                shared: Arc::new(Mutex::new(Shared { connection, socket })),
+                socket: reader,
+                buffer: vec![0; 1 << 16],
+                offset: 0,
+                length: 0,
+            })
+        }
+    }
+
+    impl Read for TlsStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            loop {
+                {
+                    let mut shared = self.shared.lock().expect("TLS session lock poisoned");
+                    match shared.connection.reader().read(buf) {
+                        Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => { },
+                        result => return result,
+                    }
+                    // All plaintext is consumed, so the session can accept more records.
+                    if self.offset < self.length {
+                        let mut pending = &self.buffer[self.offset .. self.length];
+                        self.offset += shared.connection.read_tls(&mut pending)?;
+                        let processed = shared.connection.process_new_packets();
+                        // Send any responses, including alerts describing errors.
+                        shared.send_pending()?;
+                        processed.map_err(invalid)?;
+                        continue;
+                    }
+                }
+                self.offset = 0;
+                self.length = self.socket.read(&mut self.buffer[..])?;
+                if self.length == 0 {
+                    // The socket closed without a TLS close notification.
+                    return Ok(0);
+                }
+            }
+        }
+    }
+
+    impl Write for TlsStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut shared = self.shared.lock().expect("TLS session lock poisoned");
+            let written = shared.connection.writer().write(buf)?;
+            shared.send_pending()?;
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            let mut shared = self.shared.lock().expect("TLS session lock poisoned");
+            shared.connection.writer().flush()?;
+            shared.send_pending()?;
+            shared.socket.flush()
+        }
+    }
+
+    impl Stream for TlsStream {
+        fn try_clone(&self) -> io::Result<Self> {
+            Ok(TlsStream {
+                shared: self.shared.clone(),
+                socket: self.socket.try_clone()?,
+                buffer: vec![0; 1 << 16],
+                offset: 0,
+                length: 0,
+            })
+        }
+
+        fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+            let notified = if how != Shutdown::Read {
+                let mut shared = self.shared.lock().expect("TLS session lock poisoned");
+                shared.connection.send_close_notify();
+                shared.send_pending()
+            }
+            else { Ok(()) };
+            // Shut down the socket regardless, to release any blocked readers.
+            let shutdown = self.socket.shutdown(how);
+            notified.and(shutdown)
+        }
+    }
+}
@@ -1,22 +1,45 @@
 //! A type that can unpark specific threads.
 
 use std::thread::Thread;
+#[cfg(feature = "event-loop")]
+use std::sync::Arc;
 
 /// Can unpark a specific thread.
 #[derive(Clone)]
 pub struct Buzzer {
     thread: Thread,
+    // Wakes a thread blocked on a `mio::Poll`, rather than parked.
+    #[cfg(feature = "event-loop")]
+    waker: Option<Arc<mio::Waker>>,
 }
 
 impl Buzzer {
     /// Creates a new buzzer for the current thread.
     pub fn new() -> Self {
         Self {
-            thread: std::thread::current()
+            thread: std::thread::current(),
+            #[cfg(feature = "event-loop")]
+            waker: None,
+        }
+    }
+    /// Creates a buzzer for the current thread, which blocks on the `mio::Poll` of `waker`.
+    #[cfg(feature = "event-loop")]
+    pub fn from_waker(waker: Arc<mio::Waker>) -> Self {
+        Self {
+            thread: std::thread::current(),
+            waker: Some(waker),
         }
     }
     /// Unparks the target thread.
     pub fn buzz(&self) {
+        #[cfg(feature = "event-loop")]
+        {
+            if let Some(waker) = self.waker.as_ref() {
+                // An event loop that has exited has nothing to be woken for.
+                let _ = waker.wake();
+                return;
+            }
+        }
         self.thread.unpark()
     }
-}
\ No newline at end of file
+}
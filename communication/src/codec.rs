@@ -0,0 +1,224 @@
+//! Encodings for data that do not implement `Abomonation`.
+//!
+//! Data sent between processes are encoded with `abomonation`, which is fast and allows data
+//! to be read in place from received buffers, but which is unsafe and cannot be implemented for
+//! many types, for example those holding references. A `Codec` describes an alternative, safe
+//! encoding for a type. Types opt in one at a time, either by implementing `Codec` directly or,
+//! with the `codec` feature, by implementing the marker trait `Bincode` to be encoded with
+//! `bincode` through their serde implementations.
+//!
+//! Wrapping a value in `Coded` makes it usable with channels whatever its encoding, as `Coded`
+//! implements `Abomonation` (or, with the `bincode` feature, serde's traits) using the codec.
+//! Received values are not copied out of the received buffer: a received `Coded` refers to its
+//! encoding in place, and is decoded only when read. Cloning it copies the encoding, and does
+//! not decode it.
+//!
+//! # Examples
+//! ```
+//! use std::io::{self, Write};
+//! use timely_communication::codec::{Codec, Coded};
+//!
+//! // A type that does not implement `Abomonation`.
+//! #[derive(Clone, Debug, PartialEq)]
+//! struct Name(Box<str>);
+//!
+//! impl Codec for Name {
+//!     fn encoded_len(&self) -> usize { self.0.len() }
+//!     fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> { writer.write_all(self.0.as_bytes()) }
+//!     fn decode(bytes: &[u8]) -> io::Result<Self> {
+//!         std::str::from_utf8(bytes)
+//!             .map(|name| Name(name.into()))
+//!             .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+//!     }
+//! }
+//!
+//! let name = Name("timely".into());
+//! let message = timely_communication::Message::from_typed(vec![Coded::new(name.clone())]);
+//!
+//! let mut bytes = Vec::new();
+//! message.into_bytes(&mut bytes);
+//! let received = unsafe { timely_communication::Message::<Vec<Coded<Name>>>::from_bytes(timely_bytes::arc::Bytes::from(bytes)) };
+//!
+//! let decoded = received.into_typed().pop().unwrap().into_inner();
+//! assert_eq!(decoded, name);
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Write};
+
+use serde::{Serialize, Deserialize};
+#[cfg(feature = "codec")]
+use serde::de::DeserializeOwned;
+
+/// A safe encoding of values as bytes.
+pub trait Codec: Sized {
+    /// The number of bytes `encode` writes for `self`.
+    fn encoded_len(&self) -> usize;
+    /// Writes the encoding of `self` to `writer`.
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+    /// Reads a value from its encoding.
+    fn decode(bytes: &[u8]) -> io::Result<Self>;
+}
+
+/// Opts a type in to encoding with `bincode`, through its serde implementations.
+///
+/// This requires the `codec` feature.
+///
+/// # Examples
+/// ```
+/// use timely_communication::codec::{Bincode, Coded};
+///
+/// #[derive(Clone, Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+/// enum Tree { Leaf(String), Node(Box<Tree>, Box<Tree>) }
+///
+/// impl Bincode for Tree { }
+///
+/// let tree = Tree::Node(Box::new(Tree::Leaf("a".to_owned())), Box::new(Tree::Leaf("b".to_owned())));
+/// assert_eq!(Coded::new(tree.clone()).into_inner(), tree);
+/// ```
+#[cfg(feature = "codec")]
+pub trait Bincode: Serialize+DeserializeOwned { }
+
+#[cfg(feature = "codec")]
+impl<T: Bincode> Codec for T {
+    fn encoded_len(&self) -> usize {
+        ::bincode::serialized_size(self).expect("bincode::serialized_size() failed") as usize
+    }
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        ::bincode::serialize_into(writer, self).map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))
+    }
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        ::bincode::deserialize(bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+/// A value sent along channels using its `Codec`.
+pub struct Coded<T> {
+    state: State<T>,
+}
+
+enum State<T> {
+    /// A value held in memory.
+    Decoded(T),
+    /// An encoded value, held in memory.
+    Encoded(Vec<u8>),
+    /// The location of an encoded value, in the received buffer holding this `Coded`.
+    Received(*const u8, usize),
+}
+
+// A `Received` value is only created by `exhume`, which points it into the buffer of a received
+// message, and the bytes it refers to are never written afterwards. Timely gives access to the
+// contents of received messages only by reference, for as long as the message and its buffer
+// live, and takes values out of them by cloning, which copies the bytes into `Encoded`. The
+// pointer is therefore valid whenever the `Coded` is accessible, and from any thread, as the
+// buffer is shared immutably.
+unsafe impl<T: Send> Send for Coded<T> { }
+unsafe impl<T: Sync> Sync for Coded<T> { }
+
+impl<T: Codec> Coded<T> {
+    /// Wraps a value.
+    pub fn new(value: T) -> Self {
+        Coded { state: State::Decoded(value) }
+    }
+    /// Unwraps the value, decoding it if necessary.
+    pub fn into_inner(self) -> T {
+        match self.state {
+            State::Decoded(value) => value,
+            _ => self.decode(),
+        }
+    }
+    /// A reference to the value, or a decoded copy if it is encoded.
+    pub fn get(&self) -> Cow<'_, T> where T: Clone {
+        match self.state {
+            State::Decoded(ref value) => Cow::Borrowed(value),
+            _ => Cow::Owned(self.decode()),
+        }
+    }
+    /// The encoding of the value, which is computed if the value is held in memory.
+    fn encoded(&self) -> Cow<'_, [u8]> {
+        match self.state {
+            State::Decoded(ref value) => {
+                let mut bytes = Vec::with_capacity(value.encoded_len());
+                value.encode(&mut bytes).expect("Codec::encode() failed");
+                Cow::Owned(bytes)
+            },
+            State::Encoded(ref bytes) => Cow::Borrowed(&bytes[..]),
+            State::Received(ptr, len) => Cow::Borrowed(unsafe { ::std::slice::from_raw_parts(ptr, len) }),
+        }
+    }
+    fn decode(&self) -> T {
+        T::decode(&self.encoded()).expect("Codec::decode() failed")
+    }
+}
+
+impl<T: Codec> From<T> for Coded<T> {
+    fn from(value: T) -> Self {
+        Coded::new(value)
+    }
+}
+
+impl<T: Codec+Clone> Clone for Coded<T> {
+    fn clone(&self) -> Self {
+        match self.state {
+            State::Decoded(ref value) => Coded::new(value.clone()),
+            _ => Coded { state: State::Encoded(self.encoded().into_owned()) },
+        }
+    }
+}
+
+impl<T: Codec+fmt::Debug> fmt::Debug for Coded<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.state {
+            State::Decoded(ref value) => f.debug_tuple("Coded").field(value).finish(),
+            _ => f.debug_tuple("Coded").field(&self.decode()).finish(),
+        }
+    }
+}
+
+impl<T: Codec> ::abomonation::Abomonation for Coded<T> {
+    unsafe fn entomb<W: Write>(&self, write: &mut W) -> io::Result<()> {
+        let encoded = self.encoded();
+        write.write_all(&(encoded.len() as u64).to_le_bytes())?;
+        write.write_all(&encoded)?;
+        write.write_all(&[0u8; 8][.. padding(encoded.len())])
+    }
+    unsafe fn exhume<'b>(&mut self, bytes: &'b mut [u8]) -> Option<&'b mut [u8]> {
+        if bytes.len() < 8 { return None; }
+        let (length, rest) = bytes.split_at_mut(8);
+        let mut buffer = [0u8; 8];
+        buffer.copy_from_slice(length);
+        let length = u64::from_le_bytes(buffer) as usize;
+        if rest.len() < length + padding(length) { return None; }
+        let (encoded, rest) = rest.split_at_mut(length + padding(length));
+        // The bytes of `self` were copied from a value that is not ours to drop.
+        ::std::ptr::write(self, Coded { state: State::Received(encoded.as_ptr(), length) });
+        Some(rest)
+    }
+    fn extent(&self) -> usize {
+        let length = match self.state {
+            State::Decoded(ref value) => value.encoded_len(),
+            State::Encoded(ref bytes) => bytes.len(),
+            State::Received(_, len) => len,
+        };
+        8 + length + padding(length)
+    }
+}
+
+/// Bytes following an encoding of `length` bytes, so that data after it remain aligned.
+fn padding(length: usize) -> usize {
+    (8 - length % 8) % 8
+}
+
+impl<T: Codec> Serialize for Coded<T> {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.encoded())
+    }
+}
+
+impl<'de, T: Codec> Deserialize<'de> for Coded<T> {
+    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = <Vec<u8>>::deserialize(deserializer)?;
+        T::decode(&bytes).map(Coded::new).map_err(::serde::de::Error::custom)
+    }
+}
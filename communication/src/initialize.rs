@@ -1,17 +1,27 @@
 //! Initialization logic for a generic instance of the `Allocate` channel allocation trait.
 
 use std::thread;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
 #[cfg(feature = "getopts")]
 use std::io::BufRead;
 #[cfg(feature = "getopts")]
 use getopts;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::PathBuf;
+#[cfg(feature = "getopts")]
+use std::time::Duration;
 
 use std::any::Any;
 
 use crate::allocator::thread::ThreadBuilder;
-use crate::allocator::{AllocateBuilder, Process, Generic, GenericBuilder};
-use crate::allocator::zero_copy::initialize::initialize_networking;
+use crate::allocator::{Allocate, AllocateBuilder, Process, Generic, GenericBuilder};
+use crate::allocator::zero_copy::initialize::{initialize_networking, CommsGuard, ConnectionOptions};
+#[cfg(unix)]
+use crate::allocator::zero_copy::initialize::initialize_networking_unix;
+use crate::allocator::zero_copy::tls::TlsConfig;
+use crate::allocator::zero_copy::compression::Compression;
+use crate::networking::{Retry, Transport};
 
 use crate::logging::{CommunicationSetup, CommunicationEvent};
 use logging_core::Logger;
@@ -31,13 +41,30 @@ pub enum Configuration {
         process: usize,
         /// Addresses of all processes
         addresses: Vec<String>,
-        /// Verbosely report connection process
-        report: bool,
+        /// How connections to other processes are established and served
+        options: Box<ConnectionOptions>,
         /// Closure to create a new logger for a communication thread
         log_fn: Box<dyn Fn(CommunicationSetup) -> Option<Logger<CommunicationEvent, CommunicationSetup>> + Send + Sync>,
     }
 }
 
+impl Configuration {
+    /// Describes one of several processes, each with `threads` workers, connected as described
+    /// by `options`, and without communication logging.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely_communication::{Configuration, ConnectionOptions};
+    ///
+    /// let options = ConnectionOptions { report: true, ..ConnectionOptions::default() };
+    /// let addresses = vec!["localhost:2101".to_owned(), "localhost:2102".to_owned()];
+    /// let config = Configuration::cluster(2, 0, addresses, options);
+    /// ```
+    pub fn cluster(threads: usize, process: usize, addresses: Vec<String>, options: ConnectionOptions) -> Configuration {
+        Configuration::Cluster { threads, process, addresses, options: Box::new(options), log_fn: Box::new(|_| None) }
+    }
+}
+
 #[cfg(feature = "getopts")]
 impl Configuration {
 
@@ -50,6 +77,16 @@ impl Configuration {
         opts.optopt("n", "processes", "number of processes", "NUM");
         opts.optopt("h", "hostfile", "text file whose lines are process addresses", "FILE");
         opts.optflag("r", "report", "reports connection progress");
+        opts.optflag("f", "tolerate-failures", "reports lost processes to workers rather than panicking");
+        opts.optopt("t", "transport", "how processes exchange data: tcp (default), unix, or shm", "KIND");
+        opts.optopt("", "tls-ca", "PEM file of authorities trusted to sign peer certificates", "FILE");
+        opts.optopt("", "tls-cert", "PEM file of this process's certificate chain", "FILE");
+        opts.optopt("", "tls-key", "PEM file of this process's private key", "FILE");
+        opts.optopt("", "compression", "how to compress connections between processes: lz4", "KIND");
+        opts.optopt("", "connect-timeout", "seconds allowed to connect to other processes (default: no limit)", "SECS");
+        opts.optopt("", "rendezvous", "directory through which processes exchange their addresses", "DIR");
+        opts.optopt("", "advertise-host", "host name or IP address bound and published through a rendezvous (default: all interfaces, published as this host)", "HOST");
+        opts.optopt("", "network-threads", "number of event loop threads serving connections (default: two per connection)", "NUM");
 
         opts
     }
@@ -62,13 +99,47 @@ impl Configuration {
 
         opts.parse(args)
             .map_err(|e| format!("{:?}", e))
-            .map(|matches| {
+            .and_then(|matches| {
 
             // let mut config = Configuration::new(1, 0, Vec::new());
             let threads = matches.opt_str("w").map(|x| x.parse().unwrap_or(1)).unwrap_or(1);
             let process = matches.opt_str("p").map(|x| x.parse().unwrap_or(0)).unwrap_or(0);
             let processes = matches.opt_str("n").map(|x| x.parse().unwrap_or(1)).unwrap_or(1);
             let report = matches.opt_present("report");
+            let tolerate_failures = matches.opt_present("tolerate-failures");
+            let tls = match (matches.opt_str("tls-ca"), matches.opt_str("tls-cert"), matches.opt_str("tls-key")) {
+                (Some(ca), Some(cert), Some(key)) => Some(TlsConfig::new(ca, cert, key)),
+                (None, None, None) => None,
+                _ => return Err("--tls-ca, --tls-cert, and --tls-key must be supplied together".to_owned()),
+            };
+            let transport = match matches.opt_str("transport").as_ref().map(|x| &x[..]) {
+                None | Some("tcp") => Transport::Tcp,
+                Some("unix") => Transport::Unix,
+                Some("shm") => Transport::SharedMemory,
+                Some(other) => return Err(format!("unrecognized transport: {}", other)),
+            };
+            let compression = match matches.opt_str("compression").as_ref().map(|x| &x[..]) {
+                None | Some("none") => None,
+                Some("lz4") => Some(Compression::Lz4),
+                Some(other) => return Err(format!("unrecognized compression: {}", other)),
+            };
+            let mut retry = Retry::default();
+            if let Some(timeout) = matches.opt_str("connect-timeout") {
+                let seconds: f64 = timeout.parse().map_err(|_| format!("invalid connect timeout: {}", timeout))?;
+                if !(seconds >= 0.0 && seconds.is_finite()) {
+                    return Err(format!("invalid connect timeout: {}", timeout));
+                }
+                retry.timeout = Some(Duration::from_secs_f64(seconds));
+            }
+            let rendezvous = matches.opt_str("rendezvous").map(PathBuf::from);
+            let network_threads = match matches.opt_str("network-threads") {
+                Some(number) => Some(number.parse().map_err(|_| format!("invalid number of network threads: {}", number))?),
+                None => None,
+            };
+            let advertised_host = matches.opt_str("advertise-host");
+            if rendezvous.is_some() && transport != Transport::Tcp {
+                return Err("--rendezvous is only supported with the TCP transport".to_owned());
+            }
 
             assert!(process < processes);
 
@@ -83,23 +154,48 @@ impl Configuration {
                         panic!("could only read {} addresses from {}, but -n: {}", addresses.len(), hosts, processes);
                     }
                 }
-                else {
+                else if rendezvous.is_some() {
+                    // Bind the advertised host if supplied, and otherwise every interface, so
+                    // that processes on other hosts can connect.
+                    let address = match advertised_host.as_ref() {
+                        Some(host) => match host.parse::<::std::net::IpAddr>() {
+                            Ok(ip) => ::std::net::SocketAddr::new(ip, 0).to_string(),
+                            Err(_) => format!("{}:0", host),
+                        },
+                        None => "0.0.0.0:0".to_owned(),
+                    };
+                    for _ in 0..processes {
+                        addresses.push(address.clone());
+                    }
+                }
+                else if transport == Transport::Tcp {
                     for index in 0..processes {
                         addresses.push(format!("localhost:{}", 2101 + index));
                     }
                 }
+                else {
+                    for index in 0..processes {
+                        let path = ::std::env::temp_dir().join(format!("timely-{}.sock", 2101 + index));
+                        addresses.push(path.to_string_lossy().into_owned());
+                    }
+                }
 
                 assert!(processes == addresses.len());
-                Configuration::Cluster {
-                    threads,
-                    process,
-                    addresses,
+                let options = ConnectionOptions {
+                    transport,
                     report,
-                    log_fn: Box::new( | _ | None),
-                }
+                    tolerate_failures,
+                    compression,
+                    retry,
+                    rendezvous,
+                    advertised_host,
+                    tls,
+                    network_threads,
+                };
+                Ok(Configuration::cluster(threads, process, addresses, options))
             }
-            else if threads > 1 { Configuration::Process(threads) }
-            else { Configuration::Thread }
+            else if threads > 1 { Ok(Configuration::Process(threads)) }
+            else { Ok(Configuration::Thread) }
         })
     }
 
@@ -112,8 +208,21 @@ impl Configuration {
             Configuration::Process(threads) => {
                 Ok((Process::new_vector(threads).into_iter().map(|x| GenericBuilder::Process(x)).collect(), Box::new(())))
             },
-            Configuration::Cluster { threads, process, addresses, report, log_fn } => {
-                match initialize_networking(addresses, process, threads, report, log_fn) {
+            Configuration::Cluster { threads, process, addresses, options, log_fn } => {
+                let result = match options.transport {
+                    Transport::Tcp => {
+                        initialize_networking(addresses, process, threads, *options, log_fn)
+                    },
+                    #[cfg(unix)]
+                    _ => {
+                        initialize_networking_unix(addresses, process, threads, *options, log_fn)
+                    },
+                    #[cfg(not(unix))]
+                    _ => {
+                        return Err(format!("the {:?} transport requires a Unix platform", options.transport));
+                    },
+                };
+                match result {
                     Ok((stuff, guard)) => {
                         Ok((stuff.into_iter().map(|x| GenericBuilder::ZeroCopy(x)).collect(), Box::new(guard)))
                     },
@@ -250,7 +359,52 @@ pub fn initialize<T:Send+'static, F: Fn(Generic)->T+Send+Sync+'static>(
 /// ```
 pub fn initialize_from<A, T, F>(
     builders: Vec<A>,
-    _others: Box<dyn Any>,
+    others: Box<dyn Any>,
+    func: F,
+) -> Result<WorkerGuards<T>,String>
+where
+    A: AllocateBuilder+'static,
+    T: Send+'static,
+    F: Fn(<A as AllocateBuilder>::Allocator)->T+Send+Sync+'static
+{
+    initialize_from_cancellable(builders, others, CancellationToken::new(), func)
+}
+
+/// Initializes computation and runs a distributed computation, which can be cancelled.
+///
+/// This version of `initialize_from` associates the computation with `cancellation`, which
+/// the returned `WorkerGuards` cancel on `shutdown`. The worker logic should hold a clone of
+/// the token, and return promptly once it is cancelled. Worker threads parked in
+/// `Allocate::await_events` are unparked when the token is cancelled.
+///
+/// If a worker panics, the panic is recorded with `CancellationToken::fail`, which cancels the
+/// token, so that the other workers can abort rather than await the failed worker. Announcing
+/// the failure to workers in other processes is left to the worker logic, which may do so with
+/// `Allocate::announce_failure` before the allocator is dropped.
+///
+/// # Examples
+/// ```
+/// use timely_communication::{Allocate, CancellationToken};
+///
+/// let builders = timely_communication::allocator::process::Process::new_vector(2);
+/// let cancellation = CancellationToken::new();
+/// let token = cancellation.clone();
+///
+/// // each worker waits for events until the computation is cancelled.
+/// let guards = timely_communication::initialize_from_cancellable(builders, Box::new(()), cancellation, move |allocator| {
+///     while !token.is_cancelled() {
+///         allocator.await_events(None);
+///     }
+///     allocator.index()
+/// }).unwrap();
+///
+/// let results = guards.shutdown();
+/// assert_eq!(results.into_iter().map(|r| r.unwrap()).collect::<Vec<_>>(), vec![0, 1]);
+/// ```
+pub fn initialize_from_cancellable<A, T, F>(
+    builders: Vec<A>,
+    others: Box<dyn Any>,
+    cancellation: CancellationToken,
     func: F,
 ) -> Result<WorkerGuards<T>,String>
 where
@@ -262,22 +416,120 @@ where
     let mut guards = Vec::new();
     for (index, builder) in builders.into_iter().enumerate() {
         let clone = logic.clone();
+        let cancellation = cancellation.clone();
         guards.push(thread::Builder::new()
                             .name(format!("worker thread {}", index))
                             .spawn(move || {
+                                cancellation.register(thread::current());
                                 let communicator = builder.build();
-                                (*clone)(communicator)
+                                let worker = communicator.index();
+                                // Record the first panic, which cancels the computation, and name the worker.
+                                catch_unwind(AssertUnwindSafe(|| (*clone)(communicator)))
+                                    .unwrap_or_else(|payload| {
+                                        let failure = WorkerPanic::from_payload(worker, &*payload);
+                                        let message = failure.to_string();
+                                        cancellation.fail(failure);
+                                        resume_unwind(Box::new(message))
+                                    })
                             })
                             .map_err(|e| format!("{:?}", e))?);
     }
 
-    Ok(WorkerGuards { guards, _others })
+    Ok(WorkerGuards { guards, others, cancellation })
+}
+
+/// The panic of a worker, identified by its index among all workers of the computation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorkerPanic {
+    /// The index of the worker that panicked.
+    pub worker: usize,
+    /// The message the worker panicked with.
+    pub message: String,
+}
+
+impl WorkerPanic {
+    /// Describes the panic of `worker` from its payload, as caught by `catch_unwind`.
+    pub fn from_payload(worker: usize, payload: &(dyn Any+Send)) -> Self {
+        let message =
+        if let Some(message) = payload.downcast_ref::<&str>() { (*message).to_owned() }
+        else if let Some(message) = payload.downcast_ref::<String>() { message.clone() }
+        else { "Box<Any>".to_owned() };
+        WorkerPanic { worker, message }
+    }
+}
+
+impl ::std::fmt::Display for WorkerPanic {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "worker {} panicked: {}", self.worker, self.message)
+    }
+}
+
+/// A request to stop a computation, shared by the computation's workers and its guards.
+///
+/// Cancelling the token unparks the worker threads that were started with it, so that
+/// workers awaiting events observe the request promptly.
+///
+/// The token also records the first worker of the computation to panic, if any, in which
+/// case the computation is cancelled and its workers are expected to abort.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    failure: Arc<Mutex<Option<WorkerPanic>>>,
+    threads: Arc<Mutex<Vec<thread::Thread>>>,
+}
+
+impl CancellationToken {
+    /// Creates a token that has not been cancelled.
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Requests that the computation stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        for thread in self.threads.lock().expect("mutex poisoned").iter() {
+            thread.unpark();
+        }
+    }
+    /// Indicates whether the computation has been asked to stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+    /// Records that a worker has panicked, and cancels the computation.
+    ///
+    /// Only the first failure is recorded; later failures are often consequences of it.
+    pub fn fail(&self, failure: WorkerPanic) {
+        {
+            let mut recorded = self.failure.lock().expect("mutex poisoned");
+            if recorded.is_none() {
+                *recorded = Some(failure);
+            }
+        }
+        self.cancel();
+    }
+    /// The first worker of the computation to panic, if any.
+    pub fn failure(&self) -> Option<WorkerPanic> {
+        self.failure.lock().expect("mutex poisoned").clone()
+    }
+    /// Records a worker thread to unpark on cancellation.
+    fn register(&self, thread: thread::Thread) {
+        self.threads.lock().expect("mutex poisoned").push(thread);
+    }
+}
+
+impl ::std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("CancellationToken")
+         .field("cancelled", &self.is_cancelled())
+         .field("failure", &self.failure())
+         .finish()
+    }
 }
 
 /// Maintains `JoinHandle`s for worker threads.
 pub struct WorkerGuards<T:Send+'static> {
     guards: Vec<::std::thread::JoinHandle<T>>,
-    _others: Box<dyn Any>,
+    others: Box<dyn Any>,
+    cancellation: CancellationToken,
 }
 
 impl<T:Send+'static> WorkerGuards<T> {
@@ -287,19 +539,53 @@ impl<T:Send+'static> WorkerGuards<T> {
         &self.guards[..]
     }
 
+    /// The token through which the computation can be cancelled.
+    ///
+    /// The token can be cloned and cancelled from other threads, for example on receipt of a
+    /// signal, after which `join` waits for the workers and tears down network connections.
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
     /// Waits on the worker threads and returns the results they produce.
+    ///
+    /// The error of a worker that panicked names the worker and its panic message. If a worker
+    /// panicked, `cancellation().failure()` reports the first to do so, whose failure the other
+    /// workers report having aborted after.
+    ///
+    /// If the computation has been cancelled, network connections are then torn down without
+    /// waiting indefinitely for remote processes.
     pub fn join(mut self) -> Vec<Result<T, String>> {
+        self.join_workers()
+    }
+
+    /// Requests that the workers stop, and waits on them as `join` does.
+    pub fn shutdown(self) -> Vec<Result<T, String>> {
+        self.cancellation.cancel();
+        self.join()
+    }
+
+    fn join_workers(&mut self) -> Vec<Result<T, String>> {
+        let results =
         self.guards
             .drain(..)
-            .map(|guard| guard.join().map_err(|e| format!("{:?}", e)))
-            .collect()
+            .map(|guard| guard.join().map_err(|payload| {
+                payload.downcast_ref::<String>().cloned().unwrap_or_else(|| format!("{:?}", payload))
+            }))
+            .collect();
+        if self.cancellation.is_cancelled() {
+            if let Some(comms) = self.others.downcast_mut::<CommsGuard>() {
+                comms.shutdown();
+            }
+        }
+        results
     }
 }
 
 impl<T:Send+'static> Drop for WorkerGuards<T> {
     fn drop(&mut self) {
-        for guard in self.guards.drain(..) {
-            guard.join().expect("Worker panic");
+        for result in self.join_workers() {
+            result.expect("Worker panic");
         }
         // println!("WORKER THREADS JOINED");
     }
@@ -77,10 +77,16 @@
 
 #[cfg(feature = "getopts")]
 extern crate getopts;
-#[cfg(feature = "bincode")]
+#[cfg(any(feature = "bincode", feature = "codec"))]
 extern crate bincode;
 #[cfg(feature = "bincode")]
 extern crate serde;
+#[cfg(feature = "tls")]
+extern crate rustls;
+#[cfg(feature = "event-loop")]
+extern crate mio;
+#[cfg(unix)]
+extern crate memmap2;
 
 extern crate abomonation;
 #[macro_use] extern crate abomonation_derive;
@@ -94,6 +100,7 @@ pub mod initialize;
 pub mod logging;
 pub mod message;
 pub mod buzzer;
+pub mod codec;
 
 use std::any::Any;
 
@@ -103,8 +110,9 @@ use serde::{Serialize, Deserialize};
 use abomonation::Abomonation;
 
 pub use allocator::Generic as Allocator;
-pub use allocator::Allocate;
-pub use initialize::{initialize, initialize_from, Configuration, WorkerGuards};
+pub use allocator::{Allocate, Priority};
+pub use allocator::zero_copy::initialize::ConnectionOptions;
+pub use initialize::{initialize, initialize_from, initialize_from_cancellable, CancellationToken, Configuration, WorkerGuards, WorkerPanic};
 pub use message::Message;
 
 /// A composite trait for types that may be used with channels.
@@ -138,11 +146,20 @@ pub trait Push<T> {
     /// Pushes `None`, conventionally signalling a flush.
     #[inline]
     fn done(&mut self) { self.push(&mut None); }
+    /// Indicates that a target has at least its budget of messages outstanding.
+    ///
+    /// Only channels allocated with a budget report this; see `allocator::credit`.
+    /// Pushers continue to accept elements when over budget, but producers may
+    /// prefer to yield until the target catches up.
+    #[inline]
+    fn over_budget(&self) -> bool { false }
 }
 
 impl<T, P: ?Sized + Push<T>> Push<T> for Box<P> {
     #[inline]
     fn push(&mut self, element: &mut Option<T>) { (**self).push(element) }
+    #[inline]
+    fn over_budget(&self) -> bool { (**self).over_budget() }
 }
 
 /// Pulling elements of type `T`.
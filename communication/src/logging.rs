@@ -7,7 +7,7 @@ pub struct CommunicationSetup {
     pub sender: bool,
     /// The process id of the thread.
     pub process: usize,
-    /// The remote process id.
+    /// The remote process id, or `None` for the logger of a worker.
     pub remote: Option<usize>,
 }
 
@@ -18,6 +18,10 @@ pub enum CommunicationEvent {
     Message(MessageEvent),
     /// A state transition.
     State(StateEvent),
+    /// Credits returned for received messages.
+    Credit(CreditEvent),
+    /// A frame written to or read from a compressed connection.
+    Compression(CompressionEvent),
 }
 
 /// An observed message.
@@ -29,6 +33,39 @@ pub struct MessageEvent {
     pub header: crate::networking::MessageHeader,
 }
 
+/// Credits returned by a worker for messages it received.
+///
+/// Credits are only returned for channels allocated with a budget. Credits for messages from
+/// workers in other processes are batched, so that one event may account for many messages.
+/// Within a process, credits return to the sender as each message is received, and are
+/// logged only by the receiving worker, as a receive event.
+#[derive(Abomonation, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct CreditEvent {
+    /// true for send event, false for receive event
+    pub is_send: bool,
+    /// index of the channel whose messages were received.
+    pub channel: usize,
+    /// index of the worker that received the messages.
+    pub source: usize,
+    /// index of the worker that sent the messages, to which credits are returned.
+    pub target: usize,
+    /// number of messages received.
+    pub count: usize,
+}
+
+/// A frame of messages written to or read from a compressed connection.
+///
+/// The ratio of `compressed` to `uncompressed` bytes reports the effectiveness of compression.
+#[derive(Abomonation, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct CompressionEvent {
+    /// true for send event, false for receive event
+    pub is_send: bool,
+    /// number of bytes of the frame on the connection.
+    pub compressed: usize,
+    /// number of bytes of messages in the frame.
+    pub uncompressed: usize,
+}
+
 /// Starting or stopping communication threads.
 #[derive(Abomonation, Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct StateEvent {
@@ -48,3 +85,9 @@ impl From<MessageEvent> for CommunicationEvent {
 impl From<StateEvent> for CommunicationEvent {
     fn from(v: StateEvent) -> CommunicationEvent { CommunicationEvent::State(v) }
 }
+impl From<CreditEvent> for CommunicationEvent {
+    fn from(v: CreditEvent) -> CommunicationEvent { CommunicationEvent::Credit(v) }
+}
+impl From<CompressionEvent> for CommunicationEvent {
+    fn from(v: CompressionEvent) -> CommunicationEvent { CommunicationEvent::Compression(v) }
+}
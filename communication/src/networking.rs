@@ -1,20 +1,41 @@
 //! Networking code for sending and receiving fixed size `Vec<u8>` between machines.
 
 use std::io;
-use std::io::{Read, Result};
-use std::net::{TcpListener, TcpStream};
+use std::io::{Read, Write, Result};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::thread;
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use abomonation::{encode, decode};
 
+use crate::allocator::zero_copy::compression::Compression;
+
 // This constant is sent along immediately after establishing a TCP stream, so
 // that it is easy to sniff out Timely traffic when it is multiplexed with
 // other traffic on the same port.
 const HANDSHAKE_MAGIC: u64 = 0xc2f1fb770118add9;
 
+/// The means by which processes in a cluster exchange data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Transport {
+    /// TCP connections, to addresses of the form `host:port`.
+    #[default]
+    Tcp,
+    /// Unix domain socket connections, to addresses naming socket files.
+    Unix,
+    /// Shared-memory ring buffers between processes on the same host.
+    ///
+    /// Addresses name Unix domain socket files, whose connections are used to set up the
+    /// ring buffers, to wake blocked readers, and to detect lost processes. The ring buffers
+    /// are mapped from files created next to the socket files, and removed once both processes
+    /// have mapped them; placing the sockets on a memory-backed file system such as `/dev/shm`
+    /// avoids writing the buffers back to disk.
+    SharedMemory,
+}
+
 /// Framing data for each `Vec<u8>` transmission, indicating a typed channel, the source and
 /// destination workers, and the length in bytes.
 #[derive(Abomonation, Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -59,71 +80,395 @@ impl MessageHeader {
     }
 }
 
+/// A bidirectional byte stream connecting this process to a remote process.
+///
+/// Each connection is served by a send thread and a receive thread, each of which
+/// holds its own handle to the stream. Streams handed to these threads must be `Send`,
+/// though they may be wrapped in streams that are not, once in their threads.
+pub trait Stream: Read + Write + Sized + 'static {
+    /// Creates an independently owned handle to the same stream.
+    fn try_clone(&self) -> Result<Self>;
+    /// Shuts down the read half, the write half, or both halves of the stream.
+    fn shutdown(&self, how: Shutdown) -> Result<()>;
+}
+
+impl Stream for TcpStream {
+    fn try_clone(&self) -> Result<Self> { TcpStream::try_clone(self) }
+    fn shutdown(&self, how: Shutdown) -> Result<()> { TcpStream::shutdown(self, how) }
+}
+
+#[cfg(unix)]
+impl Stream for ::std::os::unix::net::UnixStream {
+    fn try_clone(&self) -> Result<Self> { ::std::os::unix::net::UnixStream::try_clone(self) }
+    fn shutdown(&self, how: Shutdown) -> Result<()> { ::std::os::unix::net::UnixStream::shutdown(self, how) }
+}
+
+/// Identifies this process to a newly connected peer, and proposes a compression for the connection.
+///
+/// Returns the compression that the peer agrees to, as it replies from `recv_handshake`.
+fn send_handshake<S: Read+Write>(stream: &mut S, my_index: usize, compression: Option<Compression>) -> Result<Option<Compression>> {
+    unsafe { encode(&HANDSHAKE_MAGIC, stream) }?;
+    unsafe { encode(&(my_index as u64), stream) }?;
+    unsafe { encode(&Compression::code(compression), stream) }?;
+    let mut buffer = [0u8;8];
+    stream.read_exact(&mut buffer)?;
+    let agreed = *unsafe { decode::<u64>(&mut buffer) }.expect("failed to decode compression").0;
+    Ok(Compression::from_code(agreed))
+}
+
+/// Reads the identity of a newly connected peer, and replies with the compression of the connection.
+///
+/// The connection is compressed only if the peer proposes the same compression as `compression`.
+fn recv_handshake<S: Read+Write>(stream: &mut S, compression: Option<Compression>) -> Result<(usize, Option<Compression>)> {
+    let mut buffer = [0u8;24];
+    stream.read_exact(&mut buffer)?;
+    let (magic, buffer) = unsafe { decode::<u64>(&mut buffer) }.expect("failed to decode magic");
+    if magic != &HANDSHAKE_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+            "received incorrect timely handshake"));
+    }
+    let (identifier, buffer) = unsafe { decode::<u64>(buffer) }.expect("failed to decode worker index");
+    let identifier = *identifier as usize;
+    let proposed = *unsafe { decode::<u64>(buffer) }.expect("failed to decode compression").0;
+    let agreed = Compression::agree(compression, Compression::from_code(proposed));
+    unsafe { encode(&Compression::code(agreed), stream) }?;
+    Ok((identifier, agreed))
+}
+
+/// Connections to each process, and the compression agreed for each connection.
+pub type Connections<S> = (Vec<Option<S>>, Vec<Option<Compression>>);
+
+/// How persistently connections to other processes are attempted.
+///
+/// Failed attempts to connect are retried after a delay, which starts at `initial_delay`
+/// and doubles with each attempt, up to `max_delay`. If a `timeout` is set, establishing all
+/// connections (including any rendezvous) fails with `ErrorKind::TimedOut` once it elapses;
+/// otherwise attempts continue indefinitely.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Retry {
+    /// The delay before the first retry.
+    pub initial_delay: Duration,
+    /// The largest delay between retries.
+    pub max_delay: Duration,
+    /// The time allowed to establish all connections.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            timeout: None,
+        }
+    }
+}
+
+/// Tracks the delay before the next retry, and the deadline for connecting.
+#[derive(Clone, Copy, Debug)]
+struct Backoff {
+    delay: Duration,
+    max_delay: Duration,
+    deadline: Option<Instant>,
+}
+
+impl Backoff {
+    fn new(retry: Retry) -> Self {
+        Backoff {
+            delay: retry.initial_delay,
+            max_delay: retry.max_delay,
+            deadline: retry.timeout.map(|timeout| Instant::now() + timeout),
+        }
+    }
+    /// The time remaining before the deadline, if there is one.
+    fn remaining(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+    /// Sleeps before the next attempt, or fails if the deadline has passed.
+    fn wait(&mut self, failure: &dyn Fn() -> String) -> Result<()> {
+        let delay = match self.remaining() {
+            Some(remaining) if remaining == Duration::from_secs(0) => {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, failure()));
+            },
+            Some(remaining) => ::std::cmp::min(self.delay, remaining),
+            None => self.delay,
+        };
+        sleep(delay);
+        self.delay = ::std::cmp::min(self.delay * 2, self.max_delay);
+        Ok(())
+    }
+    /// A read timeout that fails a handshake that would outlast the deadline.
+    fn read_timeout(&self) -> Option<Duration> {
+        self.remaining().map(|remaining| ::std::cmp::max(remaining, Duration::from_millis(1)))
+    }
+    /// Accepts a connection with `accept`, which should not block, until the deadline.
+    fn accept<S>(&self, mut accept: impl FnMut() -> Result<S>) -> Result<S> {
+        loop {
+            match accept() {
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    if self.remaining() == Some(Duration::from_secs(0)) {
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out awaiting connections"));
+                    }
+                    sleep(Duration::from_millis(10));
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
 /// Creates socket connections from a list of host addresses.
 ///
 /// The item at index i in the resulting vec, is a Some(TcpSocket) to process i, except
-/// for item `my_index` which is None (no socket to self).
-pub fn create_sockets(addresses: Vec<String>, my_index: usize, noisy: bool) -> Result<Vec<Option<TcpStream>>> {
+/// for item `my_index` which is None (no socket to self). Each connection is compressed
+/// with `compression` if the process at its other end proposes the same compression.
+///
+/// If a `rendezvous` directory is supplied, each address is only where its process binds,
+/// perhaps with port 0, and the addresses of processes are exchanged through the directory, as
+/// described by `rendezvous`. Each process publishes its bound port with `advertised_host`, if
+/// supplied, and otherwise its bound address, in which an unspecified IP address is replaced
+/// by the name of the host.
+pub fn create_sockets(addresses: Vec<String>, my_index: usize, noisy: bool, compression: Option<Compression>, retry: Retry, rendezvous: Option<&Path>, advertised_host: Option<&str>) -> Result<Connections<TcpStream>> {
+
+    let processes = addresses.len();
+    let listener = TcpListener::bind(&addresses[my_index][..])?;
 
-    let hosts1 = Arc::new(addresses);
-    let hosts2 = hosts1.clone();
+    let started = Instant::now();
+    let addresses = match rendezvous {
+        Some(directory) => {
+            let address = advertised_address(listener.local_addr()?, advertised_host)?;
+            self::rendezvous(directory, my_index, &address, retry)?
+        },
+        None => addresses,
+    };
+    // Connections have whatever time the rendezvous left.
+    let retry = Retry { timeout: retry.timeout.map(|timeout| timeout.saturating_sub(started.elapsed())), ..retry };
 
-    let start_task = thread::spawn(move || start_connections(hosts1, my_index, noisy));
-    let await_task = thread::spawn(move || await_connections(hosts2, my_index, noisy));
+    let hosts = Arc::new(addresses);
+    let start_task = thread::spawn(move || start_connections(hosts, my_index, noisy, compression, retry));
+    let await_task = thread::spawn(move || await_connections(listener, processes, my_index, noisy, compression, retry));
 
-    let mut results = start_task.join().unwrap()?;
+    let (mut results, mut agreed) = start_task.join().unwrap()?;
     results.push(None);
-    let to_extend = await_task.join().unwrap()?;
-    results.extend(to_extend.into_iter());
+    agreed.push(None);
+    let (to_extend, agreed_extend) = await_task.join().unwrap()?;
+    results.extend(to_extend);
+    agreed.extend(agreed_extend);
+
+    if let Some(directory) = rendezvous {
+        // Every process that needed our address has connected to us.
+        ::std::fs::remove_file(rendezvous_file(directory, my_index))?;
+    }
 
     if noisy { println!("worker {}:\tinitialization complete", my_index) }
 
-    Ok(results)
+    Ok((results, agreed))
 }
 
+/// The address by which other processes reach a listener bound to `bound`.
+fn advertised_address(bound: SocketAddr, host: Option<&str>) -> Result<String> {
+    let host = match host {
+        Some(host) => host.to_owned(),
+        None if bound.ip().is_unspecified() => host_name()?,
+        None => return Ok(bound.to_string()),
+    };
+    match host.parse::<IpAddr>() {
+        Ok(ip) => Ok(SocketAddr::new(ip, bound.port()).to_string()),
+        Err(_) => Ok(format!("{}:{}", host, bound.port())),
+    }
+}
 
-/// Result contains connections [0, my_index - 1].
-pub fn start_connections(addresses: Arc<Vec<String>>, my_index: usize, noisy: bool) -> Result<Vec<Option<TcpStream>>> {
-    let results = addresses.iter().take(my_index).enumerate().map(|(index, address)| {
+/// The name of this host, as reported by the operating system.
+fn host_name() -> Result<String> {
+    let names = [
+        ::std::fs::read_to_string("/proc/sys/kernel/hostname").ok(),
+        ::std::fs::read_to_string("/etc/hostname").ok(),
+        ::std::env::var("HOSTNAME").ok(),
+        ::std::env::var("COMPUTERNAME").ok(),
+    ];
+    names
+        .iter()
+        .flatten()
+        .map(|name| name.trim())
+        .find(|name| !name.is_empty())
+        .map(|name| name.to_owned())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "could not determine the host name to advertise"))
+}
+
+/// The file through which process `index` publishes its address.
+fn rendezvous_file(directory: &Path, index: usize) -> PathBuf {
+    directory.join(format!("process-{}", index))
+}
+
+/// Publishes this process's address in `directory`, and discovers the addresses of the
+/// processes it connects to.
+///
+/// The result is `my_index` addresses, the last of which is `address`, as only processes with
+/// lower indices are connected to; the others connect to this process. Each process removes its
+/// file once it has established its connections, but as files are read as soon as they appear,
+/// each launch of a cluster should use a directory that its predecessors' files have left.
+pub fn rendezvous(directory: &Path, my_index: usize, address: &str, retry: Retry) -> Result<Vec<String>> {
+
+    let mut backoff = Backoff::new(retry);
+    ::std::fs::create_dir_all(directory)?;
+
+    // Publish the address atomically, so that it is never read in part.
+    let published = rendezvous_file(directory, my_index);
+    let staged = directory.join(format!(".process-{}-{}", my_index, ::std::process::id()));
+    ::std::fs::write(&staged, address)?;
+    ::std::fs::rename(&staged, &published)?;
+
+    let mut addresses = Vec::with_capacity(my_index + 1);
+    for index in 0 .. my_index {
         loop {
-            match TcpStream::connect(address) {
-                Ok(mut stream) => {
-                    stream.set_nodelay(true).expect("set_nodelay call failed");
-                    unsafe { encode(&HANDSHAKE_MAGIC, &mut stream) }.expect("failed to encode/send handshake magic");
-                    unsafe { encode(&(my_index as u64), &mut stream) }.expect("failed to encode/send worker index");
-                    if noisy { println!("worker {}:\tconnection to worker {}", my_index, index); }
-                    break Some(stream);
-                },
-                Err(error) => {
-                    println!("worker {}:\terror connecting to worker {}: {}; retrying", my_index, index, error);
-                    sleep(Duration::from_secs(1));
+            match ::std::fs::read_to_string(rendezvous_file(directory, index)) {
+                Ok(address) => { addresses.push(address); break; },
+                Err(ref error) if error.kind() == io::ErrorKind::NotFound => {
+                    backoff.wait(&|| format!("timed out awaiting the address of process {} in {}", index, directory.display()))?;
                 },
+                Err(error) => return Err(error),
+            }
+        }
+    }
+    addresses.push(address.to_owned());
+    Ok(addresses)
+}
+
+/// Connects to `address`, retrying until a connection is made or the deadline passes.
+fn connect(address: &str, my_index: usize, index: usize, backoff: &mut Backoff) -> Result<TcpStream> {
+    loop {
+        // Try each address the name resolves to, as `TcpStream::connect` would.
+        let attempt = address.to_socket_addrs().and_then(|resolved| {
+            let mut error = io::Error::new(io::ErrorKind::InvalidInput, "address resolved to nothing");
+            for resolved in resolved {
+                let attempt = match backoff.remaining() {
+                    Some(remaining) if remaining > Duration::from_secs(0) => TcpStream::connect_timeout(&resolved, remaining),
+                    Some(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "no time remains")),
+                    None => TcpStream::connect(resolved),
+                };
+                match attempt {
+                    Ok(stream) => return Ok(stream),
+                    Err(attempt) => error = attempt,
+                }
             }
+            Err(error)
+        });
+        match attempt {
+            Ok(stream) => return Ok(stream),
+            Err(error) => {
+                println!("worker {}:\terror connecting to worker {}: {}; retrying", my_index, index, error);
+                backoff.wait(&|| format!("timed out connecting to process {} at {}: {}", index, address, error))?;
+            },
         }
-    }).collect();
+    }
+}
+
+/// Result contains connections [0, my_index - 1].
+pub fn start_connections(addresses: Arc<Vec<String>>, my_index: usize, noisy: bool, compression: Option<Compression>, retry: Retry) -> Result<Connections<TcpStream>> {
+    let mut backoff = Backoff::new(retry);
+    let mut results = Vec::with_capacity(my_index);
+    let mut agreed = Vec::with_capacity(my_index);
+    for (index, address) in addresses.iter().take(my_index).enumerate() {
+        let mut stream = connect(address, my_index, index, &mut backoff)?;
+        stream.set_nodelay(true).expect("set_nodelay call failed");
+        stream.set_read_timeout(backoff.read_timeout())?;
+        agreed.push(send_handshake(&mut stream, my_index, compression)?);
+        stream.set_read_timeout(None)?;
+        if noisy { println!("worker {}:\tconnection to worker {}", my_index, index); }
+        results.push(Some(stream));
+    }
 
-    Ok(results)
+    Ok((results, agreed))
 }
 
-/// Result contains connections [my_index + 1, addresses.len() - 1].
-pub fn await_connections(addresses: Arc<Vec<String>>, my_index: usize, noisy: bool) -> Result<Vec<Option<TcpStream>>> {
-    let mut results: Vec<_> = (0..(addresses.len() - my_index - 1)).map(|_| None).collect();
-    let listener = TcpListener::bind(&addresses[my_index][..])?;
+/// Result contains connections [my_index + 1, processes - 1], accepted from `listener`.
+pub fn await_connections(listener: TcpListener, processes: usize, my_index: usize, noisy: bool, compression: Option<Compression>, retry: Retry) -> Result<Connections<TcpStream>> {
+    let backoff = Backoff::new(retry);
+    let mut results: Vec<_> = (0..(processes - my_index - 1)).map(|_| None).collect();
+    let mut agreed: Vec<_> = (0..(processes - my_index - 1)).map(|_| None).collect();
+    listener.set_nonblocking(backoff.deadline.is_some())?;
 
-    for _ in (my_index + 1) .. addresses.len() {
-        let mut stream = listener.accept()?.0;
+    for _ in (my_index + 1) .. processes {
+        let mut stream = backoff.accept(|| listener.accept())?.0;
+        stream.set_nonblocking(false)?;
         stream.set_nodelay(true).expect("set_nodelay call failed");
-        let mut buffer = [0u8;16];
-        stream.read_exact(&mut buffer)?;
-        let (magic, mut buffer) = unsafe { decode::<u64>(&mut buffer) }.expect("failed to decode magic");
-        if magic != &HANDSHAKE_MAGIC {
-            return Err(io::Error::new(io::ErrorKind::InvalidData,
-                "received incorrect timely handshake"));
-        }
-        let identifier = unsafe { decode::<u64>(&mut buffer) }.expect("failed to decode worker index").0.clone() as usize;
+        stream.set_read_timeout(backoff.read_timeout())?;
+        let (identifier, compression) = recv_handshake(&mut stream, compression)?;
+        stream.set_read_timeout(None)?;
         results[identifier - my_index - 1] = Some(stream);
+        agreed[identifier - my_index - 1] = compression;
         if noisy { println!("worker {}:\tconnection from worker {}", my_index, identifier); }
     }
 
-    Ok(results)
+    Ok((results, agreed))
+}
+
+/// Creates Unix domain socket connections from a list of socket paths.
+///
+/// The item at index i in the resulting vec, is a Some(UnixStream) to process i, except
+/// for item `my_index` which is None (no socket to self). Any existing file at this
+/// process's path is replaced, and the socket file is removed once all peers have connected.
+/// Compression and retries are as for `create_sockets`.
+#[cfg(unix)]
+pub fn create_unix_sockets(addresses: Vec<String>, my_index: usize, noisy: bool, compression: Option<Compression>, retry: Retry) -> Result<Connections<::std::os::unix::net::UnixStream>> {
+
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let path = addresses[my_index].clone();
+    let processes = addresses.len();
+
+    let await_task = thread::spawn(move || -> Result<Connections<UnixStream>> {
+        let backoff = Backoff::new(retry);
+        let mut results: Vec<_> = (0..(processes - my_index - 1)).map(|_| None).collect();
+        let mut agreed: Vec<_> = (0..(processes - my_index - 1)).map(|_| None).collect();
+        if processes > my_index + 1 {
+            let _ = ::std::fs::remove_file(&path);
+            let listener = UnixListener::bind(&path)?;
+            listener.set_nonblocking(backoff.deadline.is_some())?;
+            for _ in (my_index + 1) .. processes {
+                let mut stream = backoff.accept(|| listener.accept())?.0;
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(backoff.read_timeout())?;
+                let (identifier, compression) = recv_handshake(&mut stream, compression)?;
+                stream.set_read_timeout(None)?;
+                results[identifier - my_index - 1] = Some(stream);
+                agreed[identifier - my_index - 1] = compression;
+                if noisy { println!("worker {}:\tconnection from worker {}", my_index, identifier); }
+            }
+            ::std::fs::remove_file(&path)?;
+        }
+        Ok((results, agreed))
+    });
+
+    let mut backoff = Backoff::new(retry);
+    let mut results = Vec::with_capacity(processes);
+    let mut agreed = Vec::with_capacity(processes);
+    for (index, address) in addresses.iter().take(my_index).enumerate() {
+        loop {
+            match UnixStream::connect(address) {
+                Ok(mut stream) => {
+                    stream.set_read_timeout(backoff.read_timeout())?;
+                    agreed.push(send_handshake(&mut stream, my_index, compression)?);
+                    stream.set_read_timeout(None)?;
+                    if noisy { println!("worker {}:\tconnection to worker {}", my_index, index); }
+                    results.push(Some(stream));
+                    break;
+                },
+                Err(error) => {
+                    if noisy { println!("worker {}:\terror connecting to worker {}: {}; retrying", my_index, index, error); }
+                    backoff.wait(&|| format!("timed out connecting to process {} at {}: {}", index, address, error))?;
+                },
+            }
+        }
+    }
+
+    results.push(None);
+    agreed.push(None);
+    let (to_extend, agreed_extend) = await_task.join().unwrap()?;
+    results.extend(to_extend);
+    agreed.extend(agreed_extend);
+
+    if noisy { println!("worker {}:\tinitialization complete", my_index) }
+
+    Ok((results, agreed))
 }
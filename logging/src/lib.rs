@@ -35,6 +35,24 @@ impl<Id: Clone+'static> Registry<Id> {
         self.insert_logger(name, logger)
     }
 
+    /// Adds an action on the log event batches of a log name, alongside any already bound.
+    ///
+    /// Unlike `insert`, this keeps the logger bound to `name`, if it logs events of type `T`,
+    /// and attaches `action` to it and to every existing handle on it. Several consumers can
+    /// in this way share one log. If no such logger is bound, `action` is bound as by `insert`.
+    pub fn attach<T: Clone+'static, F: FnMut(&Duration, &mut Vec<(Duration, Id, T)>)+'static>(
+        &mut self,
+        name: &str,
+        action: F)
+    {
+        if let Some(logger) = self.get::<T>(name) {
+            logger.attach(action);
+        }
+        else {
+            self.insert(name, action);
+        }
+    }
+
     /// Binds a log name to a logger.
     pub fn insert_logger<T: 'static>(
         &mut self,
@@ -85,11 +103,14 @@ impl<Id> Flush for Registry<Id> {
     }
 }
 
+/// An action on batches of log events.
+type Action<T, E> = Box<dyn FnMut(&Duration, &mut Vec<(Duration, E, T)>)>;
+
 /// A buffering logger.
 pub struct Logger<T, E> {
     id:     E,
     time:   Instant,                                                    // common instant used for all loggers.
-    action: Rc<RefCell<dyn FnMut(&Duration, &mut Vec<(Duration, E, T)>)>>,  // action to take on full log buffers.
+    action: Rc<RefCell<Action<T, E>>>,                                  // action to take on full log buffers.
     buffer: Rc<RefCell<Vec<(Duration, E, T)>>>,                         // shared buffer; not obviously best design.
 }
 
@@ -113,7 +134,7 @@ impl<T, E: Clone> Logger<T, E> {
         Logger {
             id,
             time,
-            action: Rc::new(RefCell::new(action)),
+            action: Rc::new(RefCell::new(Box::new(action))),
             buffer: Rc::new(RefCell::new(Vec::with_capacity(1024))),
         }
     }
@@ -144,6 +165,44 @@ impl<T, E: Clone> Logger<T, E> {
     pub fn flush(&mut self) {
         <Self as Flush>::flush(self);
     }
+
+    /// Adds an action on the log event batches of this logger and all of its clones.
+    ///
+    /// Each action receives its own copy of each batch, after the actions attached before it.
+    /// Events logged but not yet flushed are seen by the new action as well.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use std::time::Instant;
+    /// use timely_logging::Logger;
+    ///
+    /// let seen = Rc::new(RefCell::new((0, 0)));
+    /// let (seen1, seen2) = (seen.clone(), seen.clone());
+    /// let logger = Logger::<u64, usize>::new(Instant::now(), 0, move |_time, data| {
+    ///     seen1.borrow_mut().0 += data.drain(..).count();
+    /// });
+    /// logger.clone().attach(move |_time, data| seen2.borrow_mut().1 += data.len());
+    /// logger.log(1u64);
+    /// logger.log(2u64);
+    /// drop(logger);
+    /// assert_eq!(*seen.borrow(), (2, 2));
+    /// ```
+    pub fn attach<F>(&self, mut action: F)
+    where
+        T: Clone+'static,
+        E: 'static,
+        F: FnMut(&Duration, &mut Vec<(Duration, E, T)>)+'static
+    {
+        let mut current = self.action.borrow_mut();
+        let mut previous = ::std::mem::replace(&mut *current, Box::new(|_, _| { }));
+        *current = Box::new(move |time, data| {
+            let mut copy = data.clone();
+            previous(time, &mut copy);
+            action(time, data);
+        });
+    }
 }
 
 /// Bit weird, because we only have to flush on the *last* drop, but this should be ok.
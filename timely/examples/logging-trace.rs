@@ -0,0 +1,54 @@
+extern crate timely;
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::Duration;
+
+use timely::dataflow::operators::capture::{Event, EventReader};
+use timely::dataflow::operators::capture::event::EventIterator;
+use timely::logging::{ChromeTrace, TimelyEvent, WorkerIdentifier};
+use timely::progress::ChangeBatch;
+
+// Converts captured timely logs into a Chrome trace, viewable in chrome://tracing or Perfetto.
+//
+// Logs can be captured from any timely computation, for example `exchange`, by listening
+// for the connections of its workers and saving each to a file, as in
+//
+//     nc -l 8000 > worker0.log &
+//     TIMELY_WORKER_LOG_ADDR=127.0.0.1:8000 cargo run --example exchange -- 100 10
+//     cargo run --release --example logging-trace -- trace.json worker0.log
+//
+// Captured events are decoded in place without regard to alignment, which debug builds reject.
+fn main() {
+
+    let mut args = std::env::args().skip(1);
+    let output = args.next().expect("usage: logging-trace <output> <log files> ...");
+    let mut trace = ChromeTrace::new(BufWriter::new(File::create(output).unwrap()));
+
+    for path in args {
+
+        let file = File::open(&path).unwrap();
+        let mut reader = EventReader::<Duration, (Duration, WorkerIdentifier, TimelyEvent), _>::new(file);
+
+        // A log is complete once its frontier is empty, and truncated if reads produce nothing.
+        let mut frontier = ChangeBatch::new_from(Duration::default(), 1);
+        let mut idle = 0;
+        while !frontier.is_empty() && idle < 2 {
+            match reader.next() {
+                Some(Event::Messages(_time, data)) => {
+                    for (time, worker, event) in data.iter() {
+                        trace.observe(time, *worker, event).unwrap();
+                    }
+                    idle = 0;
+                },
+                Some(Event::Progress(updates)) => {
+                    frontier.extend(updates.iter().cloned());
+                    idle = 0;
+                },
+                None => { idle += 1; },
+            }
+        }
+    }
+
+    trace.finish().unwrap();
+}
@@ -16,7 +16,8 @@ use crate::worker::AsWorker;
 use crate::dataflow::channels::pushers::Exchange as ExchangePusher;
 use super::{Bundle, Message};
 
-use crate::logging::TimelyLogger as Logger;
+use crate::logging::{MemoryKind, TimelyLogger as Logger};
+use crate::memory::MemoryGauge;
 
 /// A `ParallelizationContract` allocates paired `Push` and `Pull` implementors.
 pub trait ParallelizationContract<T: 'static, D: 'static> {
@@ -35,23 +36,66 @@ impl<T: 'static, D: 'static> ParallelizationContract<T, D> for Pipeline {
     type Puller = LogPuller<T, D, ThreadPuller<Bundle<T, D>>>;
     fn connect<A: AsWorker>(self, allocator: &mut A, identifier: usize, address: &[usize], logging: Option<Logger>) -> (Self::Pusher, Self::Puller) {
         let (pusher, puller) = allocator.pipeline::<Message<T, D>>(identifier, address);
+        let gauge = allocator.memory().borrow_mut().register(address, MemoryKind::Channel(identifier));
         // // ignore `&mut A` and use thread allocator
         // let (pusher, puller) = Thread::new::<Bundle<T, D>>();
-        (LogPusher::new(pusher, allocator.index(), allocator.index(), identifier, logging.clone()),
-         LogPuller::new(puller, allocator.index(), identifier, logging.clone()))
+        (LogPusher::new(pusher, allocator.index(), allocator.index(), identifier, logging.clone()).with_queue_gauge(gauge.clone()),
+         LogPuller::new(puller, allocator.index(), identifier, logging.clone()).with_queue_gauge(gauge))
     }
 }
 
 /// An exchange between multiple observers by data
-pub struct Exchange<D, F: FnMut(&D)->u64+'static> { hash_func: F, phantom: PhantomData<D>, }
+pub struct Exchange<D, F: FnMut(&D)->u64+'static> { hash_func: F, budget: Option<usize>, phantom: PhantomData<D>, }
 impl<D, F: FnMut(&D)->u64> Exchange<D, F> {
     /// Allocates a new `Exchange` pact from a distribution function.
     pub fn new(func: F) -> Exchange<D, F> {
         Exchange {
             hash_func:  func,
+            budget:     None,
             phantom:    PhantomData,
         }
     }
+    /// Enables flow control, with a budget of messages outstanding at each target.
+    ///
+    /// Pushers into the exchange, and the outputs of operators that feed it, then report
+    /// through `over_budget()` when a target worker has at least `budget` messages sent to it
+    /// but not yet received. Flow control is advisory: data are never refused, but operators
+    /// may observe the report and yield until the targets catch up.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Inspect};
+    /// use timely::dataflow::operators::generic::Operator;
+    /// use timely::dataflow::channels::pact::{Exchange, Pipeline};
+    /// use timely::scheduling::Scheduler;
+    ///
+    /// timely::example(|scope| {
+    ///     (0..10u64).to_stream(scope)
+    ///               .unary(Pipeline, "Producer", |_cap, info| {
+    ///                   let activator = scope.activator_for(&info.address[..]);
+    ///                   let mut stash = Vec::new();
+    ///                   move |input, output| {
+    ///                       input.for_each(|cap, data| stash.push((cap.retain(), data.replace(Vec::new()))));
+    ///                       // Yield while the downstream exchange is congested.
+    ///                       while !output.over_budget() {
+    ///                           if let Some((cap, mut data)) = stash.pop() {
+    ///                               output.session(&cap).give_vec(&mut data);
+    ///                           }
+    ///                           else { break; }
+    ///                       }
+    ///                       if !stash.is_empty() { activator.activate(); }
+    ///                   }
+    ///               })
+    ///               .unary(Exchange::new(|x: &u64| *x).with_budget(16), "Consumer", |_cap, _info| |input, output| {
+    ///                   input.for_each(|cap, data| output.session(&cap).give_vec(&mut data.replace(Vec::new())));
+    ///               })
+    ///               .inspect(|x| println!("seen: {:?}", x));
+    /// });
+    /// ```
+    pub fn with_budget(mut self, budget: usize) -> Self {
+        self.budget = Some(budget);
+        self
+    }
 }
 
 // Exchange uses a `Box<Pushable>` because it cannot know what type of pushable will return from the allocator.
@@ -61,9 +105,14 @@ impl<T: Eq+Data+Clone, D: Data+Clone, F: FnMut(&D)->u64+'static> Parallelization
     type Pusher = Box<dyn Push<Bundle<T, D>>>;
     type Puller = Box<dyn Pull<Bundle<T, D>>>;
     fn connect<A: AsWorker>(mut self, allocator: &mut A, identifier: usize, address: &[usize], logging: Option<Logger>) -> (Self::Pusher, Self::Puller) {
-        let (senders, receiver) = allocator.allocate::<Message<T, D>>(identifier, address);
-        let senders = senders.into_iter().enumerate().map(|(i,x)| LogPusher::new(x, allocator.index(), i, identifier, logging.clone())).collect::<Vec<_>>();
-        (Box::new(ExchangePusher::new(senders, move |_, d| (self.hash_func)(d))), Box::new(LogPuller::new(receiver, allocator.index(), identifier, logging.clone())))
+        let (senders, receiver) = allocator.allocate_with_budget::<Message<T, D>>(identifier, address, self.budget);
+        let gauge = allocator.memory().borrow_mut().register(address, MemoryKind::Channel(identifier));
+        let senders = senders.into_iter().enumerate().map(|(i,x)| {
+            let pusher = LogPusher::new(x, allocator.index(), i, identifier, logging.clone());
+            // Only records the worker sends to itself are accounted.
+            if i == allocator.index() { pusher.with_queue_gauge(gauge.clone()) } else { pusher }
+        }).collect::<Vec<_>>();
+        (Box::new(ExchangePusher::new(senders, move |_, d| (self.hash_func)(d))), Box::new(LogPuller::new(receiver, allocator.index(), identifier, logging.clone()).with_queue_gauge(gauge)))
     }
 }
 
@@ -99,6 +148,7 @@ pub struct LogPusher<T, D, P: Push<Bundle<T, D>>> {
     target: usize,
     phantom: ::std::marker::PhantomData<(T, D)>,
     logging: Option<Logger>,
+    queue: Option<MemoryGauge>,
 }
 impl<T, D, P: Push<Bundle<T, D>>> LogPusher<T, D, P> {
     /// Allocates a new pusher.
@@ -111,8 +161,14 @@ impl<T, D, P: Push<Bundle<T, D>>> LogPusher<T, D, P> {
             target,
             phantom: ::std::marker::PhantomData,
             logging,
+            queue: None,
         }
     }
+    /// Adds the bytes of pushed records to `gauge`, which the matching puller subtracts.
+    pub fn with_queue_gauge(mut self, gauge: MemoryGauge) -> Self {
+        self.queue = Some(gauge);
+        self
+    }
 }
 
 impl<T, D, P: Push<Bundle<T, D>>> Push<Bundle<T, D>> for LogPusher<T, D, P> {
@@ -135,9 +191,17 @@ impl<T, D, P: Push<Bundle<T, D>>> Push<Bundle<T, D>> for LogPusher<T, D, P> {
                 seq_no: self.counter-1,
                 length: bundle.data.len(),
             }));
+
+            if let Some(queue) = self.queue.as_ref() {
+                queue.add(bundle.data.len() * ::std::mem::size_of::<D>());
+            }
         }
         self.pusher.push(pair);
     }
+    #[inline]
+    fn over_budget(&self) -> bool {
+        self.pusher.over_budget()
+    }
 }
 
 /// Wraps a `Message<T,D>` puller to provide a `Pull<(T, Content<D>)>`.
@@ -147,6 +211,7 @@ pub struct LogPuller<T, D, P: Pull<Bundle<T, D>>> {
     index: usize,
     phantom: ::std::marker::PhantomData<(T, D)>,
     logging: Option<Logger>,
+    queue: Option<MemoryGauge>,
 }
 impl<T, D, P: Pull<Bundle<T, D>>> LogPuller<T, D, P> {
     /// Allocates a new `Puller`.
@@ -157,8 +222,14 @@ impl<T, D, P: Pull<Bundle<T, D>>> LogPuller<T, D, P> {
             index,
             phantom: ::std::marker::PhantomData,
             logging,
+            queue: None,
         }
     }
+    /// Subtracts the bytes of records this worker sent itself from `gauge`.
+    pub fn with_queue_gauge(mut self, gauge: MemoryGauge) -> Self {
+        self.queue = Some(gauge);
+        self
+    }
 }
 
 impl<T, D, P: Pull<Bundle<T, D>>> Pull<Bundle<T, D>> for LogPuller<T, D, P> {
@@ -176,6 +247,12 @@ impl<T, D, P: Pull<Bundle<T, D>>> Pull<Bundle<T, D>> for LogPuller<T, D, P> {
                 seq_no: bundle.seq,
                 length: bundle.data.len(),
             }));
+
+            if bundle.from == target {
+                if let Some(queue) = self.queue.as_ref() {
+                    queue.sub(bundle.data.len() * ::std::mem::size_of::<D>());
+                }
+            }
         }
         result
     }
@@ -5,6 +5,7 @@ use crate::dataflow::channels::{Bundle, Message};
 use crate::progress::Timestamp;
 use crate::dataflow::operators::Capability;
 use crate::communication::Push;
+use crate::memory::MemoryGauge;
 
 /// Buffers data sent at the same time, for efficient communication.
 ///
@@ -14,6 +15,7 @@ pub struct Buffer<T, D, P: Push<Bundle<T, D>>> {
     time: Option<T>,  // the currently open time, if it is open
     buffer: Vec<D>,   // a buffer for records, to send at self.time
     pusher: P,
+    gauge: Option<MemoryGauge>,
 }
 
 impl<T, D, P: Push<Bundle<T, D>>> Buffer<T, D, P> where T: Eq+Clone {
@@ -24,9 +26,17 @@ impl<T, D, P: Push<Bundle<T, D>>> Buffer<T, D, P> where T: Eq+Clone {
             time: None,
             buffer: Vec::with_capacity(Message::<T, D>::default_length()),
             pusher,
+            gauge: None,
         }
     }
 
+    /// Reports the bytes allocated for buffered records to `gauge`.
+    pub fn with_gauge(mut self, gauge: MemoryGauge) -> Self {
+        self.gauge = Some(gauge);
+        self.update_gauge();
+        self
+    }
+
     /// Returns a `Session`, which accepts data to send at the associated time
     pub fn session(&mut self, time: &T) -> Session<T, D, P> {
         if let Some(true) = self.time.as_ref().map(|x| x != time) { self.flush(); }
@@ -48,6 +58,9 @@ impl<T, D, P: Push<Bundle<T, D>>> Buffer<T, D, P> where T: Eq+Clone {
     /// This is currently used internally, and should not be used without some care.
     pub fn inner(&mut self) -> &mut P { &mut self.pusher }
 
+    /// Indicates that the wrapped pusher reports a target over its budget.
+    pub fn over_budget(&self) -> bool { self.pusher.over_budget() }
+
     /// Flushes all data and pushes a `None` to `self.pusher`, indicating a flush.
     pub fn cease(&mut self) {
         self.flush();
@@ -59,6 +72,14 @@ impl<T, D, P: Push<Bundle<T, D>>> Buffer<T, D, P> where T: Eq+Clone {
         if !self.buffer.is_empty() {
             let time = self.time.as_ref().unwrap().clone();
             Message::push_at(&mut self.buffer, time, &mut self.pusher);
+            self.update_gauge();
+        }
+    }
+
+    // reports the size of the buffer's allocation, which the pusher may replace.
+    fn update_gauge(&self) {
+        if let Some(gauge) = self.gauge.as_ref() {
+            gauge.set(self.buffer.capacity() * ::std::mem::size_of::<D>());
         }
     }
 
@@ -26,6 +26,10 @@ impl<T, D, P> Push<Bundle<T, D>> for Counter<T, D, P> where T : Ord+Clone+'stati
             self.pushee.push(message);
         }
     }
+    #[inline]
+    fn over_budget(&self) -> bool {
+        self.pushee.over_budget()
+    }
 }
 
 impl<T, D, P: Push<Bundle<T, D>>> Counter<T, D, P> where T : Ord+Clone+'static {
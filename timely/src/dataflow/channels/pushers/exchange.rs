@@ -98,4 +98,8 @@ impl<T: Eq+Data, D: Data, P: Push<Bundle<T, D>>, H: FnMut(&T, &D)->u64> Push<Bun
             }
         }
     }
+    #[inline]
+    fn over_budget(&self) -> bool {
+        self.pushers.iter().any(|pusher| pusher.over_budget())
+    }
 }
@@ -34,6 +34,10 @@ impl<T: Data, D: Data> Push<Bundle<T, D>> for Tee<T, D> {
             pushers[last].push(message);
         }
     }
+    #[inline]
+    fn over_budget(&self) -> bool {
+        self.shared.borrow().iter().any(|pusher| pusher.over_budget())
+    }
 }
 
 impl<T, D> Tee<T, D> {
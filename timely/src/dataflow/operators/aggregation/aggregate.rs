@@ -6,6 +6,7 @@ use crate::{Data, ExchangeData};
 use crate::dataflow::{Stream, Scope};
 use crate::dataflow::operators::generic::operator::Operator;
 use crate::dataflow::channels::pact::Exchange;
+use crate::dataflow::operators::stash::{TimeStash, SpillConfig};
 
 /// Generic intra-timestamp aggregation
 ///
@@ -65,6 +66,39 @@ pub trait Aggregate<S: Scope, K: ExchangeData+Hash, V: ExchangeData> {
         fold: F,
         emit: E,
         hash: H) -> Stream<S, R> where S::Timestamp: Eq;
+
+    /// Aggregates data of the form `(key, val)`, spilling pending data to disk.
+    ///
+    /// Rather than fold records into aggregates as they arrive, this method holds the records
+    /// in a `TimeStash`, which writes records to disk once more than `spill.threshold` are held
+    /// in memory, and folds the records at each time once the time is complete. Otherwise it
+    /// behaves as `aggregate`.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Map, Inspect};
+    /// use timely::dataflow::operators::aggregation::Aggregate;
+    /// use timely::dataflow::operators::stash::SpillConfig;
+    ///
+    /// timely::example(|scope| {
+    ///
+    ///     (0..10).to_stream(scope)
+    ///         .map(|x| (x % 2, x))
+    ///         .aggregate_with_spill(
+    ///             |_key, val, agg| { *agg += val; },
+    ///             |key, agg: i32| (key, agg),
+    ///             |key| *key as u64,
+    ///             SpillConfig::new(4),
+    ///         )
+    ///         .inspect(|x| assert!(*x == (0, 20) || *x == (1, 25)));
+    /// });
+    /// ```
+    fn aggregate_with_spill<R: Data, D: Default+'static, F: Fn(&K, V, &mut D)+'static, E: Fn(K, D)->R+'static, H: Fn(&K)->u64+'static>(
+        &self,
+        fold: F,
+        emit: E,
+        hash: H,
+        spill: SpillConfig) -> Stream<S, R> where S::Timestamp: Eq;
 }
 
 impl<S: Scope, K: ExchangeData+Hash+Eq, V: ExchangeData> Aggregate<S, K, V> for Stream<S, (K, V)> {
@@ -102,4 +136,37 @@ impl<S: Scope, K: ExchangeData+Hash+Eq, V: ExchangeData> Aggregate<S, K, V> for
         })
 
     }
+
+    fn aggregate_with_spill<R: Data, D: Default+'static, F: Fn(&K, V, &mut D)+'static, E: Fn(K, D)->R+'static, H: Fn(&K)->u64+'static>(
+        &self,
+        fold: F,
+        emit: E,
+        hash: H,
+        spill: SpillConfig) -> Stream<S, R> where S::Timestamp: Eq {
+
+        let mut stash = TimeStash::with_spill(spill);
+        self.unary_notify(Exchange::new(move |(k, _): &(K, V)| hash(k)), "Aggregate", vec![], move |input, output, notificator| {
+
+            // stash each input batch until its time is complete
+            input.for_each(|time, data| {
+                stash.insert(time.time().clone(), data.replace(Vec::new()));
+                notificator.notify_at(time.retain());
+            });
+
+            // fold stashed records for completed times, send along whatever
+            notificator.for_each(|time,_,_| {
+                let mut aggs = HashMap::new();
+                for batch in stash.remove(time.time()) {
+                    for (key, val) in batch {
+                        let agg = aggs.entry(key.clone()).or_insert_with(Default::default);
+                        fold(&key, val, agg);
+                    }
+                }
+                let mut session = output.session(&time);
+                for (key, agg) in aggs {
+                    session.give(emit(key, agg));
+                }
+            });
+        })
+    }
 }
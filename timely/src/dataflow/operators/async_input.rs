@@ -0,0 +1,206 @@
+//! Create new `Streams` connected to external inputs driven from other threads.
+//!
+//! The `AsyncInputHandle` returned by `new_async_input` can be moved to another thread, for
+//! example one running an asynchronous runtime, and it implements `futures::Sink` so that it
+//! can be fed by asynchronous code. Each message or progress update it sends activates the
+//! input operator, which unparks the worker thread if it is parked in `step_or_park`.
+
+use std::pin::Pin;
+use std::sync::mpsc::{Sender, TryRecvError};
+use std::task::{Context, Poll};
+
+use futures_util::sink::Sink;
+
+use crate::Data;
+use crate::dataflow::{Stream, ScopeParent, Scope};
+use crate::dataflow::channels::Message;
+use crate::dataflow::operators::generic::operator::source;
+use crate::dataflow::operators::to_stream::Event;
+use crate::progress::Timestamp;
+use crate::scheduling::{SyncActivator, SyncActivationError};
+
+/// Create a new `Stream` and `AsyncInputHandle` through which to supply input from other threads.
+pub trait AsyncInput : Scope {
+    /// Create a new `Stream` and `AsyncInputHandle` through which to supply input.
+    ///
+    /// The `new_async_input` method returns a pair `(AsyncInputHandle, Stream)` where the
+    /// `Stream` can be used immediately for timely dataflow construction, and the handle may
+    /// be moved to another thread to introduce data into the computation. The stream completes
+    /// once the handle is closed or dropped.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures_util::sink::SinkExt;
+    /// use timely::dataflow::operators::{Inspect, Probe};
+    /// use timely::dataflow::operators::async_input::AsyncInput;
+    ///
+    /// timely::execute(timely::Configuration::Thread, |worker| {
+    ///
+    ///     let (mut handle, probe) = worker.dataflow::<u64,_,_>(|scope| {
+    ///         let (handle, stream) = scope.new_async_input();
+    ///         let probe = stream.inspect(|x| println!("hello {:?}", x)).probe();
+    ///         (handle, probe)
+    ///     });
+    ///
+    ///     // Feed the input from an asynchronous task on another thread.
+    ///     let feeder = std::thread::spawn(move || {
+    ///         futures_executor::block_on(async move {
+    ///             for round in 0 .. 10 {
+    ///                 handle.send(round).await.unwrap();
+    ///                 handle.advance_to(round + 1).unwrap();
+    ///             }
+    ///             handle.close().await.unwrap();
+    ///         });
+    ///     });
+    ///
+    ///     // The worker parks until there is input to process.
+    ///     while !probe.done() {
+    ///         worker.step_or_park(None);
+    ///     }
+    ///     feeder.join().unwrap();
+    /// }).unwrap();
+    /// ```
+    fn new_async_input<D: Data+Send>(&mut self) -> (AsyncInputHandle<<Self as ScopeParent>::Timestamp, D>, Stream<Self, D>);
+}
+
+impl<G: Scope> AsyncInput for G {
+    fn new_async_input<D: Data+Send>(&mut self) -> (AsyncInputHandle<G::Timestamp, D>, Stream<G, D>) {
+
+        let (sender, receiver) = ::std::sync::mpsc::channel::<Event<G::Timestamp, Vec<D>>>();
+        let mut activator = None;
+
+        let stream = source(self, "AsyncInput", |capability, info| {
+
+            activator = Some(self.sync_activator_for(&info.address[..]));
+            let mut capability = Some(capability);
+
+            move |output| {
+                while capability.is_some() {
+                    match receiver.try_recv() {
+                        Ok(Event::Progress(Some(time))) => {
+                            capability.as_mut().unwrap().downgrade(&time);
+                        },
+                        Ok(Event::Message(time, mut data)) => {
+                            let delayed = capability.as_ref().unwrap().delayed(&time);
+                            output.session(&delayed).give_vec(&mut data);
+                        },
+                        Ok(Event::Progress(None)) | Err(TryRecvError::Disconnected) => {
+                            capability = None;
+                        },
+                        Err(TryRecvError::Empty) => break,
+                    }
+                }
+            }
+        });
+
+        let handle = AsyncInputHandle {
+            time: Default::default(),
+            buffer: Vec::with_capacity(Message::<G::Timestamp, D>::default_length()),
+            sender: Some(sender),
+            activator: activator.expect("Source constructor not called"),
+        };
+
+        (handle, stream)
+    }
+}
+
+/// A thread-safe handle to an input `Stream`, implementing `futures::Sink`.
+///
+/// Records are buffered and sent at the handle's current time, which can be advanced with
+/// `advance_to`. Unlike an `InputHandle`, the handle is tied to a single input stream.
+pub struct AsyncInputHandle<T: Timestamp, D: Data> {
+    time: T,
+    buffer: Vec<D>,
+    sender: Option<Sender<Event<T, Vec<D>>>>,
+    activator: SyncActivator,
+}
+
+impl<T: Timestamp, D: Data> AsyncInputHandle<T, D> {
+
+    /// Reports the current epoch.
+    pub fn time(&self) -> &T {
+        &self.time
+    }
+
+    /// Sends one record into the corresponding timely dataflow `Stream`, at the current epoch.
+    ///
+    /// Records are buffered, and are only sent once the buffer fills or the handle is flushed.
+    pub fn give(&mut self, data: D) -> Result<(), SyncActivationError> {
+        self.buffer.push(data);
+        if self.buffer.len() == self.buffer.capacity() {
+            self.flush()
+        }
+        else {
+            Ok(())
+        }
+    }
+
+    /// Sends any buffered records into the corresponding timely dataflow `Stream`.
+    pub fn flush(&mut self) -> Result<(), SyncActivationError> {
+        if !self.buffer.is_empty() {
+            let data = ::std::mem::replace(&mut self.buffer, Vec::with_capacity(Message::<T, D>::default_length()));
+            self.send_event(Event::Message(self.time.clone(), data))?;
+        }
+        Ok(())
+    }
+
+    /// Advances the current epoch to `next`.
+    ///
+    /// This method allows timely dataflow to issue progress notifications as it can now determine
+    /// that this input can no longer produce data at earlier timestamps.
+    pub fn advance_to(&mut self, next: T) -> Result<(), SyncActivationError> {
+        assert!(self.time.less_equal(&next));
+        self.flush()?;
+        self.time = next.clone();
+        self.send_event(Event::Progress(Some(next)))
+    }
+
+    /// Closes the input, flushing any buffered records.
+    ///
+    /// The corresponding `Stream` completes once the operator has received all records.
+    /// Subsequent calls to `give` or `advance_to` return errors.
+    pub fn close_input(&mut self) -> Result<(), SyncActivationError> {
+        if self.sender.is_some() {
+            self.flush()?;
+            self.send_event(Event::Progress(None))?;
+            self.sender = None;
+        }
+        Ok(())
+    }
+
+    fn send_event(&mut self, event: Event<T, Vec<D>>) -> Result<(), SyncActivationError> {
+        self.sender
+            .as_ref()
+            .ok_or(SyncActivationError)?
+            .send(event)
+            .map_err(|_| SyncActivationError)?;
+        self.activator.activate()
+    }
+}
+
+// The handle is never pinned structurally, and can be moved freely.
+impl<T: Timestamp, D: Data> Unpin for AsyncInputHandle<T, D> { }
+
+impl<T: Timestamp, D: Data> Sink<D> for AsyncInputHandle<T, D> {
+    type Error = SyncActivationError;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+    fn start_send(self: Pin<&mut Self>, item: D) -> Result<(), Self::Error> {
+        self.get_mut().give(item)
+    }
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(self.get_mut().flush())
+    }
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(self.get_mut().close_input())
+    }
+}
+
+impl<T: Timestamp, D: Data> Drop for AsyncInputHandle<T, D> {
+    fn drop(&mut self) {
+        // Errors indicate the dataflow has gone, and no longer needs the input.
+        let _ = self.close_input();
+    }
+}
@@ -0,0 +1,328 @@
+//! An operator whose state is checkpointed at frontier boundaries.
+
+use std::io;
+use std::hash::Hash;
+use std::collections::HashMap;
+
+use abomonation::Abomonation;
+
+use crate::{Data, ExchangeData};
+use crate::dataflow::{Stream, Scope};
+use crate::dataflow::channels::pact::{ParallelizationContract, Exchange};
+use crate::dataflow::channels::pushers::{Tee, Counter};
+use crate::dataflow::channels::pushers::buffer::Session;
+use crate::dataflow::operators::capture::Event;
+use crate::dataflow::operators::generic::operator::Operator;
+use crate::order::PartialOrder;
+use crate::progress::Antichain;
+
+use super::store::{CheckpointStore, Checkpointer, Events};
+
+type OutputPusher<T, D> = Counter<T, D, Tee<T, D>>;
+
+/// Extension trait for `Stream`.
+pub trait Checkpoint<G: Scope, D: Data> {
+    /// Maintains checkpointed state, applying input data in timestamp order.
+    ///
+    /// The `logic` closure is called once for each timestamp in the input, once the input
+    /// frontier has passed the timestamp, with all data at that timestamp and the state. The
+    /// records it returns are produced as output at the timestamp. Timestamps are applied in
+    /// the order of `Ord`, which for partially ordered timestamps is only one possible order.
+    ///
+    /// The operator registers with `store` under `name`. When the input frontier passes the
+    /// time of a requested checkpoint, the operator writes its state reflecting exactly the
+    /// input at times less or equal to the checkpoint time; input at other times is held back
+    /// until the checkpoint is written, and the held back input at times the frontier has passed
+    /// is written with the state. When the store has a checkpoint to restore from, the initial
+    /// state is read from it rather than starting from `Default::default()`, and the held back
+    /// input is replayed. Input at the replayed times is then discarded, and so the input must
+    /// reproduce the same data at those times, as it does if the computation is deterministic.
+    ///
+    /// The state cannot be restored by fewer workers than wrote it, as the states of several
+    /// workers cannot be merged into one, and the operator panics if asked to; when restored by
+    /// more workers, the additional workers start from `Default::default()`. State that should
+    /// follow a change in the number of workers is better kept by `checkpointed_state_machine`,
+    /// or by an operator of your own that restores with `Checkpointer::restore_merged`.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::InputHandle;
+    /// use timely::dataflow::channels::pact::Pipeline;
+    /// use timely::dataflow::operators::{Input, Inspect, Probe};
+    /// use timely::dataflow::operators::checkpoint::{Checkpoint, CheckpointStore};
+    ///
+    /// let path = ::std::env::temp_dir().join(format!("timely-checkpoint-doc-{}", ::std::process::id()));
+    ///
+    /// // Run once, checkpointing at time 3, and then restore from that checkpoint.
+    /// for _ in 0 .. 2 {
+    ///     let path = path.clone();
+    ///     timely::execute(timely::Configuration::Process(2), move |worker| {
+    ///
+    ///         let store = CheckpointStore::<u64>::new(&path, worker.index(), worker.peers()).unwrap();
+    ///         let resume = store.restored().map(|time| time + 1).unwrap_or(0);
+    ///         store.checkpoint_at(3);
+    ///
+    ///         let mut input = InputHandle::new();
+    ///         let probe = worker.dataflow(|scope| {
+    ///             scope.input_from(&mut input)
+    ///                  .checkpointed(Pipeline, "Sum", &store, |_time, data, sum: &mut u64| {
+    ///                      *sum += data.iter().sum::<u64>();
+    ///                      Some(*sum)
+    ///                  })
+    ///                  .inspect_time(|time, sum| if *time == 5 { assert_eq!(*sum, 15); })
+    ///                  .probe()
+    ///         });
+    ///
+    ///         // Inputs resume after the restored checkpoint, if any.
+    ///         input.advance_to(resume);
+    ///         for round in resume .. 6 {
+    ///             if worker.index() == 0 { input.send(round); }
+    ///             input.advance_to(round + 1);
+    ///             worker.step_while(|| probe.less_than(input.time()));
+    ///         }
+    ///     }).unwrap();
+    /// }
+    ///
+    /// ::std::fs::remove_dir_all(&path).unwrap();
+    /// ```
+    fn checkpointed<D2, S, I, L, P>(&self, pact: P, name: &str, store: &CheckpointStore<G::Timestamp>, logic: L) -> Stream<G, D2>
+    where
+        G::Timestamp: Abomonation,
+        D: Abomonation,
+        D2: Data,
+        S: Abomonation+Clone+Default+'static,
+        I: IntoIterator<Item=D2>,
+        L: FnMut(&G::Timestamp, Vec<D>, &mut S)->I+'static,
+        P: ParallelizationContract<G::Timestamp, D>;
+}
+
+impl<G: Scope, D: Data> Checkpoint<G, D> for Stream<G, D> {
+    fn checkpointed<D2, S, I, L, P>(&self, pact: P, name: &str, store: &CheckpointStore<G::Timestamp>, mut logic: L) -> Stream<G, D2>
+    where
+        G::Timestamp: Abomonation,
+        D: Abomonation,
+        D2: Data,
+        S: Abomonation+Clone+Default+'static,
+        I: IntoIterator<Item=D2>,
+        L: FnMut(&G::Timestamp, Vec<D>, &mut S)->I+'static,
+        P: ParallelizationContract<G::Timestamp, D>,
+    {
+        let checkpointer = store.register(name);
+        let state: S =
+        checkpointer
+            .restore()
+            .expect("Failed to restore checkpoint; `checkpointed` cannot be restored by fewer workers")
+            .unwrap_or_default();
+
+        let events = checkpointer.restore_events().expect("Failed to read checkpoint");
+
+        checkpointed_core(self, pact, checkpointer, state, events,
+            move |time, data, state, session| session.give_iterator(logic(time, data, state).into_iter()),
+            |state, checkpointer| checkpointer.write(state),
+        )
+    }
+}
+
+/// Extension trait for `Stream`.
+pub trait CheckpointKeyed<G: Scope, K: ExchangeData+Hash+Eq, V: ExchangeData> {
+    /// Tracks a checkpointed state for each presented key, using user-supplied state transition logic.
+    ///
+    /// This operator behaves as `StateMachine::state_machine`, applying updates in timestamp
+    /// order, but also writes its per-key states to `store` when the input frontier passes a
+    /// requested checkpoint, along with the held back input, as `checkpointed` does. As the state
+    /// is keyed, it can be restored by a different number of workers than wrote it: each worker
+    /// restores the keys, and the held back input, that `hash` routes to it. This allows a
+    /// computation to be rescaled by restarting it from a checkpoint.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::InputHandle;
+    /// use timely::dataflow::operators::{Input, Inspect, Probe};
+    /// use timely::dataflow::operators::checkpoint::{CheckpointKeyed, CheckpointStore};
+    ///
+    /// let path = ::std::env::temp_dir().join(format!("timely-checkpoint-keyed-doc-{}", ::std::process::id()));
+    ///
+    /// // Run with two workers, checkpointing at time 3, and then restore with three workers.
+    /// for &workers in [2, 3].iter() {
+    ///     let path = path.clone();
+    ///     timely::execute(timely::Configuration::Process(workers), move |worker| {
+    ///
+    ///         let store = CheckpointStore::<u64>::new(&path, worker.index(), worker.peers()).unwrap();
+    ///         let resume = store.restored().map(|time| time + 1).unwrap_or(0);
+    ///         store.checkpoint_at(3);
+    ///
+    ///         let mut input = InputHandle::new();
+    ///         let probe = worker.dataflow(|scope| {
+    ///             scope.input_from(&mut input)
+    ///                  .checkpointed_state_machine("Sums", &store,
+    ///                      |key, val, sum: &mut u64| { *sum += val; (false, Some((*key, *sum))) },
+    ///                      |key| *key,
+    ///                  )
+    ///                  .inspect_time(|time, &(_key, sum)| if *time == 5 { assert_eq!(sum, 15); })
+    ///                  .probe()
+    ///         });
+    ///
+    ///         // Inputs resume after the restored checkpoint, if any.
+    ///         input.advance_to(resume);
+    ///         for round in resume .. 6 {
+    ///             if worker.index() == 0 {
+    ///                 for key in 0 .. 4 { input.send((key, round)); }
+    ///             }
+    ///             input.advance_to(round + 1);
+    ///             worker.step_while(|| probe.less_than(input.time()));
+    ///         }
+    ///     }).unwrap();
+    /// }
+    ///
+    /// ::std::fs::remove_dir_all(&path).unwrap();
+    /// ```
+    fn checkpointed_state_machine<
+        R: Data,                                        // output type
+        S: Abomonation+Clone+Default+'static,           // per-key state
+        I: IntoIterator<Item=R>,                        // type of output iterator
+        F: Fn(&K, V, &mut S)->(bool, I)+'static,        // state update logic
+        H: Fn(&K)->u64+'static,                         // "hash" function for keys
+    >(&self, name: &str, store: &CheckpointStore<G::Timestamp>, fold: F, hash: H) -> Stream<G, R>
+    where G::Timestamp: Abomonation, K: Abomonation, V: Abomonation;
+}
+
+impl<G: Scope, K: ExchangeData+Hash+Eq, V: ExchangeData> CheckpointKeyed<G, K, V> for Stream<G, (K, V)> {
+    fn checkpointed_state_machine<
+        R: Data,                                        // output type
+        S: Abomonation+Clone+Default+'static,           // per-key state
+        I: IntoIterator<Item=R>,                        // type of output iterator
+        F: Fn(&K, V, &mut S)->(bool, I)+'static,        // state update logic
+        H: Fn(&K)->u64+'static,                         // "hash" function for keys
+    >(&self, name: &str, store: &CheckpointStore<G::Timestamp>, fold: F, hash: H) -> Stream<G, R>
+    where G::Timestamp: Abomonation, K: Abomonation, V: Abomonation {
+
+        let checkpointer = store.register(name);
+        let states: HashMap<K, S> =
+        checkpointer
+            .restore_keyed(&hash)
+            .expect("Failed to read checkpoint")
+            .map(|pairs| pairs.into_iter().collect())
+            .unwrap_or_default();
+        let events = checkpointer.restore_keyed_events(&hash).expect("Failed to read checkpoint");
+
+        let pact = Exchange::new(move |(k, _): &(K, V)| hash(k));
+        checkpointed_core(self, pact, checkpointer, states, events,
+            move |_time, data, states, session| {
+                for (key, val) in data {
+                    let (remove, output) = {
+                        let state = states.entry(key.clone()).or_insert_with(Default::default);
+                        fold(&key, val, state)
+                    };
+                    if remove { states.remove(&key); }
+                    session.give_iterator(output.into_iter());
+                }
+            },
+            |states, checkpointer| {
+                let pairs = states.iter().map(|(k, s)| (k.clone(), s.clone())).collect::<Vec<_>>();
+                checkpointer.write(&pairs)
+            },
+        )
+    }
+}
+
+/// Applies input to `state` in timestamp order, writing checkpoints as the frontier passes them.
+///
+/// Input at times not less or equal to the next requested checkpoint is held back until the
+/// checkpoint has been written, so that the written state reflects exactly the input at times
+/// less or equal to the checkpoint time. The held back input at complete times is written as
+/// `events` alongside the state, and replayed from restored `events`.
+fn checkpointed_core<G, D, D2, S, L, W, P>(
+    stream: &Stream<G, D>,
+    pact: P,
+    mut checkpointer: Checkpointer<G::Timestamp>,
+    mut state: S,
+    events: Option<Events<G::Timestamp, D>>,
+    mut logic: L,
+    write: W) -> Stream<G, D2>
+where
+    G: Scope,
+    G::Timestamp: Abomonation,
+    D: Data+Abomonation,
+    D2: Data,
+    S: 'static,
+    L: FnMut(&G::Timestamp, Vec<D>, &mut S, &mut Session<G::Timestamp, D2, OutputPusher<G::Timestamp, D2>>)+'static,
+    W: Fn(&S, &mut Checkpointer<G::Timestamp>)->io::Result<()>+'static,
+    P: ParallelizationContract<G::Timestamp, D>,
+{
+    let mut pending = HashMap::new();
+    let mut vector = Vec::new();
+    let name = checkpointer.name().to_owned();
+
+    stream.unary_frontier(pact, &name, move |capability, _info| {
+
+        // Replay the restored input, and note the frontier before which it is complete.
+        let mut replayed: Option<Antichain<G::Timestamp>> = None;
+        for event in events.into_iter().flatten() {
+            match event {
+                Event::Progress(updates) => {
+                    replayed.get_or_insert_with(Antichain::new).extend(updates.into_iter().map(|(time, _)| time));
+                },
+                Event::Messages(time, mut data) => {
+                    pending
+                        .entry(time.clone())
+                        .or_insert_with(|| (capability.delayed(&time), Vec::new()))
+                        .1
+                        .append(&mut data);
+                },
+            }
+        }
+
+        move |input, output| {
+
+            // Stash input data by time, retaining a capability for each time.
+            input.for_each(|time, data| {
+                // Input at replayed times would repeat the replayed data.
+                if replayed.as_ref().map(|replayed| !replayed.less_equal(time.time())).unwrap_or(false) {
+                    return;
+                }
+                data.swap(&mut vector);
+                pending
+                    .entry(time.time().clone())
+                    .or_insert_with(|| (time.retain(), Vec::new()))
+                    .1
+                    .append(&mut vector);
+            });
+
+            let frontier = input.frontier();
+            loop {
+
+                // Apply complete times, but only those at or before the next checkpoint.
+                let limit = checkpointer.next();
+                let mut ready =
+                pending
+                    .keys()
+                    .filter(|time| !frontier.less_equal(time))
+                    .filter(|time| limit.as_ref().map(|limit| time.less_equal(limit)).unwrap_or(true))
+                    .cloned()
+                    .collect::<Vec<G::Timestamp>>();
+                ready.sort();
+
+                for time in ready.drain(..) {
+                    let (capability, data) = pending.remove(&time).expect("Pending time absent");
+                    logic(&time, data, &mut state, &mut output.session(&capability));
+                }
+
+                // Write the checkpoint if the frontier has passed it, and continue with the next.
+                match limit {
+                    Some(ref limit) if !frontier.less_equal(limit) => {
+                        // Held back input at complete times is written with the state.
+                        let mut events = vec![Event::Progress(frontier.frontier().iter().map(|time| (time.clone(), 1)).collect())];
+                        for (time, (_, data)) in pending.iter() {
+                            if !frontier.less_equal(time) {
+                                events.push(Event::Messages(time.clone(), data.clone()));
+                            }
+                        }
+                        checkpointer.write_events(&events).expect("Failed to write checkpoint");
+                        write(&state, &mut checkpointer).expect("Failed to write checkpoint");
+                    },
+                    _ => break,
+                }
+            }
+        }
+    })
+}
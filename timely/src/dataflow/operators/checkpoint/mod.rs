@@ -0,0 +1,33 @@
+//! Checkpointing and restoration of operator state at frontier boundaries.
+//!
+//! A `CheckpointStore` collects requests for checkpoints at chosen timestamps. Operators that
+//! register with the store write their state once their input frontier passes each requested
+//! timestamp, and a checkpoint is complete once all operators on all workers have done so.
+//! A later execution opening the same store restores each operator from the most recent
+//! complete checkpoint, and should resume its inputs with the data at timestamps not less or
+//! equal to the checkpoint's timestamp, available from `CheckpointStore::restored`. Alongside
+//! their state, operators write the input they hold back at later timestamps, as a sequence of
+//! `capture::Event`s, which they replay when restored in place of the resumed input.
+//!
+//! The `Checkpoint` trait provides an operator that manages this on behalf of its logic. Other
+//! operators, for example those built with `unary_frontier` or an `OperatorBuilder`, can use a
+//! `Checkpointer` obtained from `CheckpointStore::register` directly. They must then ensure that
+//! the state they write reflects exactly the input at times less or equal to the checkpoint time,
+//! typically by holding back later input until the checkpoint is written, and may write the held
+//! back input with `Checkpointer::write_events`.
+//!
+//! Restoring operator state generally requires the same number of workers, and the same routing
+//! of data among them, as the execution that wrote the checkpoint. Keyed state, for example that of
+//! `CheckpointKeyed::checkpointed_state_machine`, can instead be redistributed among a different
+//! number of workers, which allows a computation to be rescaled by restarting it from a checkpoint.
+//! State that does not depend on the routing of data can be merged by `Checkpointer::restore_merged`.
+//!
+//! The workers of a running computation are fixed by its `Configuration`. Workers cannot join or
+//! leave it, and dataflows always use the number of workers it started with; restarting from a
+//! checkpoint is the only way to change their number.
+
+pub use self::store::{CheckpointStore, Checkpointer};
+pub use self::checkpointed::{Checkpoint, CheckpointKeyed};
+
+pub mod store;
+pub mod checkpointed;
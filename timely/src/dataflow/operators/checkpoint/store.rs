@@ -0,0 +1,454 @@
+//! Durable storage for operator checkpoints.
+//!
+//! A `CheckpointStore` is a directory shared by all workers, containing one sub-directory for
+//! each requested checkpoint, itself containing one sub-directory for each worker. Each worker
+//! writes a file for each registered operator, and optionally a file of the operator's in-flight
+//! input, and once all of its operators have written their state a `complete` marker recording
+//! the checkpoint's timestamp and the number of workers. A checkpoint can be restored from once
+//! all workers have written their markers.
+//!
+//! Checkpoints can be restored by a different number of workers than wrote them, for example
+//! to rescale a computation. Keyed operator state can be redistributed among the new workers;
+//! see `Checkpointer::restore_keyed`. Other state is assigned to the new workers by index; see
+//! `Checkpointer::restore_merged`.
+//!
+//! ```ignore
+//! path/
+//!     0/
+//!         0/
+//!             complete
+//!             OperatorName
+//!             OperatorName.events
+//!         1/
+//!             ...
+//!     1/
+//!         ...
+//! ```
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use abomonation::Abomonation;
+
+use crate::progress::{Antichain, Timestamp};
+use crate::dataflow::operators::capture::Event;
+
+/// A shared handle to a directory of operator checkpoints.
+///
+/// The handle records the times at which checkpoints have been requested, the operators
+/// that have registered state with it, and the most recent complete checkpoint found in its
+/// directory when it was constructed, from which operators restore their state.
+pub struct CheckpointStore<T: Timestamp> {
+    inner: Rc<RefCell<StoreInner<T>>>,
+}
+
+impl<T: Timestamp> Clone for CheckpointStore<T> {
+    fn clone(&self) -> Self {
+        CheckpointStore { inner: self.inner.clone() }
+    }
+}
+
+struct StoreInner<T> {
+    path: PathBuf,
+    index: usize,
+    peers: usize,
+    // Sequence number to assign to the first requested checkpoint.
+    base: usize,
+    // Sequence number, time, and number of workers of the restored checkpoint, if any.
+    restored: Option<(usize, T, usize)>,
+    // Requested checkpoint times, where `requested[i]` has sequence number `base + i`.
+    requested: Vec<T>,
+    // Names of registered operators.
+    operators: Vec<String>,
+    // Sequence number of the first checkpoint this worker has yet to complete.
+    complete: usize,
+    // Number of operators that have written each checkpoint.
+    written: HashMap<usize, usize>,
+}
+
+impl<T: Timestamp+Abomonation> CheckpointStore<T> {
+
+    /// Opens a checkpoint directory for worker `index` out of `peers`.
+    ///
+    /// The most recent checkpoint completed by all workers that wrote it is selected as the one
+    /// to restore from, and any checkpoint state written after it by this worker, or by workers
+    /// of a larger computation whose index is congruent to this worker's modulo `peers`, is
+    /// removed. Each worker should open the same directory, though the number of peers may
+    /// differ from that of the computation that wrote the checkpoints.
+    pub fn new<P: AsRef<Path>>(path: P, index: usize, peers: usize) -> io::Result<Self> {
+
+        let path = path.as_ref().to_path_buf();
+        fs::create_dir_all(&path)?;
+
+        // Collect the sequence numbers of all checkpoints, in decreasing order.
+        let mut sequence = Vec::new();
+        for entry in fs::read_dir(&path)? {
+            if let Some(seqno) = entry?.file_name().to_str().and_then(|name| name.parse::<usize>().ok()) {
+                sequence.push(seqno);
+            }
+        }
+        sequence.sort();
+        sequence.reverse();
+
+        // Each marker records the number of workers that wrote the checkpoint.
+        let mut restored = None;
+        for &seqno in sequence.iter() {
+            if marker_path(&path, seqno, 0).exists() {
+                let bytes = read_file(&marker_path(&path, seqno, 0))?;
+                let (time, writers) = decode_clone::<(T, u64)>(bytes)?;
+                let writers = writers as usize;
+                if (0 .. writers).all(|peer| marker_path(&path, seqno, peer).exists()) {
+                    restored = Some((seqno, time, writers));
+                    break;
+                }
+            }
+        }
+
+        // Remove incomplete checkpoints, so that they are not mixed with new state. Workers
+        // remove their own, and share out those of workers beyond the current peers.
+        let base = restored.as_ref().map(|&(seqno, _, _)| seqno + 1).unwrap_or(0);
+        for &seqno in sequence.iter().filter(|&&seqno| seqno >= base) {
+            for entry in fs::read_dir(path.join(seqno.to_string()))? {
+                let entry = entry?;
+                if let Some(writer) = entry.file_name().to_str().and_then(|name| name.parse::<usize>().ok()) {
+                    if writer == index || (writer >= peers && writer % peers == index) {
+                        fs::remove_dir_all(entry.path())?;
+                    }
+                }
+            }
+        }
+
+        Ok(CheckpointStore {
+            inner: Rc::new(RefCell::new(StoreInner {
+                path,
+                index,
+                peers,
+                base,
+                restored,
+                requested: Vec::new(),
+                operators: Vec::new(),
+                complete: base,
+                written: HashMap::new(),
+            }))
+        })
+    }
+
+    /// The time of the checkpoint operators restore from, if any.
+    ///
+    /// Restored operators reflect all input at times less or equal to this time, and none
+    /// at other times. Inputs should resume with the data at the other times.
+    pub fn restored(&self) -> Option<T> {
+        self.inner.borrow().restored.as_ref().map(|(_, time, _)| time.clone())
+    }
+
+    /// Requests a checkpoint once the frontier of each registered operator passes `time`.
+    ///
+    /// All workers must request the same checkpoints in the same order, and requested times
+    /// should increase, as operators write their checkpoints in the order requested.
+    pub fn checkpoint_at(&self, time: T) {
+        self.inner.borrow_mut().requested.push(time);
+    }
+
+    /// Registers an operator with the store, and returns a handle for its checkpoints.
+    ///
+    /// The name identifies the operator's state across executions, and must be unique among
+    /// the operators registered with this store. It is used as a file name, and may not be
+    /// `complete`, which names the marker of a completed checkpoint, nor end in `.events`.
+    ///
+    /// An operator takes part in the checkpoints this worker has yet to complete. Checkpoints
+    /// completed before it registered do not include it, and it restores from those as an
+    /// operator without state.
+    pub fn register(&self, name: &str) -> Checkpointer<T> {
+        let mut inner = self.inner.borrow_mut();
+        if name == "complete" || name.ends_with(".events") || inner.operators.iter().any(|operator| operator == name) {
+            panic!("Operator {:?} already registered for checkpoints", name);
+        }
+        inner.operators.push(name.to_owned());
+        let next = inner.complete;
+        Checkpointer {
+            store: self.clone(),
+            name: name.to_owned(),
+            next,
+        }
+    }
+}
+
+/// An operator's in-flight input, as written to and read from checkpoints.
+pub type Events<T, D> = Vec<Event<T, D>>;
+
+/// An operator's handle to its checkpoints in a `CheckpointStore`.
+pub struct Checkpointer<T: Timestamp> {
+    store: CheckpointStore<T>,
+    name: String,
+    // Sequence number of the next checkpoint to write.
+    next: usize,
+}
+
+impl<T: Timestamp+Abomonation> Checkpointer<T> {
+
+    /// Reads the operator's state from the restored checkpoint, if any.
+    ///
+    /// The result is `None` if there is no checkpoint, or if the operator did not take part
+    /// in it. A checkpoint written by a different number of workers is restored as described
+    /// for `restore_merged`, and this method returns an error if this worker would restore
+    /// the states of several workers, which it cannot merge.
+    pub fn restore<S: Abomonation+Clone>(&self) -> io::Result<Option<S>> {
+        let mut states = self.restored_files(&self.name, decode_clone::<S>)?;
+        if states.len() > 1 {
+            let message = format!("cannot merge the checkpointed states of {} workers", states.len());
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+        Ok(states.pop())
+    }
+
+    /// Reads the operator's state from the restored checkpoint, if any, merging states as needed.
+    ///
+    /// Each worker restores the states written by the workers whose index is congruent to its
+    /// own modulo the number of workers restoring the checkpoint, merged with `merge`. When the
+    /// number of workers is unchanged, this is the state the worker wrote, and when it grows,
+    /// some workers restore no state. Unlike `restore_keyed`, this does not account for changes
+    /// in the routing of data among workers, and suits state that does not depend on routing.
+    pub fn restore_merged<S, F>(&self, mut merge: F) -> io::Result<Option<S>>
+    where
+        S: Abomonation+Clone,
+        F: FnMut(S, S)->S,
+    {
+        let states = self.restored_files(&self.name, decode_clone::<S>)?;
+        Ok(states.into_iter().fold(None, |merged, state| Some(match merged {
+            Some(merged) => merge(merged, state),
+            None => state,
+        })))
+    }
+
+    /// Reads the operator's in-flight input from the restored checkpoint, if any.
+    ///
+    /// The events are those written by `write_events`: the input frontier when the checkpoint
+    /// was written, followed by the input data at the complete times not less or equal to the
+    /// checkpoint's time. If the checkpoint was written by a different number of workers, the
+    /// events of the workers `restore_merged` reads are combined, as for `restore_keyed_events`.
+    pub fn restore_events<D: Abomonation+Clone>(&self) -> io::Result<Option<Events<T, D>>> {
+        let (index, peers, rescaled) = {
+            let inner = self.store.inner.borrow();
+            (inner.index, inner.peers, inner.restored.as_ref().map(|&(_, _, writers)| writers != inner.peers).unwrap_or(false))
+        };
+        if rescaled {
+            self.combined_events(|writer| writer % peers == index, |_| true)
+        }
+        else {
+            Ok(self.restored_files(&events_name(&self.name), decode_clone::<Events<T, D>>)?.pop())
+        }
+    }
+
+    // Decodes this worker's files `name` in the restored checkpoint, following `restore_merged`.
+    fn restored_files<S, F>(&self, name: &str, decode: F) -> io::Result<Vec<S>>
+    where
+        F: Fn(Vec<u8>)->io::Result<S>,
+    {
+        let inner = self.store.inner.borrow();
+        let mut result = Vec::new();
+        if let Some((seqno, _, writers)) = inner.restored {
+            for writer in (inner.index .. writers).step_by(inner.peers) {
+                let path = worker_path(&inner.path, seqno, writer).join(name);
+                if path.exists() {
+                    result.push(decode(read_file(&path)?)?);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Reads the operator's keyed state from the restored checkpoint, if any.
+    ///
+    /// The state must have been written as a list of `(key, value)` pairs. The state of all
+    /// workers that wrote the checkpoint is read, and those pairs whose key hashes to this
+    /// worker are returned, matching the routing of an `Exchange` pact with the same `hash`.
+    /// This allows the state to be restored by a different number of workers.
+    pub fn restore_keyed<K, S, H>(&self, hash: H) -> io::Result<Option<Vec<(K, S)>>>
+    where
+        K: Abomonation+Clone,
+        S: Abomonation+Clone,
+        H: Fn(&K)->u64,
+    {
+        let inner = self.store.inner.borrow();
+        if let Some((seqno, _, writers)) = inner.restored {
+            let mut result = Vec::new();
+            for writer in 0 .. writers {
+                let path = worker_path(&inner.path, seqno, writer).join(&self.name);
+                if !path.exists() { continue; }
+                let mut bytes = read_file(&path)?;
+                let (pairs, _) =
+                unsafe { ::abomonation::decode::<Vec<(K, S)>>(&mut bytes[..]) }
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "failed to decode checkpoint"))?;
+                result.extend(
+                    pairs
+                        .iter()
+                        .filter(|&(key, _)| (hash(key) % inner.peers as u64) as usize == inner.index)
+                        .cloned()
+                );
+            }
+            Ok(Some(result))
+        }
+        else {
+            Ok(None)
+        }
+    }
+
+    /// Reads the operator's keyed in-flight input from the restored checkpoint, if any.
+    ///
+    /// The events of all workers that wrote the checkpoint are read, and the data routed to
+    /// this worker by `hash` are returned, as for `restore_keyed`. Each worker's events only
+    /// include the times complete at that worker, and so only data at times complete at all
+    /// workers are returned, following a frontier that combines those of all workers.
+    pub fn restore_keyed_events<K, V, H>(&self, hash: H) -> io::Result<Option<Events<T, (K, V)>>>
+    where
+        K: Abomonation+Clone,
+        V: Abomonation+Clone,
+        H: Fn(&K)->u64,
+    {
+        let (index, peers) = {
+            let inner = self.store.inner.borrow();
+            (inner.index, inner.peers)
+        };
+        self.combined_events(|_| true, |(key, _)| (hash(key) % peers as u64) as usize == index)
+    }
+
+    // Combines the in-flight input of the `assigned` workers that wrote the restored checkpoint,
+    // retaining the data that `keep` selects at times complete at all workers.
+    fn combined_events<D, W, F>(&self, assigned: W, mut keep: F) -> io::Result<Option<Events<T, D>>>
+    where
+        D: Abomonation+Clone,
+        W: Fn(usize)->bool,
+        F: FnMut(&D)->bool,
+    {
+        let inner = self.store.inner.borrow();
+        if let Some((seqno, _, writers)) = inner.restored {
+            let mut written = false;
+            let mut frontier = Antichain::new();
+            let mut messages = Vec::new();
+            for writer in 0 .. writers {
+                let path = worker_path(&inner.path, seqno, writer).join(events_name(&self.name));
+                if !path.exists() { continue; }
+                written = true;
+                for event in decode_clone::<Events<T, D>>(read_file(&path)?)? {
+                    match event {
+                        Event::Progress(updates) => {
+                            for (time, _) in updates { frontier.insert(time); }
+                        },
+                        Event::Messages(time, mut data) => {
+                            if assigned(writer) {
+                                data.retain(|datum| keep(datum));
+                                messages.push((time, data));
+                            }
+                        },
+                    }
+                }
+            }
+            if !written { return Ok(None); }
+            let mut result = vec![Event::Progress(frontier.elements().iter().map(|time| (time.clone(), 1)).collect())];
+            result.extend(
+                messages
+                    .into_iter()
+                    .filter(|(time, data)| !frontier.less_equal(time) && !data.is_empty())
+                    .map(|(time, data)| Event::Messages(time, data))
+            );
+            Ok(Some(result))
+        }
+        else {
+            Ok(None)
+        }
+    }
+
+    /// The name under which the operator registered.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The time of the next requested checkpoint the operator has yet to write, if any.
+    ///
+    /// The operator should write its state once its input frontier passes this time.
+    pub fn next(&self) -> Option<T> {
+        let inner = self.store.inner.borrow();
+        inner.requested.get(self.next - inner.base).cloned()
+    }
+
+    /// Writes `events` as the operator's in-flight input for the time reported by `next()`.
+    ///
+    /// The events should start with an `Event::Progress` listing the elements of the input
+    /// frontier, followed by the input data at times the frontier has passed but that are
+    /// not less or equal to the checkpoint's time. They must be written before the state, as
+    /// `write` completes the operator's checkpoint.
+    pub fn write_events<D: Abomonation>(&self, events: &Events<T, D>) -> io::Result<()> {
+        let inner = self.store.inner.borrow();
+        let worker_path = worker_path(&inner.path, self.next, inner.index);
+        fs::create_dir_all(&worker_path)?;
+        write_file(&worker_path.join(events_name(&self.name)), events)
+    }
+
+    /// Writes `state` as the operator's checkpoint for the time reported by `next()`.
+    ///
+    /// Once all operators registered with the store have written their checkpoints, the
+    /// checkpoint is marked as complete for this worker.
+    pub fn write<S: Abomonation>(&mut self, state: &S) -> io::Result<()> {
+
+        let mut inner = self.store.inner.borrow_mut();
+        let time = inner.requested.get(self.next - inner.base).cloned().expect("No checkpoint requested");
+
+        let worker_path = worker_path(&inner.path, self.next, inner.index);
+        fs::create_dir_all(&worker_path)?;
+        write_file(&worker_path.join(&self.name), state)?;
+
+        let operators = inner.operators.len();
+        let written = {
+            let written = inner.written.entry(self.next).or_insert(0);
+            *written += 1;
+            *written
+        };
+        if written == operators {
+            inner.written.remove(&self.next);
+            inner.complete = self.next + 1;
+            write_file(&marker_path(&inner.path, self.next, inner.index), &(time, inner.peers as u64))?;
+        }
+
+        self.next += 1;
+        Ok(())
+    }
+}
+
+fn worker_path(path: &Path, seqno: usize, index: usize) -> PathBuf {
+    path.join(seqno.to_string()).join(index.to_string())
+}
+
+fn marker_path(path: &Path, seqno: usize, index: usize) -> PathBuf {
+    worker_path(path, seqno, index).join("complete")
+}
+
+fn events_name(name: &str) -> String {
+    format!("{}.events", name)
+}
+
+fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    fs::File::open(path)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+// Writes to a temporary file first, so that a crash cannot leave a partially written file.
+fn write_file<S: Abomonation>(path: &Path, value: &S) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let mut file = fs::File::create(&temp)?;
+    unsafe { ::abomonation::encode(value, &mut file)?; }
+    file.flush()?;
+    file.sync_all()?;
+    fs::rename(temp, path)
+}
+
+fn decode_clone<S: Abomonation+Clone>(mut bytes: Vec<u8>) -> io::Result<S> {
+    unsafe { ::abomonation::decode::<S>(&mut bytes[..]) }
+        .map(|(value, _)| value.clone())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "failed to decode checkpoint"))
+}
@@ -2,11 +2,12 @@
 
 use std::collections::HashMap;
 
-use crate::Data;
+use crate::{Data, ExchangeData};
 use crate::order::{PartialOrder, TotalOrder};
 use crate::dataflow::channels::pact::Pipeline;
 use crate::dataflow::{Stream, Scope};
 use crate::dataflow::operators::generic::operator::Operator;
+use crate::dataflow::operators::stash::{TimeStash, SpillConfig};
 
 /// Methods to advance the timestamps of records or batches of records.
 pub trait Delay<G: Scope, D: Data> {
@@ -92,6 +93,31 @@ pub trait Delay<G: Scope, D: Data> {
     /// });
     /// ```
     fn delay_batch(&self, func: impl Fn(&G::Timestamp)->G::Timestamp+'static) -> Self;
+
+    /// Advances the timestamp of records using a supplied function, spilling to disk.
+    ///
+    /// This method behaves as `delay`, but holds pending records in a `TimeStash`, which
+    /// writes records to disk once more than `spill.threshold` are held in memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Delay, Operator};
+    /// use timely::dataflow::operators::stash::SpillConfig;
+    /// use timely::dataflow::channels::pact::Pipeline;
+    ///
+    /// timely::example(|scope| {
+    ///     (0..10).to_stream(scope)
+    ///            .delay_with_spill(|data, time| *data, SpillConfig::new(4))
+    ///            .sink(Pipeline, "example", |input| {
+    ///                input.for_each(|time, data| {
+    ///                    assert_eq!(data[0], *time.time());
+    ///                });
+    ///            });
+    /// });
+    /// ```
+    fn delay_with_spill(&self, func: impl Fn(&D, &G::Timestamp)->G::Timestamp+'static, spill: SpillConfig) -> Self
+    where D: ExchangeData;
 }
 
 impl<G: Scope, D: Data> Delay<G, D> for Stream<G, D> {
@@ -147,4 +173,33 @@ impl<G: Scope, D: Data> Delay<G, D> for Stream<G, D> {
             });
         })
     }
+
+    fn delay_with_spill(&self, func: impl Fn(&D, &G::Timestamp)->G::Timestamp+'static, spill: SpillConfig) -> Stream<G, D>
+    where D: ExchangeData
+    {
+        let mut stash = TimeStash::with_spill(spill);
+        let mut vector = Vec::new();
+        let mut batches = HashMap::new();
+        self.unary_notify(Pipeline, "Delay", vec![], move |input, output, notificator| {
+            input.for_each(|time, data| {
+                data.swap(&mut vector);
+                for datum in vector.drain(..) {
+                    let new_time = func(&datum, &time);
+                    assert!(time.time().less_equal(&new_time));
+                    batches.entry(new_time).or_insert_with(Vec::new).push(datum);
+                }
+                for (new_time, batch) in batches.drain() {
+                    notificator.notify_at(time.delayed(&new_time));
+                    stash.insert(new_time, batch);
+                }
+            });
+
+            // for each available notification, send corresponding set
+            notificator.for_each(|time,_,_| {
+                for mut data in stash.remove(&time) {
+                    output.session(&time).give_vec(&mut data);
+                }
+            });
+        })
+    }
 }
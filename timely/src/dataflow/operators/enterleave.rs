@@ -157,6 +157,7 @@ impl<TOuter: Timestamp, TInner: Timestamp+Refines<TOuter>, TData: Data> Push<Bun
         }
         else { self.targets.done(); }
     }
+    fn over_budget(&self) -> bool { self.targets.over_budget() }
 }
 
 
@@ -181,4 +182,5 @@ where TOuter: Timestamp, TInner: Timestamp+Refines<TOuter>, TData: Data {
         }
         else { self.targets.done(); }
     }
+    fn over_budget(&self) -> bool { self.targets.over_budget() }
 }
@@ -1,5 +1,7 @@
 //! Exchange records between workers.
 
+use std::hash::{Hash, Hasher};
+
 use crate::ExchangeData;
 use crate::dataflow::channels::pact::Exchange as ExchangePact;
 use crate::dataflow::{Stream, Scope};
@@ -37,3 +39,26 @@ impl<G: Scope, D: ExchangeData> Exchange<G::Timestamp, D> for Stream<G, D> {
         })
     }
 }
+
+/// Routes records to workers by `key`, for operators that exchange records with equal keys.
+///
+/// Unlike `DefaultHasher`, whose algorithm may change between Rust releases, this uses
+/// 64-bit FNV-1a, so that processes built separately route keys to the same workers.
+pub(crate) fn hash_key<K: Hash>(key: &K) -> u64 {
+    let mut hasher = FnvHasher(0xcbf2_9ce4_8422_2325);
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A 64-bit FNV-1a hasher.
+struct FnvHasher(u64);
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 { self.0 }
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
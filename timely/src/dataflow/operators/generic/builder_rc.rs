@@ -21,7 +21,8 @@ use crate::dataflow::operators::capability::mint as mint_capability;
 use crate::dataflow::operators::generic::handles::{InputHandle, new_input_handle, OutputWrapper};
 use crate::dataflow::operators::generic::operator_info::OperatorInfo;
 
-use crate::logging::TimelyLogger as Logger;
+use crate::logging::{MemoryKind, TimelyLogger as Logger};
+use crate::memory::MemoryRegistry;
 
 use super::builder_raw::OperatorBuilder as OperatorBuilderRaw;
 
@@ -33,6 +34,7 @@ pub struct OperatorBuilder<G: Scope> {
     internal: Rc<RefCell<Vec<Rc<RefCell<ChangeBatch<G::Timestamp>>>>>>,
     produced: Vec<Rc<RefCell<ChangeBatch<G::Timestamp>>>>,
     logging: Option<Logger>,
+    memory: Rc<RefCell<MemoryRegistry>>,
 }
 
 impl<G: Scope> OperatorBuilder<G> {
@@ -40,6 +42,7 @@ impl<G: Scope> OperatorBuilder<G> {
     /// Allocates a new generic operator builder from its containing scope.
     pub fn new(name: String, scope: G) -> Self {
         let logging = scope.logging();
+        let memory = scope.memory();
         OperatorBuilder {
             builder: OperatorBuilderRaw::new(name, scope),
             frontier: Vec::new(),
@@ -47,6 +50,7 @@ impl<G: Scope> OperatorBuilder<G> {
             internal: Rc::new(RefCell::new(Vec::new())),
             produced: Vec::new(),
             logging,
+            memory,
         }
     }
 
@@ -92,7 +96,9 @@ impl<G: Scope> OperatorBuilder<G> {
         let internal = Rc::new(RefCell::new(ChangeBatch::new()));
         self.internal.borrow_mut().push(internal.clone());
 
-        let mut buffer = PushBuffer::new(PushCounter::new(tee));
+        let address = self.builder.operator_info().address;
+        let gauge = self.memory.borrow_mut().register(&address[..], MemoryKind::Output(self.produced.len()));
+        let mut buffer = PushBuffer::new(PushCounter::new(tee)).with_gauge(gauge);
         self.produced.push(buffer.inner().produced().clone());
 
         (OutputWrapper::new(buffer, internal), stream)
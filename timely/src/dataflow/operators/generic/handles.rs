@@ -175,6 +175,13 @@ impl<T: Timestamp, D, P: Push<Bundle<T, D>>> OutputWrapper<T, D, P> {
             internal_buffer: &self.internal_buffer,
         }
     }
+    /// Indicates that a channel fed by this output has a target over its budget.
+    ///
+    /// Only channels with flow control enabled, for example with `Exchange::with_budget`,
+    /// report this. Operators may use it to yield rather than produce more data.
+    pub fn over_budget(&self) -> bool {
+        self.push_buffer.over_budget()
+    }
 }
 
 
@@ -211,6 +218,13 @@ impl<'a, T: Timestamp, D, P: Push<Bundle<T, D>>> OutputHandle<'a, T, D, P> {
         assert!(cap.valid_for_output(&self.internal_buffer), "Attempted to open output session with invalid capability");
         self.push_buffer.session(cap.time())
     }
+
+    /// Indicates that a channel fed by this output has a target over its budget.
+    ///
+    /// See `OutputWrapper::over_budget`.
+    pub fn over_budget(&self) -> bool {
+        self.push_buffer.over_budget()
+    }
 }
 
 impl<'a, T: Timestamp, D, P: Push<Bundle<T, D>>> Drop for OutputHandle<'a, T, D, P> {
@@ -0,0 +1,281 @@
+//! Keyed hash joins between streams.
+//!
+//! The symmetric joins match records of the two inputs with equal keys and equal timestamps,
+//! producing matches as soon as both records have arrived. Each worker holds the records of
+//! each timestamp until both input frontiers have passed the timestamp, at which point the
+//! outer joins report any unmatched records and the state for the timestamp is discarded.
+//!
+//! The static join instead retains one input indefinitely, as an indexed relation, which
+//! records of the other input are matched against once the relation is complete up to their
+//! timestamp.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::{Data, ExchangeData};
+use crate::order::PartialOrder;
+use crate::dataflow::{Stream, Scope};
+use crate::dataflow::channels::pact::Exchange;
+use crate::dataflow::operators::exchange::hash_key;
+use crate::dataflow::operators::generic::FrontierNotificator;
+use crate::dataflow::operators::generic::operator::Operator;
+
+/// Keyed hash joins between streams.
+pub trait Join<G: Scope, K: ExchangeData+Hash+Eq, V: ExchangeData> {
+
+    /// Matches records with equal keys and timestamps.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Capture};
+    /// use timely::dataflow::operators::capture::Extract;
+    /// use timely::dataflow::operators::join::Join;
+    ///
+    /// let captured = timely::example(|scope| {
+    ///     let names = vec![(1, "one".to_owned()), (2, "two".to_owned())].to_stream(scope);
+    ///     let counts = vec![(1, 10), (1, 11), (3, 30)].to_stream(scope);
+    ///     names.join(&counts).capture()
+    /// });
+    ///
+    /// assert_eq!(captured.extract()[0].1, vec![(1, "one".to_owned(), 10), (1, "one".to_owned(), 11)]);
+    /// ```
+    fn join<V2: ExchangeData>(&self, other: &Stream<G, (K, V2)>) -> Stream<G, (K, V, V2)>;
+
+    /// Matches records with equal keys and timestamps, and reports unmatched records of `self`.
+    ///
+    /// Unmatched records are reported once both input frontiers have passed their timestamp.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Capture};
+    /// use timely::dataflow::operators::capture::Extract;
+    /// use timely::dataflow::operators::join::Join;
+    ///
+    /// let captured = timely::example(|scope| {
+    ///     let names = vec![(1, "one".to_owned()), (2, "two".to_owned())].to_stream(scope);
+    ///     let counts = vec![(1, 10), (3, 30)].to_stream(scope);
+    ///     names.left_join(&counts).capture()
+    /// });
+    ///
+    /// assert_eq!(captured.extract()[0].1, vec![(1, "one".to_owned(), Some(10)), (2, "two".to_owned(), None)]);
+    /// ```
+    fn left_join<V2: ExchangeData>(&self, other: &Stream<G, (K, V2)>) -> Stream<G, (K, V, Option<V2>)>;
+
+    /// Matches records with equal keys and timestamps, and reports unmatched records of both inputs.
+    ///
+    /// Unmatched records are reported once both input frontiers have passed their timestamp.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Capture};
+    /// use timely::dataflow::operators::capture::Extract;
+    /// use timely::dataflow::operators::join::Join;
+    ///
+    /// let captured = timely::example(|scope| {
+    ///     let names = vec![(1, "one".to_owned()), (2, "two".to_owned())].to_stream(scope);
+    ///     let counts = vec![(1, 10), (3, 30)].to_stream(scope);
+    ///     names.outer_join(&counts).capture()
+    /// });
+    ///
+    /// assert_eq!(captured.extract()[0].1, vec![
+    ///     (1, Some("one".to_owned()), Some(10)),
+    ///     (2, Some("two".to_owned()), None),
+    ///     (3, None, Some(30)),
+    /// ]);
+    /// ```
+    fn outer_join<V2: ExchangeData>(&self, other: &Stream<G, (K, V2)>) -> Stream<G, (K, Option<V>, Option<V2>)>;
+
+    /// Matches records against a relation that is retained indefinitely.
+    ///
+    /// Each record of `self` is matched against the records of `relation` with an equal key and
+    /// a timestamp less or equal to its own, once the frontier of `relation` has passed its
+    /// timestamp. Records of `self` are not retained after they are matched, whereas records of
+    /// `relation` are never discarded.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Delay, Capture};
+    /// use timely::dataflow::operators::capture::Extract;
+    /// use timely::dataflow::operators::join::Join;
+    ///
+    /// let captured = timely::example(|scope| {
+    ///     let names = vec![(1, "one".to_owned()), (2, "two".to_owned())].to_stream(scope);
+    ///     let events = vec![(1, 0), (2, 1), (1, 2)].to_stream(scope).delay(|x, _| x.1);
+    ///     events.join_static(&names).capture()
+    /// });
+    ///
+    /// assert_eq!(captured.extract(), vec![
+    ///     (0, vec![(1, 0, "one".to_owned())]),
+    ///     (1, vec![(2, 1, "two".to_owned())]),
+    ///     (2, vec![(1, 2, "one".to_owned())]),
+    /// ]);
+    /// ```
+    fn join_static<V2: ExchangeData>(&self, relation: &Stream<G, (K, V2)>) -> Stream<G, (K, V, V2)>;
+}
+
+impl<G: Scope, K: ExchangeData+Hash+Eq, V: ExchangeData> Join<G, K, V> for Stream<G, (K, V)> {
+
+    fn join<V2: ExchangeData>(&self, other: &Stream<G, (K, V2)>) -> Stream<G, (K, V, V2)> {
+        symmetric(self, other, "Join", |k, v1, v2| (k.clone(), v1.clone(), v2.clone()), None, None)
+    }
+
+    fn left_join<V2: ExchangeData>(&self, other: &Stream<G, (K, V2)>) -> Stream<G, (K, V, Option<V2>)> {
+        symmetric(
+            self,
+            other,
+            "LeftJoin",
+            |k, v1, v2| (k.clone(), v1.clone(), Some(v2.clone())),
+            Some(|k, v1| (k.clone(), v1.clone(), None)),
+            None,
+        )
+    }
+
+    fn outer_join<V2: ExchangeData>(&self, other: &Stream<G, (K, V2)>) -> Stream<G, (K, Option<V>, Option<V2>)> {
+        symmetric(
+            self,
+            other,
+            "OuterJoin",
+            |k, v1, v2| (k.clone(), Some(v1.clone()), Some(v2.clone())),
+            Some(|k, v1| (k.clone(), Some(v1.clone()), None)),
+            Some(|k, v2| (k.clone(), None, Some(v2.clone()))),
+        )
+    }
+
+    fn join_static<V2: ExchangeData>(&self, relation: &Stream<G, (K, V2)>) -> Stream<G, (K, V, V2)> {
+
+        let exchange1 = Exchange::new(|(key, _): &(K, V)| hash_key(key));
+        let exchange2 = Exchange::new(|(key, _): &(K, V2)| hash_key(key));
+
+        self.binary_frontier(relation, exchange1, exchange2, "StaticJoin", |_cap, _info| {
+
+            // Records of `relation`, with their timestamps.
+            let mut index = HashMap::<K, Vec<(G::Timestamp, V2)>>::new();
+            // Records of `self`, awaiting the completion of `relation` at their timestamps.
+            let mut pending = HashMap::<G::Timestamp, Vec<(K, V)>>::new();
+            let mut notificator = FrontierNotificator::new();
+            let mut vector1 = Vec::new();
+            let mut vector2 = Vec::new();
+
+            move |input1, input2, output| {
+
+                input2.for_each(|cap, data| {
+                    data.swap(&mut vector2);
+                    for (key, val2) in vector2.drain(..) {
+                        index.entry(key).or_insert_with(Vec::new).push((cap.time().clone(), val2));
+                    }
+                });
+
+                input1.for_each(|cap, data| {
+                    data.swap(&mut vector1);
+                    pending
+                        .entry(cap.time().clone())
+                        .or_insert_with(|| { notificator.notify_at(cap.retain()); Vec::new() })
+                        .append(&mut vector1);
+                });
+
+                notificator.for_each(&[input2.frontier()], |cap, _not| {
+                    if let Some(records) = pending.remove(cap.time()) {
+                        let mut session = output.session(&cap);
+                        for (key, val1) in records {
+                            if let Some(vals2) = index.get(&key) {
+                                for (time, val2) in vals2.iter() {
+                                    if time.less_equal(cap.time()) {
+                                        session.give((key.clone(), val1.clone(), val2.clone()));
+                                    }
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+        })
+    }
+}
+
+/// A symmetric hash join, reporting unmatched records of either input if functions are supplied.
+fn symmetric<G, K, V1, V2, R>(
+    stream1: &Stream<G, (K, V1)>,
+    stream2: &Stream<G, (K, V2)>,
+    name: &str,
+    matched: fn(&K, &V1, &V2)->R,
+    unmatched1: Option<fn(&K, &V1)->R>,
+    unmatched2: Option<fn(&K, &V2)->R>) -> Stream<G, R>
+where
+    G: Scope,
+    K: ExchangeData+Hash+Eq,
+    V1: ExchangeData,
+    V2: ExchangeData,
+    R: Data,
+{
+    let exchange1 = Exchange::new(|(key, _): &(K, V1)| hash_key(key));
+    let exchange2 = Exchange::new(|(key, _): &(K, V2)| hash_key(key));
+
+    stream1.binary_frontier(stream2, exchange1, exchange2, name, |_cap, _info| {
+
+        // Records of each input, by timestamp and key.
+        let mut state = HashMap::<G::Timestamp, (HashMap<K, Vec<V1>>, HashMap<K, Vec<V2>>)>::new();
+        let mut notificator = FrontierNotificator::new();
+        let mut vector1 = Vec::new();
+        let mut vector2 = Vec::new();
+
+        move |input1, input2, output| {
+
+            // Drain first input, check second map, update first map.
+            input1.for_each(|cap, data| {
+                data.swap(&mut vector1);
+                let (map1, map2) = state.entry(cap.time().clone()).or_insert_with(|| {
+                    notificator.notify_at(cap.delayed(cap.time()));
+                    (HashMap::new(), HashMap::new())
+                });
+                let mut session = output.session(&cap);
+                for (key, val1) in vector1.drain(..) {
+                    if let Some(values) = map2.get(&key) {
+                        for val2 in values.iter() {
+                            session.give(matched(&key, &val1, val2));
+                        }
+                    }
+                    map1.entry(key).or_insert_with(Vec::new).push(val1);
+                }
+            });
+
+            // Drain second input, check first map, update second map.
+            input2.for_each(|cap, data| {
+                data.swap(&mut vector2);
+                let (map1, map2) = state.entry(cap.time().clone()).or_insert_with(|| {
+                    notificator.notify_at(cap.delayed(cap.time()));
+                    (HashMap::new(), HashMap::new())
+                });
+                let mut session = output.session(&cap);
+                for (key, val2) in vector2.drain(..) {
+                    if let Some(values) = map1.get(&key) {
+                        for val1 in values.iter() {
+                            session.give(matched(&key, val1, &val2));
+                        }
+                    }
+                    map2.entry(key).or_insert_with(Vec::new).push(val2);
+                }
+            });
+
+            // Retire the state of timestamps both inputs have passed.
+            notificator.for_each(&[input1.frontier(), input2.frontier()], |cap, _not| {
+                if let Some((map1, map2)) = state.remove(cap.time()) {
+                    let mut session = output.session(&cap);
+                    if let Some(unmatched1) = unmatched1 {
+                        for (key, vals1) in map1.iter().filter(|(key, _)| !map2.contains_key(key)) {
+                            for val1 in vals1.iter() {
+                                session.give(unmatched1(key, val1));
+                            }
+                        }
+                    }
+                    if let Some(unmatched2) = unmatched2 {
+                        for (key, vals2) in map2.iter().filter(|(key, _)| !map1.contains_key(key)) {
+                            for val2 in vals2.iter() {
+                                session.give(unmatched2(key, val2));
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    })
+}
@@ -12,6 +12,7 @@ pub use self::enterleave::{Enter, EnterAt, Leave};
 // pub use self::queue::*;
 pub use self::input::Input;
 pub use self::unordered_input::UnorderedInput;
+pub use self::async_input::AsyncInput;
 pub use self::feedback::{Feedback, LoopVariable, ConnectLoop};
 pub use self::concat::{Concat, Concatenate};
 pub use self::partition::Partition;
@@ -24,6 +25,7 @@ pub use self::broadcast::Broadcast;
 pub use self::probe::Probe;
 pub use self::to_stream::ToStream;
 pub use self::capture::Capture;
+pub use self::checkpoint::Checkpoint;
 pub use self::branch::{Branch, BranchWhen};
 
 pub use self::generic::Operator;
@@ -36,6 +38,7 @@ pub mod enterleave;
 pub mod input;
 pub mod flow_controlled;
 pub mod unordered_input;
+pub mod async_input;
 pub mod feedback;
 pub mod concat;
 pub mod partition;
@@ -48,12 +51,16 @@ pub mod broadcast;
 pub mod probe;
 pub mod to_stream;
 pub mod capture;
+pub mod checkpoint;
 pub mod branch;
 
 pub mod aggregation;
 pub mod generic;
 
 pub mod reclock;
+pub mod stash;
+pub mod window;
+pub mod join;
 pub mod count;
 
 // keep "mint" module-private
@@ -1,10 +1,11 @@
 //! Extension methods for `Stream` based on record-by-record transformation.
 
-use crate::Data;
+use crate::{Data, ExchangeData};
 use crate::order::PartialOrder;
 use crate::dataflow::{Stream, Scope};
 use crate::dataflow::channels::pact::Pipeline;
 use crate::dataflow::operators::generic::operator::Operator;
+use crate::dataflow::operators::stash::{TimeStash, SpillConfig};
 
 /// Extension trait for reclocking a stream.
 pub trait Reclock<S: Scope, D: Data> {
@@ -46,6 +47,38 @@ pub trait Reclock<S: Scope, D: Data> {
     /// assert_eq!(extracted[2], (8, vec![6,7,8]));
     /// ```
     fn reclock(&self, clock: &Stream<S, ()>) -> Stream<S, D>;
+
+    /// Delays records until an input is observed on the `clock` input, spilling to disk.
+    ///
+    /// This method behaves as `reclock`, but holds buffered records in a `TimeStash`, which
+    /// writes records to disk once more than `spill.threshold` are held in memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Delay, Map, Reclock, Capture};
+    /// use timely::dataflow::operators::capture::Extract;
+    /// use timely::dataflow::operators::stash::SpillConfig;
+    ///
+    /// let captured = timely::example(|scope| {
+    ///
+    ///     let data = (0..10).to_stream(scope)
+    ///                       .delay(|x,t| *x);
+    ///
+    ///     let clock = vec![3, 5, 8].into_iter()
+    ///                              .to_stream(scope)
+    ///                              .delay(|x,t| *x)
+    ///                              .map(|_| ());
+    ///
+    ///     data.reclock_with_spill(&clock, SpillConfig::new(2))
+    ///         .capture()
+    /// });
+    ///
+    /// let extracted = captured.extract();
+    /// assert_eq!(extracted, vec![(3, vec![0,1,2,3]), (5, vec![4,5]), (8, vec![6,7,8])]);
+    /// ```
+    fn reclock_with_spill(&self, clock: &Stream<S, ()>, spill: SpillConfig) -> Stream<S, D>
+    where D: ExchangeData;
 }
 
 impl<S: Scope, D: Data> Reclock<S, D> for Stream<S, D> {
@@ -77,4 +110,33 @@ impl<S: Scope, D: Data> Reclock<S, D> for Stream<S, D> {
             });
         })
     }
+
+    fn reclock_with_spill(&self, clock: &Stream<S, ()>, spill: SpillConfig) -> Stream<S, D>
+    where D: ExchangeData
+    {
+        let mut stash = TimeStash::with_spill(spill);
+
+        self.binary_notify(clock, Pipeline, Pipeline, "Reclock", vec![], move |input1, input2, output, notificator| {
+
+            // stash each data input with its timestamp.
+            input1.for_each(|cap, data| {
+                stash.insert(cap.time().clone(), data.replace(Vec::new()));
+            });
+
+            // request notification at time, to flush stash.
+            input2.for_each(|time, _data| {
+                notificator.notify_at(time.retain());
+            });
+
+            // each time with complete stash can be flushed.
+            notificator.for_each(|cap,_,_| {
+                let mut session = output.session(&cap);
+                for (_time, batches) in stash.remove_where(|t| t.less_equal(cap.time())) {
+                    for mut data in batches {
+                        session.give_vec(&mut data);
+                    }
+                }
+            });
+        })
+    }
 }
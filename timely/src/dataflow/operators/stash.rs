@@ -0,0 +1,276 @@
+//! A time-indexed stash of records, which can spill to disk.
+//!
+//! Operators that hold records until their input frontier passes a time, like `Delay`,
+//! `Reclock`, and `Aggregate`, otherwise keep all pending records in memory. A `TimeStash`
+//! holds batches of records by time, and when constructed with a `SpillConfig` writes batches
+//! to files once the number of records it holds in memory exceeds a threshold. Batches are
+//! encoded as they would be for exchange between processes, using `abomonation` or, with the
+//! `bincode` feature, `bincode`.
+//!
+//! Batches are spilled starting from the greatest times, which for totally ordered times are
+//! those that will be released last. Spilled batches are read back, and their files removed,
+//! when their time is removed from the stash.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{Data, ExchangeData};
+use crate::bytes::arc::Bytes;
+use crate::communication::Message;
+use crate::progress::Timestamp;
+
+/// Distinguishes the files of stashes within a process.
+static STASH_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Where and when a `TimeStash` spills records to disk.
+#[derive(Clone, Debug)]
+pub struct SpillConfig {
+    /// The directory in which to create spill files.
+    pub directory: PathBuf,
+    /// The number of records held in memory above which batches are spilled.
+    pub threshold: usize,
+}
+
+impl SpillConfig {
+    /// Spills records in excess of `threshold` to the system's temporary directory.
+    pub fn new(threshold: usize) -> Self {
+        SpillConfig {
+            directory: ::std::env::temp_dir(),
+            threshold,
+        }
+    }
+    /// Sets the directory in which to create spill files.
+    pub fn directory<P: Into<PathBuf>>(mut self, directory: P) -> Self {
+        self.directory = directory.into();
+        self
+    }
+}
+
+/// Encoding functions for a record type, and the state of the spill files.
+struct Spill<D> {
+    config: SpillConfig,
+    // Prefix of the names of this stash's files.
+    prefix: String,
+    // Number of files created, used to name the next file.
+    files: usize,
+    encode: fn(Vec<D>, &mut File) -> io::Result<()>,
+    decode: fn(Bytes) -> Vec<D>,
+}
+
+/// Batches of records at one time.
+struct Entry<D> {
+    batches: Vec<Vec<D>>,
+    // Number of records in `batches`.
+    records: usize,
+    // File holding spilled batches, and the number of records in it.
+    spilled: Option<(PathBuf, File, usize)>,
+}
+
+impl<D> Entry<D> {
+    fn new() -> Self {
+        Entry { batches: Vec::new(), records: 0, spilled: None }
+    }
+}
+
+/// Batches of records indexed by time, optionally spilling to disk.
+///
+/// # Examples
+/// ```
+/// use timely::dataflow::operators::stash::{TimeStash, SpillConfig};
+///
+/// let mut stash = TimeStash::<u64, String>::with_spill(SpillConfig::new(2));
+/// stash.insert(1, vec!["a".to_owned(), "b".to_owned()]);
+/// stash.insert(2, vec!["c".to_owned(), "d".to_owned()]);
+///
+/// // Only two records are held in memory; the batch at time 2 is on disk.
+/// assert_eq!(stash.len(), 4);
+/// assert_eq!(stash.in_memory(), 2);
+///
+/// let batches = stash.remove(&2);
+/// assert_eq!(batches, vec![vec!["c".to_owned(), "d".to_owned()]]);
+/// assert_eq!(stash.times().cloned().collect::<Vec<_>>(), vec![1]);
+/// ```
+pub struct TimeStash<T: Timestamp, D: Data> {
+    entries: HashMap<T, Entry<D>>,
+    in_memory: usize,
+    spilled: usize,
+    spill: Option<Spill<D>>,
+}
+
+impl<T: Timestamp, D: Data> TimeStash<T, D> {
+
+    /// Creates a stash that holds all records in memory.
+    pub fn new() -> Self {
+        TimeStash {
+            entries: HashMap::new(),
+            in_memory: 0,
+            spilled: 0,
+            spill: None,
+        }
+    }
+
+    /// Adds a batch of records at `time`.
+    ///
+    /// Empty batches are ignored; in particular, they do not introduce `time` to the stash.
+    pub fn insert(&mut self, time: T, batch: Vec<D>) {
+        if !batch.is_empty() {
+            let entry = self.entries.entry(time).or_insert_with(Entry::new);
+            entry.records += batch.len();
+            self.in_memory += batch.len();
+            entry.batches.push(batch);
+            self.spill_excess();
+        }
+    }
+
+    /// Removes and returns all batches at `time`, reading back any that were spilled.
+    pub fn remove(&mut self, time: &T) -> Vec<Vec<D>> {
+        match self.entries.remove(time) {
+            Some(entry) => self.restore(entry),
+            None => Vec::new(),
+        }
+    }
+
+    /// Removes and returns the batches at all times satisfying `predicate`.
+    ///
+    /// The result is sorted by time.
+    pub fn remove_where<F: FnMut(&T)->bool>(&mut self, mut predicate: F) -> Vec<(T, Vec<Vec<D>>)> {
+        let mut times = self.entries.keys().filter(|time| predicate(time)).cloned().collect::<Vec<_>>();
+        times.sort();
+        times.into_iter().map(|time| { let batches = self.remove(&time); (time, batches) }).collect()
+    }
+
+    /// The times at which the stash holds records.
+    pub fn times(&self) -> impl Iterator<Item=&T> {
+        self.entries.keys()
+    }
+
+    /// Returns `true` if the stash holds no records.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The number of records held, in memory or on disk.
+    pub fn len(&self) -> usize {
+        self.in_memory + self.spilled
+    }
+
+    /// The number of records held in memory.
+    pub fn in_memory(&self) -> usize {
+        self.in_memory
+    }
+
+    /// Spills batches, starting from the greatest times, until under the threshold.
+    fn spill_excess(&mut self) {
+        let threshold = match self.spill {
+            Some(ref spill) if self.in_memory > spill.config.threshold => spill.config.threshold,
+            _ => return,
+        };
+        let mut times = self.entries.iter().filter(|(_, entry)| entry.records > 0).map(|(time, _)| time.clone()).collect::<Vec<_>>();
+        times.sort();
+        while self.in_memory > threshold {
+            let time = times.pop().expect("records in memory without a time");
+            let spill = self.spill.as_mut().unwrap();
+            let entry = self.entries.get_mut(&time).unwrap();
+            let written = spill.write(entry).expect("failed to spill records to disk");
+            self.in_memory -= written;
+            self.spilled += written;
+        }
+    }
+
+    /// Collects the batches of a removed entry.
+    fn restore(&mut self, mut entry: Entry<D>) -> Vec<Vec<D>> {
+        self.in_memory -= entry.records;
+        if let Some((path, file, records)) = entry.spilled.take() {
+            drop(file);
+            let spill = self.spill.as_ref().unwrap();
+            let mut batches = spill.read(&path).expect("failed to read spilled records");
+            fs::remove_file(&path).expect("failed to remove spill file");
+            self.spilled -= records;
+            batches.append(&mut entry.batches);
+            batches
+        }
+        else {
+            entry.batches
+        }
+    }
+}
+
+impl<T: Timestamp, D: ExchangeData> TimeStash<T, D> {
+    /// Creates a stash that spills records to disk as described by `config`.
+    pub fn with_spill(config: SpillConfig) -> Self {
+        let prefix = format!("timely-stash-{}-{}", ::std::process::id(), STASH_COUNTER.fetch_add(1, Ordering::SeqCst));
+        let mut stash = Self::new();
+        stash.spill = Some(Spill {
+            config,
+            prefix,
+            files: 0,
+            encode: |batch, file| {
+                let message = Message::from_typed(batch);
+                let length = message.length_in_bytes();
+                file.write_all(&(length as u64).to_le_bytes())?;
+                let mut buffer = Vec::with_capacity(length);
+                message.into_bytes(&mut buffer);
+                file.write_all(&buffer)
+            },
+            decode: |bytes| unsafe { Message::<Vec<D>>::from_bytes(bytes) }.into_typed(),
+        });
+        stash
+    }
+}
+
+impl<T: Timestamp, D: Data> Default for TimeStash<T, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Timestamp, D: Data> Drop for TimeStash<T, D> {
+    fn drop(&mut self) {
+        for (_, entry) in self.entries.drain() {
+            if let Some((path, file, _)) = entry.spilled {
+                drop(file);
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+impl<D: Data> Spill<D> {
+
+    /// Appends the in-memory batches of `entry` to its file, returning the number of records.
+    fn write(&mut self, entry: &mut Entry<D>) -> io::Result<usize> {
+        if entry.spilled.is_none() {
+            let path = self.config.directory.join(format!("{}-{}", self.prefix, self.files));
+            self.files += 1;
+            let file = OpenOptions::new().write(true).create_new(true).open(&path)?;
+            entry.spilled = Some((path, file, 0));
+        }
+        let (_, ref mut file, ref mut records) = *entry.spilled.as_mut().unwrap();
+        for batch in entry.batches.drain(..) {
+            (self.encode)(batch, file)?;
+        }
+        file.flush()?;
+        let written = entry.records;
+        *records += written;
+        entry.records = 0;
+        Ok(written)
+    }
+
+    /// Reads the batches in the file at `path`.
+    fn read(&self, path: &Path) -> io::Result<Vec<Vec<D>>> {
+        let mut contents = Vec::new();
+        File::open(path)?.read_to_end(&mut contents)?;
+        let mut bytes = Bytes::from(contents);
+        let mut batches = Vec::new();
+        while !bytes.is_empty() {
+            let mut length = [0u8; 8];
+            length.copy_from_slice(&bytes.extract_to(8)[..]);
+            let length = u64::from_le_bytes(length) as usize;
+            batches.push((self.decode)(bytes.extract_to(length)));
+        }
+        Ok(batches)
+    }
+}
@@ -1,4 +1,8 @@
-//! Conversion to the `Stream` type from iterators.
+//! Conversion to the `Stream` type from iterators and asynchronous streams.
+
+use std::task::{Context, Poll};
+
+use futures_util::stream::StreamExt;
 
 use crate::progress::Timestamp;
 
@@ -56,3 +60,86 @@ impl<T: Timestamp, I: IntoIterator+'static> ToStream<T, I::Item> for I where I::
         })
     }
 }
+
+/// Data and progress events of an asynchronous stream.
+#[derive(Debug, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum Event<T, D> {
+    /// Indicates that the stream will only produce data at times greater or equal to the
+    /// supplied time, or that it will produce no further data if `None`.
+    Progress(Option<T>),
+    /// A batch of data at a timestamp.
+    Message(T, D),
+}
+
+/// Converts to a timely `Stream` from an asynchronous stream of events.
+pub trait ToStreamAsync<T: Timestamp, D: Data> {
+    /// Converts a `futures::Stream` of `Event`s to a timely `Stream`.
+    ///
+    /// The asynchronous stream is polled whenever the operator is scheduled, with a waker that
+    /// activates the operator. Data produced by the stream are introduced at their timestamps,
+    /// which must be greater or equal to the time of the most recent progress event. The timely
+    /// stream completes once the asynchronous stream yields `Event::Progress(None)` or ends.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use timely::dataflow::operators::Capture;
+    /// use timely::dataflow::operators::capture::Extract;
+    /// use timely::dataflow::operators::to_stream::{Event, ToStreamAsync};
+    ///
+    /// let data = timely::example(|scope| {
+    ///     let events = vec![
+    ///         Event::Message(0, vec![0, 1]),
+    ///         Event::Progress(Some(2)),
+    ///         Event::Message(2, vec![2]),
+    ///     ];
+    ///     futures_util::stream::iter(events)
+    ///         .to_stream(scope)
+    ///         .capture()
+    /// });
+    ///
+    /// assert_eq!(data.extract(), vec![(0, vec![0, 1]), (2, vec![2])]);
+    /// ```
+    fn to_stream<S: Scope<Timestamp=T>>(self, scope: &S) -> Stream<S, D>;
+}
+
+impl<T, D, B, I> ToStreamAsync<T, D> for I
+where
+    T: Timestamp,
+    D: Data,
+    B: IntoIterator<Item=D>,
+    I: futures_util::stream::Stream<Item=Event<T, B>>+Unpin+'static,
+{
+    fn to_stream<S: Scope<Timestamp=T>>(mut self, scope: &S) -> Stream<S, D> {
+
+        source(scope, "ToStreamAsync", move |capability, info| {
+
+            // Acquire a thread-safe activator, which wakes the operator from other threads.
+            let activator = ::std::sync::Arc::new(scope.sync_activator_for(&info.address[..]));
+            let mut capability = Some(capability);
+
+            move |output| {
+
+                let waker = futures_util::task::waker(activator.clone());
+                let mut context = Context::from_waker(&waker);
+
+                // Consume all ready events; the waker will activate us once more are ready.
+                while capability.is_some() {
+                    match self.poll_next_unpin(&mut context) {
+                        Poll::Ready(Some(Event::Progress(Some(time)))) => {
+                            capability.as_mut().unwrap().downgrade(&time);
+                        },
+                        Poll::Ready(Some(Event::Message(time, data))) => {
+                            let delayed = capability.as_ref().unwrap().delayed(&time);
+                            output.session(&delayed).give_iterator(data.into_iter());
+                        },
+                        Poll::Ready(Some(Event::Progress(None))) | Poll::Ready(None) => {
+                            capability = None;
+                        },
+                        Poll::Pending => break,
+                    }
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,274 @@
+//! Keyed windows over event time.
+//!
+//! The operators in this module group the values of a stream of `(key, val)` records by key
+//! and by window, where windows are determined by an event time extracted from each value.
+//! Event times are timestamps of the stream, and the values of each window are presented to
+//! user logic, at a timestamp equal to the end of the window, once the input frontier has
+//! passed the end of the window. Records are exchanged by key, so that each key is windowed by
+//! a single worker.
+//!
+//! A record whose window has ended before the record's own timestamp cannot be reported at the
+//! end of the window, and is discarded.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::{Add, Rem, Sub};
+
+use crate::{Data, ExchangeData};
+use crate::order::{PartialOrder, TotalOrder};
+use crate::progress::Timestamp;
+use crate::dataflow::{Stream, Scope};
+use crate::dataflow::channels::pact::Exchange;
+use crate::dataflow::operators::exchange::hash_key;
+use crate::dataflow::operators::generic::FrontierNotificator;
+use crate::dataflow::operators::generic::operator::Operator;
+
+/// Timestamps that can bound windows, such as `u64`.
+pub trait EventTime: Timestamp+TotalOrder+Copy+Add<Output=Self>+Sub<Output=Self>+Rem<Output=Self> { }
+impl<T: Timestamp+TotalOrder+Copy+Add<Output=T>+Sub<Output=T>+Rem<Output=T>> EventTime for T { }
+
+/// The event times covered by a window, from `start` inclusive to `end` exclusive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Bounds<T> {
+    /// The least event time in the window.
+    pub start: T,
+    /// The least event time after the window.
+    pub end: T,
+}
+
+/// Keyed windows over event time.
+pub trait Window<G: Scope, K: ExchangeData+Hash+Eq, V: ExchangeData> where G::Timestamp: EventTime {
+
+    /// Groups values into consecutive windows of `size` event times.
+    ///
+    /// The window containing event time `t` starts at `t - t % size`.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Map, Capture};
+    /// use timely::dataflow::operators::capture::Extract;
+    /// use timely::dataflow::operators::window::Window;
+    ///
+    /// let captured = timely::example(|scope| {
+    ///     (0..10u64).to_stream(scope)
+    ///               .map(|x| (x % 2, x))
+    ///               .tumbling_window(5, |x| *x, |key, bounds, mut vals| {
+    ///                   vals.sort();
+    ///                   (*key, bounds.start, vals)
+    ///               })
+    ///               .capture()
+    /// });
+    ///
+    /// assert_eq!(captured.extract(), vec![
+    ///     (5, vec![(0, 0, vec![0, 2, 4]), (1, 0, vec![1, 3])]),
+    ///     (10, vec![(0, 5, vec![6, 8]), (1, 5, vec![5, 7, 9])]),
+    /// ]);
+    /// ```
+    fn tumbling_window<R: Data, E, L>(&self, size: G::Timestamp, time: E, logic: L) -> Stream<G, R>
+    where
+        E: Fn(&V)->G::Timestamp+'static,
+        L: FnMut(&K, Bounds<G::Timestamp>, Vec<V>)->R+'static;
+
+    /// Groups values into windows of `size` event times, starting every `slide` event times.
+    ///
+    /// Each value is presented in every window containing its event time. Windows start at
+    /// multiples of `slide`, and windows that would start before the default timestamp are
+    /// omitted.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Map, Capture};
+    /// use timely::dataflow::operators::capture::Extract;
+    /// use timely::dataflow::operators::window::Window;
+    ///
+    /// let captured = timely::example(|scope| {
+    ///     (0..6u64).to_stream(scope)
+    ///              .map(|x| ((), x))
+    ///              .sliding_window(4, 2, |x| *x, |_key, bounds, mut vals| {
+    ///                  vals.sort();
+    ///                  (bounds.start, vals)
+    ///              })
+    ///              .capture()
+    /// });
+    ///
+    /// assert_eq!(captured.extract(), vec![
+    ///     (4, vec![(0, vec![0, 1, 2, 3])]),
+    ///     (6, vec![(2, vec![2, 3, 4, 5])]),
+    ///     (8, vec![(4, vec![4, 5])]),
+    /// ]);
+    /// ```
+    fn sliding_window<R: Data, E, L>(&self, size: G::Timestamp, slide: G::Timestamp, time: E, logic: L) -> Stream<G, R>
+    where
+        E: Fn(&V)->G::Timestamp+'static,
+        L: FnMut(&K, Bounds<G::Timestamp>, Vec<V>)->R+'static;
+
+    /// Groups the values of each key into sessions separated by at least `gap` event times.
+    ///
+    /// A session ends `gap` event times after its last value, and sessions are merged when
+    /// a value arrives that is within `gap` of both. Values are presented in no particular order.
+    /// A value is discarded if its event time plus `gap` precedes its timestamp.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Map, Capture};
+    /// use timely::dataflow::operators::capture::Extract;
+    /// use timely::dataflow::operators::window::Window;
+    ///
+    /// let captured = timely::example(|scope| {
+    ///     vec![1u64, 2, 3, 10, 11, 20].to_stream(scope)
+    ///                                 .map(|x| (0, x))
+    ///                                 .session_window(5, |x| *x, |_key, bounds, mut vals| {
+    ///                                     vals.sort();
+    ///                                     (bounds.start, vals)
+    ///                                 })
+    ///                                 .capture()
+    /// });
+    ///
+    /// assert_eq!(captured.extract(), vec![
+    ///     (8, vec![(1, vec![1, 2, 3])]),
+    ///     (16, vec![(10, vec![10, 11])]),
+    ///     (25, vec![(20, vec![20])]),
+    /// ]);
+    /// ```
+    fn session_window<R: Data, E, L>(&self, gap: G::Timestamp, time: E, logic: L) -> Stream<G, R>
+    where
+        E: Fn(&V)->G::Timestamp+'static,
+        L: FnMut(&K, Bounds<G::Timestamp>, Vec<V>)->R+'static;
+}
+
+impl<G: Scope, K: ExchangeData+Hash+Eq, V: ExchangeData> Window<G, K, V> for Stream<G, (K, V)> where G::Timestamp: EventTime {
+
+    fn tumbling_window<R: Data, E, L>(&self, size: G::Timestamp, time: E, logic: L) -> Stream<G, R>
+    where
+        E: Fn(&V)->G::Timestamp+'static,
+        L: FnMut(&K, Bounds<G::Timestamp>, Vec<V>)->R+'static,
+    {
+        self.sliding_window(size, size, time, logic)
+    }
+
+    fn sliding_window<R: Data, E, L>(&self, size: G::Timestamp, slide: G::Timestamp, time: E, mut logic: L) -> Stream<G, R>
+    where
+        E: Fn(&V)->G::Timestamp+'static,
+        L: FnMut(&K, Bounds<G::Timestamp>, Vec<V>)->R+'static,
+    {
+        let zero: G::Timestamp = Default::default();
+        assert!(zero.less_than(&slide), "window slide must be positive");
+
+        // Values of each key, by the end of their windows.
+        let mut windows = HashMap::<G::Timestamp, HashMap<K, Vec<V>>>::new();
+        let mut vector = Vec::new();
+
+        self.unary_frontier(Exchange::new(|(key, _): &(K, V)| hash_key(key)), "SlidingWindow", move |_cap, _info| {
+
+            let mut notificator = FrontierNotificator::new();
+
+            move |input, output| {
+
+                input.for_each(|cap, data| {
+                    data.swap(&mut vector);
+                    for (key, val) in vector.drain(..) {
+                        let event = time(&val);
+                        // Visit windows from the latest start, while they still contain `event`.
+                        let mut start = event - event % slide;
+                        loop {
+                            let end = start + size;
+                            if event.less_than(&end) && cap.time().less_equal(&end) {
+                                windows
+                                    .entry(end)
+                                    .or_insert_with(|| { notificator.notify_at(cap.delayed(&end)); HashMap::new() })
+                                    .entry(key.clone())
+                                    .or_insert_with(Vec::new)
+                                    .push(val.clone());
+                            }
+                            if !event.less_than(&end) || start.less_than(&slide) { break; }
+                            start = start - slide;
+                        }
+                    }
+                });
+
+                notificator.for_each(&[input.frontier()], |cap, _not| {
+                    if let Some(keys) = windows.remove(cap.time()) {
+                        let bounds = Bounds { start: *cap.time() - size, end: *cap.time() };
+                        let mut session = output.session(&cap);
+                        for (key, vals) in keys {
+                            session.give(logic(&key, bounds, vals));
+                        }
+                    }
+                });
+            }
+        })
+    }
+
+    fn session_window<R: Data, E, L>(&self, gap: G::Timestamp, time: E, mut logic: L) -> Stream<G, R>
+    where
+        E: Fn(&V)->G::Timestamp+'static,
+        L: FnMut(&K, Bounds<G::Timestamp>, Vec<V>)->R+'static,
+    {
+        // Open sessions of each key, and the keys with sessions ending at each time.
+        let mut sessions = HashMap::<K, Vec<(Bounds<G::Timestamp>, Vec<V>)>>::new();
+        let mut ending = HashMap::<G::Timestamp, Vec<K>>::new();
+        let mut vector = Vec::new();
+
+        self.unary_frontier(Exchange::new(|(key, _): &(K, V)| hash_key(key)), "SessionWindow", move |_cap, _info| {
+
+            let mut notificator = FrontierNotificator::new();
+
+            move |input, output| {
+
+                input.for_each(|cap, data| {
+                    data.swap(&mut vector);
+                    for (key, val) in vector.drain(..) {
+                        let event = time(&val);
+                        let mut bounds = Bounds { start: event, end: event + gap };
+                        if !cap.time().less_equal(&bounds.end) { continue; }
+                        // Merge any open sessions that overlap the new value's session.
+                        let open = sessions.entry(key.clone()).or_insert_with(Vec::new);
+                        let mut vals = vec![val];
+                        let mut index = 0;
+                        while index < open.len() {
+                            let other = open[index].0;
+                            if other.start.less_than(&bounds.end) && bounds.start.less_than(&other.end) {
+                                let (other, mut other_vals) = open.swap_remove(index);
+                                if other.start.less_than(&bounds.start) { bounds.start = other.start; }
+                                if bounds.end.less_than(&other.end) { bounds.end = other.end; }
+                                vals.append(&mut other_vals);
+                            }
+                            else {
+                                index += 1;
+                            }
+                        }
+                        open.push((bounds, vals));
+                        ending
+                            .entry(bounds.end)
+                            .or_insert_with(|| { notificator.notify_at(cap.delayed(&bounds.end)); Vec::new() })
+                            .push(key);
+                    }
+                });
+
+                notificator.for_each(&[input.frontier()], |cap, _not| {
+                    if let Some(keys) = ending.remove(cap.time()) {
+                        let mut session = output.session(&cap);
+                        for key in keys {
+                            // Sessions may have been extended, or already reported.
+                            if let Some(open) = sessions.get_mut(&key) {
+                                let mut index = 0;
+                                while index < open.len() {
+                                    if open[index].0.end.less_equal(cap.time()) {
+                                        let (bounds, vals) = open.swap_remove(index);
+                                        session.give(logic(&key, bounds, vals));
+                                    }
+                                    else {
+                                        index += 1;
+                                    }
+                                }
+                                if open.is_empty() {
+                                    sessions.remove(&key);
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+        })
+    }
+}
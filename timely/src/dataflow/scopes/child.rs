@@ -3,7 +3,7 @@
 use std::rc::Rc;
 use std::cell::RefCell;
 
-use crate::communication::{Data, Push, Pull};
+use crate::communication::{Data, Priority, Push, Pull};
 use crate::communication::allocator::thread::{ThreadPusher, ThreadPuller};
 use crate::scheduling::Scheduler;
 use crate::scheduling::activate::Activations;
@@ -12,6 +12,7 @@ use crate::progress::{Source, Target};
 use crate::progress::timestamp::Refines;
 use crate::order::Product;
 use crate::logging::TimelyLogger as Logger;
+use crate::memory::MemoryRegistry;
 use crate::worker::AsWorker;
 
 use super::{ScopeParent, Scope};
@@ -57,6 +58,12 @@ where
     fn allocate<D: Data>(&mut self, identifier: usize, address: &[usize]) -> (Vec<Box<dyn Push<Message<D>>>>, Box<dyn Pull<Message<D>>>) {
         self.parent.allocate(identifier, address)
     }
+    fn allocate_with_budget<D: Data>(&mut self, identifier: usize, address: &[usize], budget: Option<usize>) -> (Vec<Box<dyn Push<Message<D>>>>, Box<dyn Pull<Message<D>>>) {
+        self.parent.allocate_with_budget(identifier, address, budget)
+    }
+    fn allocate_with_priority<D: Data>(&mut self, identifier: usize, address: &[usize], priority: Priority) -> (Vec<Box<dyn Push<Message<D>>>>, Box<dyn Pull<Message<D>>>) {
+        self.parent.allocate_with_priority(identifier, address, priority)
+    }
     fn pipeline<D: 'static>(&mut self, identifier: usize, address: &[usize]) -> (ThreadPusher<Message<D>>, ThreadPuller<Message<D>>) {
         self.parent.pipeline(identifier, address)
     }
@@ -66,6 +73,7 @@ where
     fn log_register(&self) -> ::std::cell::RefMut<crate::logging_core::Registry<crate::logging::WorkerIdentifier>> {
         self.parent.log_register()
     }
+    fn memory(&self) -> Rc<RefCell<MemoryRegistry>> { self.parent.memory() }
 }
 
 impl<'a, G, T> Scheduler for Child<'a, G, T>
@@ -1,6 +1,9 @@
 //! Starts a timely dataflow execution from configuration information and per-worker logic.
 
-use crate::communication::{initialize_from, Configuration, Allocator, allocator::AllocateBuilder, WorkerGuards};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+use crate::communication::{initialize_from_cancellable, Allocate, CancellationToken, Configuration, Allocator, allocator::AllocateBuilder, WorkerGuards};
+use crate::communication::allocator::simulation::{Chooser, Network, Simulated};
 use crate::dataflow::scopes::Child;
 use crate::worker::Worker;
 
@@ -88,6 +91,130 @@ where
     result
 }
 
+/// Executes a timely dataflow computation on simulated workers, all in the current thread.
+///
+/// The `simulate` method constructs `peers` workers connected by a simulated network, whose
+/// deliveries of messages and progress updates, and the order in which workers are stepped,
+/// are decided by `chooser`. The supplied closure is applied to each worker in turn, and must
+/// only construct dataflows, as no worker is stepped until all have been constructed. The
+/// workers are then stepped until their dataflows complete, and the results of the closure
+/// are returned in the order of the workers.
+///
+/// The same chooser, or a `Seeded` chooser from the same seed, reproduces the same schedule.
+///
+/// # Examples
+/// ```rust
+/// use timely::dataflow::operators::{ToStream, Exchange, Accumulate, Capture};
+/// use timely::dataflow::operators::capture::Extract;
+/// use timely::communication::allocator::simulation::Seeded;
+///
+/// // the count must not depend on the order in which messages are delivered.
+/// for seed in 0 .. 10 {
+///     let mut captures = timely::simulate(3, Seeded::new(seed), |worker| {
+///         let index = worker.index() as u64;
+///         worker.dataflow::<u64,_,_>(|scope| {
+///             (10 * index .. 10 * index + 10)
+///                 .to_stream(scope)
+///                 .exchange(|x| *x)
+///                 .count()
+///                 .exchange(|_| 0)
+///                 .accumulate(0, |sum, data| { for x in data.iter() { *sum += *x; } })
+///                 .capture()
+///         })
+///     });
+///     assert_eq!(captures.remove(0).extract(), vec![(0, vec![30])]);
+/// }
+/// ```
+pub fn simulate<T, C, F>(peers: usize, chooser: C, func: F) -> Vec<T>
+where
+    C: Chooser+'static,
+    F: FnMut(&mut Worker<Simulated>)->T,
+{
+    let mut simulation = Simulation::new(peers, chooser);
+    let results = simulation.build(func);
+    simulation.run();
+    results
+}
+
+/// Simulated workers sharing the current thread, stepped under the control of a `Chooser`.
+///
+/// A `Simulation` allows dataflows to be constructed and fed with input between steps, where
+/// `simulate` only constructs dataflows before running them to completion.
+///
+/// # Examples
+/// ```rust
+/// use timely::execute::Simulation;
+/// use timely::communication::allocator::simulation::Exhaustive;
+/// use timely::dataflow::InputHandle;
+/// use timely::dataflow::operators::{Input, Exchange, Probe};
+///
+/// // enumerate the schedules that differ in their first six decisions.
+/// let exhaustive = std::rc::Rc::new(std::cell::RefCell::new(Exhaustive::new(6)));
+/// loop {
+///     let mut simulation = Simulation::new(2, exhaustive.clone());
+///     let mut built = simulation.build(|worker| {
+///         let mut input = InputHandle::new();
+///         let probe = worker.dataflow(|scope| scope.input_from(&mut input).exchange(|x: &u64| *x).probe());
+///         (input, probe)
+///     });
+///     for round in 0 .. 3 {
+///         for (input, _probe) in built.iter_mut() {
+///             input.send(round);
+///             input.advance_to(round + 1);
+///         }
+///         while built.iter().any(|(_, probe)| probe.less_than(&(round + 1))) {
+///             simulation.step();
+///         }
+///     }
+///     if !exhaustive.borrow_mut().next_schedule() { break; }
+/// }
+/// ```
+pub struct Simulation {
+    network: Network,
+    workers: Vec<Worker<Simulated>>,
+    // The worker stepped when the chooser chooses zero, so that workers otherwise take turns.
+    next: usize,
+}
+
+impl Simulation {
+    /// Creates `peers` simulated workers, scheduled by `chooser`.
+    pub fn new<C: Chooser+'static>(peers: usize, chooser: C) -> Self {
+        let network = Network::new(peers, chooser);
+        let workers = network.allocators().into_iter().map(Worker::new).collect();
+        Simulation { network, workers, next: 0 }
+    }
+
+    /// Applies `func` to each worker in turn, returning the results in the order of the workers.
+    pub fn build<T, F: FnMut(&mut Worker<Simulated>)->T>(&mut self, func: F) -> Vec<T> {
+        self.workers.iter_mut().map(func).collect()
+    }
+
+    /// Steps a worker chosen by the chooser, and indicates if any worker has dataflows remaining
+    /// or any message is still in flight.
+    pub fn step(&mut self) -> bool {
+        let peers = self.workers.len();
+        let index = (self.next + self.network.choose(peers)) % peers;
+        self.next = (index + 1) % peers;
+        self.workers[index].step();
+        self.network.in_flight() > 0 || self.workers.iter().any(|worker| !worker.installed_dataflows().is_empty())
+    }
+
+    /// Steps workers until their dataflows complete and the network is empty.
+    pub fn run(&mut self) {
+        while self.step() { }
+    }
+
+    /// The simulated workers.
+    pub fn workers(&mut self) -> &mut [Worker<Simulated>] {
+        &mut self.workers
+    }
+
+    /// The network connecting the workers.
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+}
+
 /// Executes a timely dataflow from a configuration and per-communicator logic.
 ///
 /// The `execute` method takes a `Configuration` and spins up some number of
@@ -98,6 +225,14 @@ where
 /// containing a `WorkerGuards<T>` (or error information), which can be joined
 /// to recover the result `T` values from the local workers.
 ///
+/// The computation can be stopped early with `WorkerGuards::shutdown`, which cancels the
+/// token that each worker exposes through `Worker::cancellation`. Workers then drop their
+/// dataflows, and network connections are torn down once the workers have stopped.
+///
+/// Should a worker panic, the panic is announced to all other workers, including those of
+/// other processes, which panic in turn the next time they step. Joining the guards then
+/// reports the worker that failed, and its panic message.
+///
 /// # Examples
 /// ```rust
 /// use timely::dataflow::operators::{ToStream, Inspect};
@@ -142,11 +277,28 @@ where
     T:Send+'static,
     F: Fn(&mut Worker<Allocator>)->T+Send+Sync+'static {
 
+    // If an environment variable is set, serve metrics of workers and communication threads.
+    #[cfg(feature = "metrics")]
+    let metrics = match ::std::env::var("TIMELY_METRICS_ADDR") {
+        Ok(addr) => {
+            let metrics = crate::metrics::Metrics::new();
+            let local = metrics.serve(&addr[..]).map_err(|error| format!("Could not serve metrics at {:?}: {}", addr, error))?;
+            eprintln!("serving metrics at {}", local);
+            Some(metrics)
+        },
+        Err(_) => None,
+    };
+    #[cfg(feature = "metrics")]
+    let comm_metrics = metrics.clone();
+
     if let Configuration::Cluster { ref mut log_fn, .. } = config {
 
-        *log_fn = Box::new(|events_setup| {
+        // Communication events go to the supplied `log_fn`, and also to any of the destinations
+        // named by environment variables.
+        let supplied = ::std::mem::replace(log_fn, Box::new(|_| None));
+        *log_fn = Box::new(move |events_setup| {
 
-            let mut result = None;
+            let mut result = supplied(events_setup);
             if let Ok(addr) = ::std::env::var("TIMELY_COMM_LOG_ADDR") {
 
                 use ::std::net::TcpStream;
@@ -158,25 +310,35 @@ where
                 if let Ok(stream) = TcpStream::connect(&addr) {
                     let writer = EventWriter::new(stream);
                     let mut logger = BatchLogger::new(writer);
-                    result = Some(crate::logging_core::Logger::new(
-                        ::std::time::Instant::now(),
-                        events_setup,
-                        move |time, data| logger.publish_batch(time, data)
-                    ));
+                    let action = move |time: &::std::time::Duration, data: &mut Vec<_>| logger.publish_batch(time, data);
+                    match result {
+                        Some(ref result) => result.attach(action),
+                        None => result = Some(crate::logging_core::Logger::new(::std::time::Instant::now(), events_setup, action)),
+                    }
                 }
                 else {
                     panic!("Could not connect to communication log address: {:?}", addr);
                 }
             }
+
+            #[cfg(feature = "metrics")]
+            {
+                if let Some(metrics) = comm_metrics.as_ref() {
+                    metrics.attach_communication(&mut result, events_setup);
+                }
+            }
+
             result
         });
     }
 
     let (allocators, other) = config.try_build()?;
 
-    initialize_from(allocators, other, move |allocator| {
+    let cancellation = CancellationToken::new();
+    let token = cancellation.clone();
+    initialize_from_cancellable(allocators, other, cancellation, move |allocator| {
 
-        let mut worker = Worker::new(allocator);
+        let worker = Worker::new(allocator).with_cancellation(token.clone());
 
         // If an environment variable is set, use it as the default timely logging.
         if let Ok(addr) = ::std::env::var("TIMELY_WORKER_LOG_ADDR") {
@@ -189,7 +351,7 @@ where
                 let writer = EventWriter::new(stream);
                 let mut logger = BatchLogger::new(writer);
                 worker.log_register()
-                    .insert::<TimelyEvent,_>("timely", move |time, data|
+                    .attach::<TimelyEvent,_>("timely", move |time, data|
                         logger.publish_batch(time, data)
                     );
             }
@@ -198,9 +360,30 @@ where
             }
         }
 
+        #[cfg(feature = "metrics")]
+        {
+            if let Some(metrics) = metrics.as_ref() {
+                metrics.install(&mut worker.log_register());
+            }
+        }
+
+        run_worker(worker, &func)
+    })
+}
+
+/// Runs `func` and then the worker's dataflows to completion.
+///
+/// Should the worker panic, the failure is recorded and announced to the workers of other
+/// processes before the panic resumes, so that they abort rather than await the worker.
+fn run_worker<A: Allocate, T, F: Fn(&mut Worker<A>)->T>(mut worker: Worker<A>, func: &F) -> T {
+    let result = catch_unwind(AssertUnwindSafe(|| {
         let result = func(&mut worker);
         while worker.step_or_park(None) { }
         result
+    }));
+    result.unwrap_or_else(|payload| {
+        worker.fail(&*payload);
+        resume_unwind(payload)
     })
 }
 
@@ -226,6 +409,32 @@ where
 /// If not specified, `localhost` will be used, with port numbers increasing from 2101 (chosen
 /// arbitrarily).
 ///
+/// `-f, --tolerate-failures`: report the loss of a process through `Worker::lost_processes`,
+/// rather than panicking.
+///
+/// `-t, --transport`: how processes exchange data; one of `tcp` (the default), `unix` for Unix
+/// domain sockets, or `shm` for shared memory. The latter two require all processes to be on
+/// one host, and use addresses that are socket paths, by default in the temporary directory.
+///
+/// `--tls-ca`, `--tls-cert`, `--tls-key`: PEM files of the trusted certificate authorities, and
+/// of this process's certificate chain and private key. If supplied, connections between
+/// processes use mutually authenticated TLS, which requires the `tls` feature.
+///
+/// `--compression`: how to compress connections between processes; currently only `lz4`.
+/// A connection is compressed only if the processes at both ends agree, which requires the
+/// `compression` feature.
+///
+/// `--connect-timeout`: seconds allowed to establish connections to other processes, after
+/// which initialization fails; by default, connections are retried indefinitely.
+///
+/// `--rendezvous`: a directory through which processes exchange their addresses. Addresses then
+/// need only say where each process binds, and may use port 0; if there is no hostfile, each
+/// process binds a port of `127.0.0.1`. Each launch should use a directory of its own.
+///
+/// `--network-threads`: serve TCP connections from this many event loop threads, rather than
+/// from a send and a receive thread per connection, which requires the `event-loop` feature.
+/// Such connections can be neither secured with TLS nor compressed.
+///
 /// # Examples
 ///
 /// ```rust
@@ -281,10 +490,10 @@ where
     A: AllocateBuilder+'static,
     T: Send+'static,
     F: Fn(&mut Worker<<A as AllocateBuilder>::Allocator>)->T+Send+Sync+'static {
-    initialize_from(builders, others, move |allocator| {
-        let mut worker = Worker::new(allocator);
-        let result = func(&mut worker);
-        while worker.step_or_park(None) { }
-        result
+    let cancellation = CancellationToken::new();
+    let token = cancellation.clone();
+    initialize_from_cancellable(builders, others, cancellation, move |allocator| {
+        let worker = Worker::new(allocator).with_cancellation(token.clone());
+        run_worker(worker, &func)
     })
 }
\ No newline at end of file
@@ -63,11 +63,12 @@ extern crate abomonation;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate futures_util;
 extern crate timely_communication;
 extern crate timely_bytes;
 extern crate timely_logging;
 
-pub use execute::{execute, execute_directly, execute_from_args, example};
+pub use execute::{execute, execute_directly, execute_from_args, example, simulate};
 pub use order::PartialOrder;
 
 pub use timely_communication::Configuration;
@@ -95,8 +96,12 @@ pub mod execute;
 pub mod order;
 
 pub mod logging;
+pub mod memory;
 // pub mod log_events;
 
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
 pub mod scheduling;
 
 /// A composite trait for types usable as data in timely dataflow.
@@ -109,5 +114,8 @@ impl<T: Clone+'static> Data for T { }
 ///
 /// The `ExchangeData` trait extends `Data` with any requirements imposed by the `timely_communication`
 /// `Data` trait, which describes requirements for communication along channels.
+///
+/// Types that do not implement `Abomonation` can be exchanged by wrapping them in
+/// `communication::codec::Coded`, once they implement `communication::codec::Codec`.
 pub trait ExchangeData: Data + communication::Data { }
 impl<T: Data + communication::Data> ExchangeData for T { }
\ No newline at end of file
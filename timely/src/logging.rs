@@ -214,6 +214,32 @@ impl ParkEvent {
     pub fn unpark() -> Self { ParkEvent::Unpark }
 }
 
+/// The purpose for which an operator holds memory.
+#[derive(Serialize, Deserialize, Abomonation, Debug, Clone, Hash, PartialEq, Eq, Ord, PartialOrd)]
+pub enum MemoryKind {
+    /// Records buffered at the output port with this index.
+    Output(usize),
+    /// Records queued for the operator in the channel with this identifier.
+    Channel(usize),
+    /// State declared by the operator under this name.
+    State(String),
+    /// Bytes the worker has sent to the process with this index, awaiting the network.
+    Sent(usize),
+    /// Bytes the worker has received from the process with this index, awaiting the worker.
+    Received(usize),
+}
+
+#[derive(Serialize, Deserialize, Abomonation, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+/// The bytes held on behalf of an operator
+pub struct MemoryEvent {
+    /// Address of the operator, or empty for memory held by the worker itself
+    pub addr: Vec<usize>,
+    /// Purpose of the memory
+    pub kind: MemoryKind,
+    /// Number of bytes held
+    pub bytes: usize,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Abomonation, Hash, Eq, PartialEq, Ord, PartialOrd)]
 /// An event in a timely worker
 pub enum TimelyEvent {
@@ -245,6 +271,8 @@ pub enum TimelyEvent {
     Park(ParkEvent),
     /// Unstructured event.
     Text(String),
+    /// Memory usage change.
+    Memory(MemoryEvent),
 }
 
 impl From<OperatesEvent> for TimelyEvent {
@@ -298,3 +326,359 @@ impl From<InputEvent> for TimelyEvent {
 impl From<ParkEvent> for TimelyEvent {
     fn from(v: ParkEvent) -> TimelyEvent { TimelyEvent::Park(v) }
 }
+
+impl From<MemoryEvent> for TimelyEvent {
+    fn from(v: MemoryEvent) -> TimelyEvent { TimelyEvent::Memory(v) }
+}
+
+/// The operators and channels of dataflows, assembled from logged events.
+///
+/// A `DataflowGraph` observes `OperatesEvent` and `ChannelsEvent` events, and renders the graph
+/// they describe as Graphviz DOT or as JSON. Nested scopes are identified by operator addresses,
+/// and the channels of a scope connect its children to one another and to the scope's own
+/// inputs and outputs, which are the ports of its child zero.
+///
+/// # Examples
+/// ```
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+/// use timely::dataflow::operators::{ToStream, Map, Inspect};
+/// use timely::logging::TimelyEvent;
+///
+/// timely::execute_from_args(std::env::args(), |worker| {
+///
+///     // Other consumers of the log continue to see its events.
+///     let events = Rc::new(Cell::new(0));
+///     let events2 = events.clone();
+///     worker.log_register()
+///           .insert::<TimelyEvent,_>("timely", move |_time, data| events2.set(events2.get() + data.len()));
+///
+///     let graph = worker.dataflow_graph();
+///     worker.dataflow::<u64,_,_>(|scope| {
+///         (0 .. 10).to_stream(scope)
+///                  .map(|x| x + 1)
+///                  .inspect(|x| println!("seen: {:?}", x));
+///     });
+///
+///     let graph = graph.borrow();
+///     assert_eq!(graph.operators().count(), 4);
+///     assert_eq!(graph.channels().len(), 2);
+///     assert!(graph.to_dot().starts_with("digraph"));
+///     assert!(graph.to_json().contains("\"name\":\"Map\""));
+///     assert!(events.get() > 0);
+/// }).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DataflowGraph {
+    operators: ::std::collections::BTreeMap<Vec<usize>, OperatesEvent>,
+    channels: Vec<ChannelsEvent>,
+}
+
+impl DataflowGraph {
+    /// Creates an empty graph.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Records the operator or channel described by `event`, and ignores other events.
+    pub fn observe(&mut self, event: &TimelyEvent) {
+        match event {
+            TimelyEvent::Operates(operates) => { self.operators.insert(operates.addr.clone(), operates.clone()); },
+            TimelyEvent::Channels(channels) => { self.channels.push(channels.clone()); },
+            _ => { },
+        }
+    }
+
+    /// The recorded operators, ordered by address.
+    pub fn operators(&self) -> impl Iterator<Item=&OperatesEvent> {
+        self.operators.values()
+    }
+
+    /// The recorded channels, in the order they were observed.
+    pub fn channels(&self) -> &[ChannelsEvent] {
+        &self.channels[..]
+    }
+
+    /// Renders the graph in Graphviz DOT format.
+    ///
+    /// Each scope is drawn as a cluster containing its children, and each other operator as a
+    /// node. The inputs and outputs of a scope are drawn as points on the boundary of its cluster,
+    /// through which channels into and out of the scope pass.
+    pub fn to_dot(&self) -> String {
+
+        // Input and output ports of scopes through which channels pass.
+        let mut ports = Ports::new();
+        let mut edges = Vec::new();
+        for channel in self.channels.iter() {
+            let source = self.endpoint(&channel.scope_addr, channel.source, false, &mut ports);
+            let target = self.endpoint(&channel.scope_addr, channel.target, true, &mut ports);
+            edges.push((source, target));
+        }
+
+        let mut dot = String::from("digraph dataflow {\n");
+        let roots = self.operators.keys().filter(|addr| addr.len() == 1).cloned().collect::<Vec<_>>();
+        for addr in roots {
+            self.dot_operator(&addr, &ports, 1, &mut dot);
+        }
+        for (source, target) in edges {
+            dot.push_str(&format!("    {} -> {};\n", source, target));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the graph as a JSON object with `operators` and `channels` arrays.
+    ///
+    /// Operators and channels have the fields of `OperatesEvent` and `ChannelsEvent`, with
+    /// addresses and descriptors as arrays of integers.
+    pub fn to_json(&self) -> String {
+        let operators = self.operators().map(|operator| {
+            format!("{{\"id\":{},\"addr\":{:?},\"name\":{}}}", operator.id, operator.addr, json_string(&operator.name))
+        }).collect::<Vec<_>>();
+        let mut channels = self.channels.iter().collect::<Vec<_>>();
+        channels.sort_by_key(|channel| channel.id);
+        let channels = channels.into_iter().map(|channel| {
+            format!(
+                "{{\"id\":{},\"scope_addr\":{:?},\"source\":[{},{}],\"target\":[{},{}]}}",
+                channel.id, channel.scope_addr, channel.source.0, channel.source.1, channel.target.0, channel.target.1,
+            )
+        }).collect::<Vec<_>>();
+        format!("{{\"operators\":[{}],\"channels\":[{}]}}", operators.join(","), channels.join(","))
+    }
+
+    /// Returns `true` if the operator at `addr` contains other operators.
+    fn is_scope(&self, addr: &[usize]) -> bool {
+        self.operators.keys().any(|other| other.len() > addr.len() && other.starts_with(addr))
+    }
+
+    /// The DOT node of a channel's source or target, recording any scope ports it passes through.
+    fn endpoint(&self, scope: &[usize], (node, port): (usize, usize), is_target: bool, ports: &mut Ports) -> String {
+        let mut addr = scope.to_vec();
+        // Child zero is the scope itself, whose inputs are sources and outputs are targets.
+        let is_input = if node == 0 { !is_target } else { addr.push(node); is_target };
+        if node == 0 || self.is_scope(&addr) {
+            let node = port_node(&addr, is_input, port);
+            let (inputs, outputs) = ports.entry(addr).or_default();
+            let list = if is_input { inputs } else { outputs };
+            if !list.contains(&port) { list.push(port); }
+            node
+        }
+        else {
+            operator_node(&addr)
+        }
+    }
+
+    /// Renders the operator at `addr`, and any operators it contains.
+    fn dot_operator(&self, addr: &[usize], ports: &Ports, depth: usize, dot: &mut String) {
+        let indent = "    ".repeat(depth);
+        let name = self.operators.get(addr).map(|operator| &operator.name[..]).unwrap_or("");
+        if self.is_scope(addr) {
+            dot.push_str(&format!("{}subgraph cluster_{} {{\n", indent, node_suffix(addr)));
+            dot.push_str(&format!("{}    label=\"{}\";\n", indent, dot_escape(name)));
+            if let Some((inputs, outputs)) = ports.get(addr) {
+                for &port in inputs.iter() {
+                    dot.push_str(&format!("{}    {} [shape=point, xlabel=\"in {}\"];\n", indent, port_node(addr, true, port), port));
+                }
+                for &port in outputs.iter() {
+                    dot.push_str(&format!("{}    {} [shape=point, xlabel=\"out {}\"];\n", indent, port_node(addr, false, port), port));
+                }
+            }
+            let children = self.operators.keys().filter(|child| child.len() == addr.len() + 1 && child.starts_with(addr));
+            for child in children {
+                self.dot_operator(child, ports, depth + 1, dot);
+            }
+            dot.push_str(&format!("{}}}\n", indent));
+        }
+        else {
+            dot.push_str(&format!("{}{} [label=\"{}\"];\n", indent, operator_node(addr), dot_escape(name)));
+        }
+    }
+}
+
+/// The input and output ports of scopes, by scope address.
+type Ports = ::std::collections::BTreeMap<Vec<usize>, (Vec<usize>, Vec<usize>)>;
+
+/// Converts timely events into the Chrome trace-event format.
+///
+/// The output is a JSON array of trace events, viewable in `chrome://tracing` or Perfetto, with
+/// a timeline for each worker. Operator executions, from `ScheduleEvent`s, and parking, from
+/// `ParkEvent`s, are shown as nested slices. Message sends and receives are shown as instant
+/// events joined by flow arrows, and progress sends and receives as instant events. Operators
+/// are named using `OperatesEvent`s, which should be observed before the operators execute.
+///
+/// A `ChromeTrace` can be attached to the `"timely"` log, alongside other consumers of its
+/// events, or can be fed events replayed from a captured log stream.
+///
+/// # Examples
+/// ```
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+/// use timely::dataflow::operators::{ToStream, Inspect};
+/// use timely::logging::{ChromeTrace, TimelyEvent};
+///
+/// timely::execute_from_args(std::env::args(), |worker| {
+///
+///     let buffer = Rc::new(RefCell::new(ChromeTrace::new(Vec::new())));
+///     let buffer2 = buffer.clone();
+///     worker.log_register()
+///           .attach::<TimelyEvent,_>("timely", move |time, data| buffer2.borrow_mut().publish_batch(time, data));
+///     let graph = worker.dataflow_graph();
+///
+///     worker.dataflow::<u64,_,_>(|scope| {
+///         (0 .. 10).to_stream(scope)
+///                  .inspect(|x| println!("seen: {:?}", x));
+///     });
+///     while worker.step() { }
+///     worker.log_register().remove("timely");
+///
+///     let trace = Rc::try_unwrap(buffer).ok().unwrap().into_inner().finish().unwrap();
+///     let trace = String::from_utf8(trace).unwrap();
+///     assert!(trace.starts_with("["));
+///     assert!(trace.contains("\"name\":\"ToStream\",\"ph\":\"B\""));
+///     assert_eq!(graph.borrow().operators().count(), 3);
+/// }).unwrap();
+/// ```
+pub struct ChromeTrace<W: ::std::io::Write> {
+    writer: Option<W>,
+    // Operator names, by worker and operator identifier.
+    names: ::std::collections::HashMap<(WorkerIdentifier, usize), String>,
+    // Workers whose timelines have been named.
+    workers: ::std::collections::HashSet<WorkerIdentifier>,
+    // Whether an event has been written, and so the next must be preceded by a comma.
+    written: bool,
+}
+
+impl<W: ::std::io::Write> ChromeTrace<W> {
+
+    /// Creates a trace that writes to `writer`.
+    pub fn new(writer: W) -> Self {
+        ChromeTrace {
+            writer: Some(writer),
+            names: ::std::collections::HashMap::new(),
+            workers: ::std::collections::HashSet::new(),
+            written: false,
+        }
+    }
+
+    /// Writes the trace events, if any, for an event logged by `worker` at `time`.
+    pub fn observe(&mut self, time: &Duration, worker: WorkerIdentifier, event: &TimelyEvent) -> ::std::io::Result<()> {
+
+        if self.workers.insert(worker) {
+            self.write(format!(
+                "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":{},\"args\":{{\"name\":\"worker {}\"}}}}",
+                worker, worker,
+            ))?;
+        }
+
+        let ts = time.as_secs() as f64 * 1_000_000.0 + f64::from(time.subsec_nanos()) / 1_000.0;
+        let common = format!("\"pid\":0,\"tid\":{},\"ts\":{:.3}", worker, ts);
+
+        match event {
+            TimelyEvent::Operates(operates) => {
+                self.names.insert((worker, operates.id), operates.name.clone());
+                Ok(())
+            },
+            TimelyEvent::Schedule(schedule) => {
+                let phase = if schedule.start_stop == StartStop::Start { "B" } else { "E" };
+                let name = self.names.get(&(worker, schedule.id)).cloned().unwrap_or_else(|| format!("operator {}", schedule.id));
+                self.write(format!(
+                    "{{\"name\":{},\"ph\":\"{}\",{},\"args\":{{\"id\":{}}}}}",
+                    json_string(&name), phase, common, schedule.id,
+                ))
+            },
+            TimelyEvent::Park(park) => {
+                let phase = if let ParkEvent::Park(_) = park { "B" } else { "E" };
+                self.write(format!("{{\"name\":\"park\",\"ph\":\"{}\",{}}}", phase, common))
+            },
+            TimelyEvent::Messages(messages) => {
+                let (name, phase) = if messages.is_send { ("send", "s") } else { ("recv", "f") };
+                let args = format!(
+                    "\"args\":{{\"channel\":{},\"source\":{},\"target\":{},\"seq_no\":{},\"length\":{}}}",
+                    messages.channel, messages.source, messages.target, messages.seq_no, messages.length,
+                );
+                // Flow events join the send and receive of a message, which may be on different workers.
+                let flow = format!("\"{}-{}-{}-{}\"", messages.channel, messages.source, messages.target, messages.seq_no);
+                self.write(format!("{{\"name\":\"{}\",\"cat\":\"messages\",\"ph\":\"i\",\"s\":\"t\",{},{}}}", name, common, args))?;
+                self.write(format!("{{\"name\":\"message\",\"cat\":\"messages\",\"ph\":\"{}\",\"bp\":\"e\",\"id\":{},{}}}", phase, flow, common))
+            },
+            TimelyEvent::Progress(progress) => {
+                let name = if progress.is_send { "progress send" } else { "progress recv" };
+                self.write(format!(
+                    "{{\"name\":\"{}\",\"cat\":\"progress\",\"ph\":\"i\",\"s\":\"t\",{},\"args\":{{\"channel\":{},\"source\":{},\"seq_no\":{},\"addr\":{:?},\"messages\":{},\"internal\":{}}}}}",
+                    name, common, progress.channel, progress.source, progress.seq_no, progress.addr, progress.messages.len(), progress.internal.len(),
+                ))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the trace events for a batch of logged events, for use as a logger action.
+    ///
+    /// # Panics
+    ///
+    /// Panics if writing fails.
+    pub fn publish_batch(&mut self, _time: &Duration, data: &mut Vec<(Duration, WorkerIdentifier, TimelyEvent)>) {
+        for (time, worker, event) in data.drain(..) {
+            self.observe(&time, worker, &event).expect("failed to write trace events");
+        }
+        self.writer.as_mut().unwrap().flush().expect("failed to write trace events");
+    }
+
+    /// Completes the JSON array and returns the writer.
+    ///
+    /// A trace that is dropped without being finished lacks the closing bracket of the array,
+    /// which trace viewers accept.
+    pub fn finish(mut self) -> ::std::io::Result<W> {
+        let mut writer = self.writer.take().unwrap();
+        if !self.written { writer.write_all(b"[")?; }
+        writer.write_all(b"]\n")?;
+        writer.flush()?;
+        Ok(writer)
+    }
+
+    /// Writes one trace event.
+    fn write(&mut self, event: String) -> ::std::io::Result<()> {
+        let writer = self.writer.as_mut().unwrap();
+        writer.write_all(if self.written { b",\n" } else { b"[\n" })?;
+        self.written = true;
+        writer.write_all(event.as_bytes())
+    }
+}
+
+/// Underscore-separated address, used in DOT identifiers.
+fn node_suffix(addr: &[usize]) -> String {
+    addr.iter().map(|index| index.to_string()).collect::<Vec<_>>().join("_")
+}
+
+/// The DOT identifier of an operator.
+fn operator_node(addr: &[usize]) -> String {
+    format!("op_{}", node_suffix(addr))
+}
+
+/// The DOT identifier of an input or output of a scope.
+fn port_node(addr: &[usize], is_input: bool, port: usize) -> String {
+    format!("{}_{}_{}", if is_input { "in" } else { "out" }, node_suffix(addr), port)
+}
+
+/// Escapes a string for use in a quoted DOT label.
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Renders a string as a JSON string literal.
+fn json_string(text: &str) -> String {
+    let mut result = String::from("\"");
+    for character in text.chars() {
+        match character {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
@@ -0,0 +1,161 @@
+//! Accounting of the memory held on behalf of operators.
+//!
+//! Each worker maintains a `MemoryRegistry` of gauges, each reporting the bytes held for one
+//! purpose on behalf of one operator: records buffered at its outputs, records queued in the
+//! worker's channels to its inputs, and any state the operator declares. Sizes are shallow, in
+//! that they count `size_of` each record, and not any memory the records own indirectly.
+//!
+//! Channels are accounted for the records a worker sends to itself, through pipeline channels
+//! or to its own part of an exchange, and which its operators have not yet received. Records
+//! sent to workers of the same process are not accounted. Records sent to and received from
+//! other processes are accounted as the serialized bytes queued between the worker and the
+//! network threads, per process, with an empty address as they are held for the worker rather
+//! than for any one operator.
+//!
+//! The registry is queried with `Worker::memory_usage`, and changes in usage are logged to the
+//! `"timely"` log as `MemoryEvent`s, each time the worker steps.
+//!
+//! # Examples
+//! ```
+//! use timely::dataflow::{InputHandle, Scope};
+//! use timely::dataflow::operators::{Input, Probe};
+//! use timely::dataflow::operators::generic::Operator;
+//! use timely::dataflow::channels::pact::Pipeline;
+//! use timely::logging::MemoryKind;
+//! use timely::worker::AsWorker;
+//!
+//! timely::execute_from_args(std::env::args(), |worker| {
+//!
+//!     let mut input = InputHandle::new();
+//!     let probe = worker.dataflow::<u64,_,_>(|scope| {
+//!         let memory = scope.memory();
+//!         scope.input_from(&mut input)
+//!              .unary(Pipeline, "Collect", move |_cap, info| {
+//!                  // Declare the memory held by the operator's state.
+//!                  let gauge = memory.borrow_mut().state_gauge(&info.address, "collected");
+//!                  let mut collected = Vec::new();
+//!                  move |input, output| {
+//!                      input.for_each(|time, data| {
+//!                          collected.extend(data.iter().cloned());
+//!                          gauge.set(collected.capacity() * std::mem::size_of::<u64>());
+//!                          output.session(&time).give_vec(&mut data.replace(Vec::new()));
+//!                      });
+//!                  }
+//!              })
+//!              .probe()
+//!     });
+//!
+//!     for round in 0 .. 10u64 { input.send(round); }
+//!     input.advance_to(1);
+//!     while probe.less_than(input.time()) { worker.step(); }
+//!
+//!     let usage = worker.memory_usage();
+//!     let state = usage.iter().find(|event| event.kind == MemoryKind::State("collected".to_owned()));
+//!     assert!(state.unwrap().bytes >= 80);
+//! }).unwrap();
+//! ```
+
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::logging::{MemoryEvent, MemoryKind};
+
+/// A count of bytes, shared between the holder of the memory and the worker's registry.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryGauge {
+    bytes: Rc<Cell<usize>>,
+}
+
+impl MemoryGauge {
+    /// Sets the number of bytes held.
+    #[inline]
+    pub fn set(&self, bytes: usize) { self.bytes.set(bytes); }
+    /// Increases the number of bytes held.
+    #[inline]
+    pub fn add(&self, bytes: usize) { self.bytes.set(self.bytes.get() + bytes); }
+    /// Decreases the number of bytes held, saturating at zero.
+    #[inline]
+    pub fn sub(&self, bytes: usize) { self.bytes.set(self.bytes.get().saturating_sub(bytes)); }
+    /// The number of bytes held.
+    #[inline]
+    pub fn get(&self) -> usize { self.bytes.get() }
+}
+
+/// An operator address, the purpose of the memory, its gauge, and the bytes last logged.
+type Entry = (Vec<usize>, MemoryKind, Weak<Cell<usize>>, usize);
+
+/// The gauges of a worker, and the values last logged for each.
+#[derive(Default)]
+pub struct MemoryRegistry {
+    entries: Vec<Entry>,
+    /// Gauges of the bytes sent to and received from each other process.
+    queues: HashMap<usize, (MemoryGauge, MemoryGauge)>,
+}
+
+impl MemoryRegistry {
+
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a gauge for memory of `kind` held on behalf of the operator at `addr`.
+    ///
+    /// The gauge is reported until it and all of its clones are dropped.
+    pub fn register(&mut self, addr: &[usize], kind: MemoryKind) -> MemoryGauge {
+        let gauge = MemoryGauge::default();
+        self.entries.push((addr.to_vec(), kind, Rc::downgrade(&gauge.bytes), 0));
+        gauge
+    }
+
+    /// Creates a gauge for state named `name` held by the operator at `addr`.
+    pub fn state_gauge(&mut self, addr: &[usize], name: &str) -> MemoryGauge {
+        self.register(addr, MemoryKind::State(name.to_owned()))
+    }
+
+    /// Records the bytes queued between the worker and other processes.
+    ///
+    /// The argument lists `(process, sent, received)`, as reported by `Allocate::queued_bytes`.
+    pub(crate) fn set_queued_bytes(&mut self, queued: &[(usize, usize, usize)]) {
+        for &(process, sent, received) in queued.iter() {
+            if !self.queues.contains_key(&process) {
+                let gauges = (self.register(&[], MemoryKind::Sent(process)), self.register(&[], MemoryKind::Received(process)));
+                self.queues.insert(process, gauges);
+            }
+            let gauges = &self.queues[&process];
+            gauges.0.set(sent);
+            gauges.1.set(received);
+        }
+    }
+
+    /// The bytes currently reported by each live gauge.
+    pub fn usage(&mut self) -> Vec<MemoryEvent> {
+        self.entries.retain(|entry| entry.2.upgrade().is_some());
+        self.entries
+            .iter()
+            .map(|(addr, kind, bytes, _)| MemoryEvent {
+                addr: addr.clone(),
+                kind: kind.clone(),
+                bytes: bytes.upgrade().map(|bytes| bytes.get()).unwrap_or(0),
+            })
+            .collect()
+    }
+
+    /// The gauges whose bytes have changed since they were last reported by this method.
+    ///
+    /// Dropped gauges are reported once as holding zero bytes, if they held any when last reported.
+    pub fn changes(&mut self) -> Vec<MemoryEvent> {
+        let mut changes = Vec::new();
+        for (addr, kind, bytes, logged) in self.entries.iter_mut() {
+            let current = bytes.upgrade().map(|bytes| bytes.get()).unwrap_or(0);
+            if current != *logged {
+                changes.push(MemoryEvent { addr: addr.clone(), kind: kind.clone(), bytes: current });
+                *logged = current;
+            }
+        }
+        self.entries.retain(|entry| entry.2.upgrade().is_some());
+        changes
+    }
+}
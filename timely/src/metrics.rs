@@ -0,0 +1,375 @@
+//! Operational metrics, served in the Prometheus text format.
+//!
+//! A `Metrics` aggregates timely and communication log events into counters and histograms,
+//! and serves them over HTTP for scraping by Prometheus. It is shared by all workers and
+//! communication threads of a process, which attach it to their loggers alongside any other
+//! consumers of the same events.
+//!
+//! The metrics are
+//!
+//! * `timely_operator_records_in_total` and `timely_operator_records_out_total`, counting the
+//!   records each operator receives and sends on its channels,
+//! * `timely_operator_schedule_seconds`, a histogram of the durations for which each operator
+//!   is scheduled, which for scopes include the durations of their operators,
+//! * `timely_communication_bytes_total` and `timely_communication_messages_total`, counting the
+//!   bytes and messages sent to and received from each other process, and
+//! * `timely_frontier_lag_seconds`, reporting lags supplied through `set_frontier_lag`.
+//!
+//! The metrics of an operator are removed when it shuts down, and those of all operators of a
+//! dataflow when the dataflow completes or is dropped.
+//!
+//! When the `TIMELY_METRICS_ADDR` environment variable is set, `execute` serves the metrics of
+//! its workers and communication threads at that address, in addition to any other logging.
+//!
+//! # Examples
+//! ```
+//! use timely::dataflow::InputHandle;
+//! use timely::dataflow::operators::{Input, Map, Probe};
+//! use timely::metrics::Metrics;
+//!
+//! let metrics = Metrics::new();
+//! let metrics2 = metrics.clone();
+//! timely::execute(timely::Configuration::Thread, move |worker| {
+//!     metrics2.install(&mut worker.log_register());
+//!     let mut input = InputHandle::new();
+//!     let probe = worker.dataflow::<u64,_,_>(|scope| {
+//!         scope.input_from(&mut input)
+//!              .map(|x| x + 1)
+//!              .probe()
+//!     });
+//!     for x in 0 .. 10 { input.send(x); }
+//!     input.advance_to(1);
+//!     while probe.less_than(&1) { worker.step(); }
+//!
+//!     // The dataflow is still running, and so its operators are reported.
+//!     let text = metrics2.render();
+//!     assert!(text.contains("timely_operator_records_in_total{worker=\"0\",operator=\"Map\",id=\"2\",addr=\"0,2\"} 10"));
+//!     assert!(text.contains("timely_operator_records_out_total{worker=\"0\",operator=\"Map\",id=\"2\",addr=\"0,2\"} 10"));
+//! }).unwrap();
+//!
+//! // The dataflow has completed, and its operators are no longer reported.
+//! assert!(!metrics.render().contains("operator=\"Map\""));
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::communication::logging::{CommunicationEvent, CommunicationSetup};
+use crate::logging::{StartStop, TimelyEvent, WorkerIdentifier};
+use crate::logging_core::{Logger, Registry};
+
+/// Upper bounds, in seconds, of the buckets of scheduling histograms.
+const SCHEDULE_BUCKETS: [f64; 7] = [0.000_001, 0.000_01, 0.000_1, 0.001, 0.01, 0.1, 1.0];
+
+/// Metrics aggregated from log events, shared between threads.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Operators by worker and identifier.
+    operators: BTreeMap<(WorkerIdentifier, usize), Operator>,
+    /// Operator identifiers by worker and address.
+    addresses: HashMap<(WorkerIdentifier, Vec<usize>), usize>,
+    /// Source and target operator addresses of channels, by worker and channel identifier.
+    channels: HashMap<(WorkerIdentifier, usize), (Vec<usize>, Vec<usize>)>,
+    /// Traffic by process and remote process.
+    peers: BTreeMap<(usize, usize), Traffic>,
+    /// Frontier lags by worker and name.
+    lags: BTreeMap<(WorkerIdentifier, String), Duration>,
+}
+
+struct Operator {
+    name: String,
+    addr: Vec<usize>,
+    records_in: u64,
+    records_out: u64,
+    // Log time at which the operator was last scheduled, if it is running.
+    started: Option<Duration>,
+    // Counts of schedulings in each bucket, with a final bucket for longer durations.
+    buckets: [u64; 8],
+    scheduled: Duration,
+}
+
+#[derive(Default)]
+struct Traffic {
+    bytes_sent: u64,
+    bytes_received: u64,
+    messages_sent: u64,
+    messages_received: u64,
+}
+
+impl Metrics {
+
+    /// Creates metrics with no recorded events.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Attaches these metrics to the `"timely"` log of `registry`.
+    ///
+    /// Any existing binding is kept, and sees the same events as before.
+    pub fn install(&self, registry: &mut Registry<WorkerIdentifier>) {
+        let metrics = self.clone();
+        registry.attach::<TimelyEvent,_>("timely", move |_time, data| {
+            let mut state = metrics.state.lock().expect("metrics lock poisoned");
+            for (time, worker, event) in data.iter() {
+                state.observe_timely(*time, *worker, event);
+            }
+        });
+    }
+
+    /// A logger for a communication thread that records into these metrics.
+    ///
+    /// This is intended for the `log_fn` of a `Configuration::Cluster`.
+    pub fn communication_logger(&self, setup: CommunicationSetup) -> Logger<CommunicationEvent, CommunicationSetup> {
+        Logger::new(Instant::now(), setup, self.communication_action())
+    }
+
+    /// Attaches these metrics to the logger of a communication thread, or supplies one.
+    ///
+    /// This is intended for a `log_fn` that also has other consumers of communication events.
+    pub fn attach_communication(&self, logger: &mut Option<Logger<CommunicationEvent, CommunicationSetup>>, setup: CommunicationSetup) {
+        match logger {
+            Some(logger) => logger.attach(self.communication_action()),
+            None => *logger = Some(self.communication_logger(setup)),
+        }
+    }
+
+    /// An action on batches of communication events that records into these metrics.
+    fn communication_action(&self) -> impl FnMut(&Duration, &mut Vec<(Duration, CommunicationSetup, CommunicationEvent)>) {
+        let metrics = self.clone();
+        move |_time, data| {
+            let mut state = metrics.state.lock().expect("metrics lock poisoned");
+            for (_time, setup, event) in data.iter() {
+                state.observe_communication(setup, event);
+            }
+        }
+    }
+
+    /// Records the lag of a frontier observed by `worker`, reported under `name`.
+    ///
+    /// Timestamps need not be times, so lags are measured by the caller, for example as the
+    /// difference between the current time and the frontier of a probe.
+    pub fn set_frontier_lag(&self, worker: WorkerIdentifier, name: &str, lag: Duration) {
+        let mut state = self.state.lock().expect("metrics lock poisoned");
+        state.lags.insert((worker, name.to_owned()), lag);
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        self.state.lock().expect("metrics lock poisoned").render()
+    }
+
+    /// Serves the metrics over HTTP at `addr`, from a new thread.
+    ///
+    /// Every request is answered with the rendered metrics. The result is the address bound,
+    /// which reveals the port chosen if `addr` has port zero.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    /// use timely::metrics::Metrics;
+    ///
+    /// let metrics = Metrics::new();
+    /// metrics.set_frontier_lag(0, "output", std::time::Duration::from_millis(1500));
+    /// let addr = metrics.serve("127.0.0.1:0").unwrap();
+    ///
+    /// let mut stream = TcpStream::connect(addr).unwrap();
+    /// stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    ///
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    /// assert!(response.contains("timely_frontier_lag_seconds{worker=\"0\",name=\"output\"} 1.5"));
+    /// ```
+    pub fn serve<A: ToSocketAddrs>(&self, addr: A) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let local = listener.local_addr()?;
+        let metrics = self.clone();
+        ::std::thread::Builder::new()
+            .name("timely:metrics".to_owned())
+            .spawn(move || {
+                // Failed connections and requests should not stop the service.
+                for stream in listener.incoming().flatten() {
+                    let _ = metrics.respond(stream);
+                }
+            })?;
+        Ok(local)
+    }
+
+    /// Reads a request from `stream` and writes the rendered metrics.
+    fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !request.ends_with(b"\r\n\r\n") && request.len() < 16 * 1024 {
+            let read = stream.read(&mut buffer)?;
+            if read == 0 { break; }
+            request.extend_from_slice(&buffer[.. read]);
+        }
+        let body = self.render();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body,
+        )?;
+        stream.flush()
+    }
+}
+
+impl State {
+
+    fn observe_timely(&mut self, time: Duration, worker: WorkerIdentifier, event: &TimelyEvent) {
+        match event {
+            TimelyEvent::Operates(operates) => {
+                self.addresses.insert((worker, operates.addr.clone()), operates.id);
+                self.operators.insert((worker, operates.id), Operator {
+                    name: operates.name.clone(),
+                    addr: operates.addr.clone(),
+                    records_in: 0,
+                    records_out: 0,
+                    started: None,
+                    buckets: [0; 8],
+                    scheduled: Duration::default(),
+                });
+            },
+            TimelyEvent::Channels(channels) => {
+                // Channels are created before their target operators, so are resolved by address.
+                // Child zero of a scope is the scope itself.
+                let address = |node: usize| {
+                    let mut addr = channels.scope_addr.clone();
+                    if node != 0 { addr.push(node); }
+                    addr
+                };
+                let endpoints = (address(channels.source.0), address(channels.target.0));
+                self.channels.insert((worker, channels.id), endpoints);
+            },
+            TimelyEvent::Messages(messages) => {
+                if let Some((source, target)) = self.channels.get(&(worker, messages.channel)) {
+                    let addr = if messages.is_send { source } else { target };
+                    if let Some(id) = self.addresses.get(&(worker, addr.clone())) {
+                        if let Some(operator) = self.operators.get_mut(&(worker, *id)) {
+                            if messages.is_send { operator.records_out += messages.length as u64; }
+                            else { operator.records_in += messages.length as u64; }
+                        }
+                    }
+                }
+            },
+            TimelyEvent::Schedule(schedule) => {
+                if let Some(operator) = self.operators.get_mut(&(worker, schedule.id)) {
+                    match schedule.start_stop {
+                        StartStop::Start => { operator.started = Some(time); },
+                        StartStop::Stop => {
+                            if let Some(started) = operator.started.take() {
+                                let elapsed = time.checked_sub(started).unwrap_or_default();
+                                let bucket = SCHEDULE_BUCKETS.iter().position(|&bound| seconds(elapsed) <= bound).unwrap_or(SCHEDULE_BUCKETS.len());
+                                operator.buckets[bucket] += 1;
+                                operator.scheduled += elapsed;
+                            }
+                        },
+                    }
+                }
+            },
+            TimelyEvent::Shutdown(shutdown) => {
+                // The operators and channels of a scope, including a dataflow, shut down with it.
+                if let Some(operator) = self.operators.get(&(worker, shutdown.id)) {
+                    let addr = operator.addr.clone();
+                    self.operators.retain(|&(other, _), operator| other != worker || !operator.addr.starts_with(&addr));
+                    self.addresses.retain(|(other, address), _| *other != worker || !address.starts_with(&addr));
+                    self.channels.retain(|(other, _), (source, target)| *other != worker || !(source.starts_with(&addr) || target.starts_with(&addr)));
+                }
+            },
+            _ => { },
+        }
+    }
+
+    fn observe_communication(&mut self, setup: &CommunicationSetup, event: &CommunicationEvent) {
+        if let (CommunicationEvent::Message(message), Some(remote)) = (event, setup.remote) {
+            let traffic = self.peers.entry((setup.process, remote)).or_default();
+            if message.is_send {
+                traffic.bytes_sent += message.header.length as u64;
+                traffic.messages_sent += 1;
+            }
+            else {
+                traffic.bytes_received += message.header.length as u64;
+                traffic.messages_received += 1;
+            }
+        }
+    }
+
+    fn render(&self) -> String {
+
+        let mut text = String::new();
+
+        let labels = |worker: &WorkerIdentifier, id: &usize, operator: &Operator| {
+            let addr = operator.addr.iter().map(|index| index.to_string()).collect::<Vec<_>>().join(",");
+            format!("worker=\"{}\",operator=\"{}\",id=\"{}\",addr=\"{}\"", worker, escape(&operator.name), id, addr)
+        };
+
+        header(&mut text, "timely_operator_records_in_total", "counter", "Records received by an operator.");
+        for ((worker, id), operator) in self.operators.iter() {
+            writeln!(text, "timely_operator_records_in_total{{{}}} {}", labels(worker, id, operator), operator.records_in).unwrap();
+        }
+        header(&mut text, "timely_operator_records_out_total", "counter", "Records sent by an operator.");
+        for ((worker, id), operator) in self.operators.iter() {
+            writeln!(text, "timely_operator_records_out_total{{{}}} {}", labels(worker, id, operator), operator.records_out).unwrap();
+        }
+
+        header(&mut text, "timely_operator_schedule_seconds", "histogram", "Durations for which an operator is scheduled.");
+        for ((worker, id), operator) in self.operators.iter() {
+            let labels = labels(worker, id, operator);
+            let mut cumulative = 0;
+            for (bound, count) in SCHEDULE_BUCKETS.iter().zip(operator.buckets.iter()) {
+                cumulative += count;
+                writeln!(text, "timely_operator_schedule_seconds_bucket{{{},le=\"{}\"}} {}", labels, bound, cumulative).unwrap();
+            }
+            cumulative += operator.buckets[SCHEDULE_BUCKETS.len()];
+            writeln!(text, "timely_operator_schedule_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, cumulative).unwrap();
+            writeln!(text, "timely_operator_schedule_seconds_sum{{{}}} {}", labels, seconds(operator.scheduled)).unwrap();
+            writeln!(text, "timely_operator_schedule_seconds_count{{{}}} {}", labels, cumulative).unwrap();
+        }
+
+        header(&mut text, "timely_communication_bytes_total", "counter", "Bytes sent to or received from another process.");
+        for ((process, remote), traffic) in self.peers.iter() {
+            writeln!(text, "timely_communication_bytes_total{{process=\"{}\",remote=\"{}\",direction=\"send\"}} {}", process, remote, traffic.bytes_sent).unwrap();
+            writeln!(text, "timely_communication_bytes_total{{process=\"{}\",remote=\"{}\",direction=\"recv\"}} {}", process, remote, traffic.bytes_received).unwrap();
+        }
+        header(&mut text, "timely_communication_messages_total", "counter", "Messages sent to or received from another process.");
+        for ((process, remote), traffic) in self.peers.iter() {
+            writeln!(text, "timely_communication_messages_total{{process=\"{}\",remote=\"{}\",direction=\"send\"}} {}", process, remote, traffic.messages_sent).unwrap();
+            writeln!(text, "timely_communication_messages_total{{process=\"{}\",remote=\"{}\",direction=\"recv\"}} {}", process, remote, traffic.messages_received).unwrap();
+        }
+
+        header(&mut text, "timely_frontier_lag_seconds", "gauge", "Lag of a frontier, as reported by the application.");
+        for ((worker, name), lag) in self.lags.iter() {
+            writeln!(text, "timely_frontier_lag_seconds{{worker=\"{}\",name=\"{}\"}} {}", worker, escape(name), seconds(*lag)).unwrap();
+        }
+
+        text
+    }
+}
+
+/// Writes the help and type lines of a metric.
+fn header(text: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(text, "# HELP {} {}", name, help).unwrap();
+    writeln!(text, "# TYPE {} {}", name, kind).unwrap();
+}
+
+/// A duration in seconds.
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1_000_000_000.0
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
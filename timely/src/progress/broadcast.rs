@@ -2,7 +2,7 @@
 
 use crate::progress::{ChangeBatch, Timestamp};
 use crate::progress::Location;
-use crate::communication::{Message, Push, Pull};
+use crate::communication::{Message, Priority, Push, Pull};
 use crate::logging::TimelyLogger as Logger;
 
 /// A list of progress updates corresponding to `((child_scope, [in/out]_port, timestamp), delta)`
@@ -33,7 +33,7 @@ impl<T:Timestamp+Send> Progcaster<T> {
     pub fn new<A: crate::worker::AsWorker>(worker: &mut A, path: &Vec<usize>, mut logging: Option<Logger>) -> Progcaster<T> {
 
         let channel_identifier = worker.new_identifier();
-        let (pushers, puller) = worker.allocate(channel_identifier, &path[..]);
+        let (pushers, puller) = worker.allocate_with_priority(channel_identifier, &path[..], Priority::Control);
         logging.as_mut().map(|l| l.log(crate::logging::CommChannelsEvent {
             identifier: channel_identifier,
             kind: crate::logging::CommChannelKind::Progress,
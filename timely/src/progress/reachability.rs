@@ -664,6 +664,86 @@ impl<T:Timestamp> Tracker<T> {
             Port::Source(port) => self.per_operator[location.node].sources[port].is_global(time),
         }
     }
+
+    /// Outstanding pointstamps that hold back the frontier at `location`.
+    ///
+    /// Each result is the location, time, and count of a pointstamp that results in an element
+    /// of the frontier at `location` along some path to it. Pointstamps at sources are held
+    /// capabilities, and pointstamps at targets are messages in flight to them. Results reflect
+    /// only updates that have been propagated, and are sorted by location and time.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use timely::progress::{Location, Source, Target};
+    /// use timely::progress::frontier::Antichain;
+    /// use timely::progress::reachability::Builder;
+    ///
+    /// // Two sources feeding the one input of a third node.
+    /// let mut builder = Builder::<usize>::new();
+    /// builder.add_node(0, 0, 1, vec![]);
+    /// builder.add_node(1, 0, 1, vec![]);
+    /// builder.add_node(2, 1, 1, vec![vec![Antichain::from_elem(0)]]);
+    /// builder.add_edge(Source::new(0, 0), Target::new(2, 0));
+    /// builder.add_edge(Source::new(1, 0), Target::new(2, 0));
+    ///
+    /// let (mut tracker, _) = builder.build();
+    /// tracker.update_source(Source::new(0, 0), 3, 1);
+    /// tracker.update_source(Source::new(1, 0), 3, 1);
+    /// tracker.update_source(Source::new(1, 0), 5, 1);
+    /// tracker.propagate_all();
+    ///
+    /// // Both sources hold back the input, but only at their earliest times.
+    /// assert_eq!(tracker.blockers(Location::new_target(2, 0)), vec![
+    ///     (Location::new_source(0, 0), 3, 1),
+    ///     (Location::new_source(1, 0), 3, 1),
+    /// ]);
+    /// ```
+    pub fn blockers(&self, location: Location) -> Vec<(Location, T, i64)> {
+
+        let frontier = self.port_information(location).implications.frontier();
+        let mut blockers = Vec::new();
+        for (origin, summaries) in summarize_to::<T>(&self.nodes, &self.edges, location) {
+            let pointstamps = &self.port_information(origin).pointstamps;
+            for time in pointstamps.frontier().iter() {
+                let blocks =
+                summaries
+                    .elements()
+                    .iter()
+                    .flat_map(|summary| summary.results_in(time))
+                    .any(|result| frontier.iter().any(|element| element == &result));
+                if blocks {
+                    blockers.push((origin, time.clone(), pointstamps.count_for(time)));
+                }
+            }
+        }
+        blockers.sort();
+        blockers
+    }
+
+    /// Outstanding pointstamps at all locations, sorted by location and time.
+    pub fn pointstamps(&self) -> Vec<(Location, T, i64)> {
+        let mut pointstamps = Vec::new();
+        for (node, operator) in self.per_operator.iter().enumerate() {
+            let targets = operator.targets.iter().enumerate().map(|(port, info)| (Location::new_target(node, port), info));
+            let sources = operator.sources.iter().enumerate().map(|(port, info)| (Location::new_source(node, port), info));
+            for (location, info) in targets.chain(sources) {
+                for time in info.pointstamps.frontier().iter() {
+                    pointstamps.push((location, time.clone(), info.pointstamps.count_for(time)));
+                }
+            }
+        }
+        pointstamps.sort();
+        pointstamps
+    }
+
+    /// Progress information for the port at `location`.
+    fn port_information(&self, location: Location) -> &PortInformation<T> {
+        match location.port {
+            Port::Target(port) => &self.per_operator[location.node].targets[port],
+            Port::Source(port) => &self.per_operator[location.node].sources[port],
+        }
+    }
 }
 
 /// Determines summaries from locations to scope outputs.
@@ -756,3 +836,59 @@ fn summarize_outputs<T: Timestamp>(
 
     results
 }
+
+/// Determines summaries from each location to `location`.
+///
+/// The result includes `location` itself, with the default summary, and omits locations
+/// with no paths to `location`.
+fn summarize_to<T: Timestamp>(
+    nodes: &[Vec<Vec<Antichain<T::Summary>>>],
+    edges: &[Vec<Vec<Target>>],
+    location: Location,
+    ) -> HashMap<Location, Antichain<T::Summary>>
+{
+    // A reverse edge map, to allow us to walk back up the dataflow graph.
+    // A target may be fed by several sources, all of which we must walk back.
+    let mut reverse = HashMap::<Location, Vec<Location>>::new();
+    for (node, outputs) in edges.iter().enumerate() {
+        for (output, targets) in outputs.iter().enumerate() {
+            for target in targets.iter() {
+                reverse.entry(Location::from(*target)).or_default().push(Location::new_source(node, output));
+            }
+        }
+    }
+
+    let mut results = HashMap::new();
+    let mut worklist = VecDeque::<(Location, T::Summary)>::new();
+    results.insert(location, Antichain::from_elem(Default::default()));
+    worklist.push_back((location, Default::default()));
+
+    // Loop until we stop discovering novel reachability paths.
+    while let Some((location, summary)) = worklist.pop_front() {
+        match location.port {
+            // Crawl up the operator, to its inputs.
+            Port::Source(output_port) => {
+                for (input_port, summaries) in nodes[location.node].iter().enumerate() {
+                    let location = Location::new_target(location.node, input_port);
+                    for operator_summary in summaries[output_port].elements().iter() {
+                        if let Some(combined) = operator_summary.followed_by(&summary) {
+                            if results.entry(location).or_insert_with(Antichain::new).insert(combined.clone()) {
+                                worklist.push_back((location, combined));
+                            }
+                        }
+                    }
+                }
+            },
+            // Walk back the edges leading to the target.
+            Port::Target(_port) => {
+                for source in reverse.get(&location).into_iter().flatten() {
+                    if results.entry(*source).or_insert_with(Antichain::new).insert(summary.clone()) {
+                        worklist.push_back((*source, summary.clone()));
+                    }
+                }
+            },
+        }
+    }
+
+    results
+}
@@ -204,6 +204,39 @@ where
 }
 
 
+/// An outstanding pointstamp within a scope, described for diagnostics.
+///
+/// Pointstamps at sources (operator outputs and scope inputs) are held capabilities, and
+/// pointstamps at targets (operator inputs and scope outputs) are messages in flight to them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PointstampReport {
+    /// The address of the scope containing the pointstamp.
+    pub scope: Vec<usize>,
+    /// The location of the pointstamp within the scope.
+    ///
+    /// Node zero is the scope itself, whose inputs are sources and outputs are targets.
+    pub location: Location,
+    /// The name of the operator at the location.
+    pub name: String,
+    /// The timestamp of the pointstamp, formatted with `Debug`.
+    pub time: String,
+    /// The number of capabilities or messages at the timestamp.
+    pub count: i64,
+}
+
+impl ::std::fmt::Display for PointstampReport {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let mut addr = self.scope.clone();
+        if self.location.node != 0 { addr.push(self.location.node); }
+        match (self.location.node, self.location.port) {
+            (0, Port::Source(port)) => write!(f, "{} {:?} input {}: {} capabilities at {}", self.name, addr, port, self.count, self.time),
+            (0, Port::Target(port)) => write!(f, "{} {:?} output {}: {} messages at {}", self.name, addr, port, self.count, self.time),
+            (_, Port::Source(port)) => write!(f, "{} {:?} output {}: {} capabilities at {}", self.name, addr, port, self.count, self.time),
+            (_, Port::Target(port)) => write!(f, "{} {:?} input {}: {} messages at {}", self.name, addr, port, self.count, self.time),
+        }
+    }
+}
+
 /// A dataflow subgraph.
 ///
 /// The subgraph type contains the infrastructure required to describe the topology of and track
@@ -311,6 +344,34 @@ where
 
         incomplete || tracking
     }
+
+    fn report_pointstamps(&self, target: Option<(&[usize], usize)>, report: &mut Vec<PointstampReport>) {
+        let depth = self.path.len();
+        match target {
+            // The target is an input of a child of this scope.
+            Some((addr, port)) if addr.len() == depth + 1 && addr.starts_with(&self.path) => {
+                let location = Location::new_target(addr[depth], port);
+                for (location, time, count) in self.pointstamp_tracker.blockers(location) {
+                    report.push(self.describe(location, time, count));
+                }
+            },
+            // The target is within a child scope.
+            Some((addr, _)) if addr.len() > depth + 1 && addr.starts_with(&self.path) => {
+                if let Some(operator) = self.children.get(addr[depth]).and_then(|child| child.operator.as_ref()) {
+                    operator.report_pointstamps(target, report);
+                }
+            },
+            Some(_) => { },
+            None => {
+                for (location, time, count) in self.pointstamp_tracker.pointstamps() {
+                    report.push(self.describe(location, time, count));
+                }
+                for operator in self.children.iter().filter_map(|child| child.operator.as_ref()) {
+                    operator.report_pointstamps(None, report);
+                }
+            },
+        }
+    }
 }
 
 
@@ -319,6 +380,18 @@ where
     TOuter: Timestamp,
     TInner: Timestamp+Refines<TOuter>,
 {
+    /// Describes a pointstamp at `location` within this scope.
+    fn describe(&self, location: Location, time: TInner, count: i64) -> PointstampReport {
+        let name = if location.node == 0 { &self.name } else { &self.children[location.node].name };
+        PointstampReport {
+            scope: self.path.clone(),
+            location,
+            name: name.clone(),
+            time: format!("{:?}", time),
+            count,
+        }
+    }
+
     /// Schedules a child operator and collects progress statements.
     ///
     /// The return value indicates that the child task cannot yet shut down.
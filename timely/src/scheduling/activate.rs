@@ -2,8 +2,11 @@
 
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::sync::mpsc::{Sender, Receiver};
 use std::thread::Thread;
+use std::time::{Duration, Instant};
 
 /// Allocation-free activation tracker.
 pub struct Activations {
@@ -12,8 +15,12 @@ pub struct Activations {
     bounds: Vec<(usize, usize)>,
     slices: Vec<usize>,
     buffer: Vec<usize>,
+    // Inter-thread activations.
     tx: Sender<Vec<usize>>,
     rx: Receiver<Vec<usize>>,
+    // Delayed activations, by their deadline measured from `timer`.
+    timer: Instant,
+    queue: BinaryHeap<Reverse<(Duration, Vec<usize>)>>,
 }
 
 impl Activations {
@@ -35,13 +42,34 @@ impl Activations {
         self.slices.extend(path);
     }
 
+    /// Schedules a future activation for the task addressed by `path`.
+    ///
+    /// A delay of zero activates the task immediately.
+    pub fn activate_after(&mut self, path: &[usize], delay: Duration) {
+        if delay == Duration::new(0, 0) {
+            self.activate(path);
+        }
+        else {
+            let moment = self.timer.elapsed() + delay;
+            self.queue.push(Reverse((moment, path.to_vec())));
+        }
+    }
+
     /// Discards the current active set and presents the next active set.
     pub fn advance(&mut self) {
 
+        // Drain inter-thread activations.
         while let Ok(path) = self.rx.try_recv() {
             self.activate(&path)
         }
 
+        // Drain delayed activations whose deadlines have passed.
+        let now = self.timer.elapsed();
+        while self.queue.peek().map(|Reverse((moment, _))| moment <= &now) == Some(true) {
+            let Reverse((_moment, path)) = self.queue.pop().unwrap();
+            self.activate(&path);
+        }
+
         self.bounds.drain(.. self.clean);
 
         {   // Scoped, to allow borrow to drop.
@@ -97,6 +125,22 @@ impl Activations {
             });
     }
 
+    /// The time until the next delayed activation, if any.
+    ///
+    /// A worker with no pending activations may park for at most this long before it has
+    /// work to perform. If there are pending activations the result is zero.
+    pub fn empty_for(&self) -> Option<Duration> {
+        if !self.bounds.is_empty() {
+            Some(Duration::new(0, 0))
+        }
+        else {
+            self.queue.peek().map(|Reverse((moment, _))| {
+                let elapsed = self.timer.elapsed();
+                if moment < &elapsed { Duration::new(0, 0) } else { *moment - elapsed }
+            })
+        }
+    }
+
     /// Constructs a thread-safe `SyncActivations` handle to this activator.
     pub fn sync(&self) -> SyncActivations {
         SyncActivations {
@@ -116,6 +160,8 @@ impl Default for Activations {
             buffer: Vec::new(),
             tx,
             rx,
+            timer: Instant::now(),
+            queue: BinaryHeap::new(),
         }
     }
 }
@@ -170,6 +216,49 @@ impl Activator {
             .borrow_mut()
             .activate(&self.path[..]);
     }
+
+    /// Activates the associated path once `delay` has elapsed.
+    ///
+    /// A worker parked with `step_or_park` wakes in time for the activation.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use timely::dataflow::operators::generic::operator::source;
+    /// use timely::scheduling::Scheduler;
+    ///
+    /// timely::execute_directly(|worker| {
+    ///     let start = Instant::now();
+    ///     worker.dataflow::<u64,_,_>(|scope| {
+    ///         source(scope, "Ticker", |cap, info| {
+    ///             let activator = scope.activator_for(&info.address[..]);
+    ///             let mut cap = Some(cap);
+    ///             let mut ticks = 0;
+    ///             let mut next = Instant::now();
+    ///             move |output| {
+    ///                 // The operator may be scheduled for other reasons.
+    ///                 if Instant::now() < next { return; }
+    ///                 if let Some(cap) = cap.as_mut() {
+    ///                     output.session(&cap).give(ticks);
+    ///                     ticks += 1;
+    ///                     cap.downgrade(&ticks);
+    ///                     next = Instant::now() + Duration::from_millis(10);
+    ///                     activator.activate_after(Duration::from_millis(10));
+    ///                 }
+    ///                 if ticks == 5 { cap = None; }
+    ///             }
+    ///         });
+    ///     });
+    ///     // Parks between ticks, rather than spinning.
+    ///     while worker.step_or_park(None) { }
+    ///     assert!(start.elapsed() >= Duration::from_millis(40));
+    /// });
+    /// ```
+    pub fn activate_after(&self, delay: Duration) {
+        self.queue
+            .borrow_mut()
+            .activate_after(&self.path[..], delay);
+    }
 }
 
 /// A thread-safe version of `Activator`.
@@ -193,6 +282,15 @@ impl SyncActivator {
     }
 }
 
+// Allows a `SyncActivator` to serve as the waker of a future, such that its operator is
+// activated when the future can make progress.
+impl futures_util::task::ArcWake for SyncActivator {
+    fn wake_by_ref(arc_self: &std::sync::Arc<Self>) {
+        // An error indicates the worker has gone, and there is no one left to wake.
+        let _ = arc_self.activate();
+    }
+}
+
 /// The error returned when activation fails across thread boundaries because
 /// the receiving end has hung up.
 #[derive(Debug)]
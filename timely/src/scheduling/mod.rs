@@ -5,7 +5,7 @@ use std::cell::RefCell;
 
 pub mod activate;
 
-pub use self::activate::{Activations, Activator, ActivateOnDrop, SyncActivator};
+pub use self::activate::{Activations, Activator, ActivateOnDrop, SyncActivator, SyncActivationError};
 
 /// A type that can be scheduled.
 pub trait Schedule {
@@ -18,6 +18,12 @@ pub trait Schedule {
     /// The return value indicates whether `self` has outstanding
     /// work and would be upset if the computation terminated.
     fn schedule(&mut self) -> bool;
+    /// Reports outstanding pointstamps within the scopes that `self` hosts.
+    ///
+    /// If `target` is supplied, as the address of an operator and one of its input ports, only
+    /// the pointstamps in the operator's scope that hold back the frontier of that input are
+    /// reported. Operators that host no scopes report nothing.
+    fn report_pointstamps(&self, _target: Option<(&[usize], usize)>, _report: &mut Vec<crate::progress::subgraph::PointstampReport>) { }
 }
 
 /// Methods for types which schedule fibers.
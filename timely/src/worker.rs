@@ -7,7 +7,7 @@ use std::time::{Instant, Duration};
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
 
-use crate::communication::{Allocate, Data, Push, Pull};
+use crate::communication::{Allocate, CancellationToken, Data, Priority, Push, Pull, WorkerPanic};
 use crate::communication::allocator::thread::{ThreadPusher, ThreadPuller};
 use crate::scheduling::{Schedule, Scheduler, Activations};
 use crate::progress::timestamp::{Refines};
@@ -15,6 +15,7 @@ use crate::progress::SubgraphBuilder;
 use crate::progress::operate::Operate;
 use crate::dataflow::scopes::Child;
 use crate::logging::TimelyLogger;
+use crate::memory::MemoryRegistry;
 
 /// Methods provided by the root Worker.
 ///
@@ -36,6 +37,20 @@ pub trait AsWorker : Scheduler {
     /// Most commonly, this would be the address of the *target* of the
     /// channel.
     fn allocate<T: Data>(&mut self, identifier: usize, address: &[usize]) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>);
+    /// Allocates a new channel with flow control, from a supplied identifier and address.
+    ///
+    /// If `budget` is set, the pushers report through `over_budget()` when their target has
+    /// at least `budget` messages outstanding. By default, the budget is ignored.
+    fn allocate_with_budget<T: Data>(&mut self, identifier: usize, address: &[usize], _budget: Option<usize>) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate(identifier, address)
+    }
+    /// Allocates a new channel whose traffic has `priority` over the network.
+    ///
+    /// Traffic of `Priority::Control`, such as progress updates, is sent ahead of `Priority::Data`
+    /// traffic between processes. By default, the priority is ignored.
+    fn allocate_with_priority<T: Data>(&mut self, identifier: usize, address: &[usize], _priority: Priority) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate(identifier, address)
+    }
     /// Constructs a pipeline channel from the worker to itself.
     ///
     /// By default this method uses the native channel allocation mechanism, but the expectation is
@@ -48,6 +63,10 @@ pub trait AsWorker : Scheduler {
     fn log_register(&self) -> ::std::cell::RefMut<crate::logging_core::Registry<crate::logging::WorkerIdentifier>>;
     /// Provides access to the timely logging stream.
     fn logging(&self) -> Option<crate::logging::TimelyLogger> { self.log_register().get("timely") }
+    /// Provides access to the worker's memory accounting.
+    ///
+    /// By default this method provides a new registry, whose gauges are not reported.
+    fn memory(&self) -> Rc<RefCell<MemoryRegistry>> { Default::default() }
 }
 
 /// A `Worker` is the entry point to a timely dataflow computation. It wraps a `Allocate`,
@@ -61,6 +80,8 @@ pub struct Worker<A: Allocate> {
     dataflows: Rc<RefCell<HashMap<usize, Wrapper>>>,
     dataflow_counter: Rc<RefCell<usize>>,
     logging: Rc<RefCell<crate::logging_core::Registry<crate::logging::WorkerIdentifier>>>,
+    memory: Rc<RefCell<MemoryRegistry>>,
+    cancellation: CancellationToken,
 
     activations: Rc<RefCell<Activations>>,
     active_dataflows: Vec<usize>,
@@ -68,17 +89,30 @@ pub struct Worker<A: Allocate> {
     // Temporary storage for channel identifiers during dataflow construction.
     // These are then associated with a dataflow once constructed.
     temp_channel_ids: Rc<RefCell<Vec<usize>>>,
+
+    // Announcements of retired dataflows, to and from other workers.
+    teardown: Rc<RefCell<Teardown>>,
 }
 
 impl<A: Allocate> AsWorker for Worker<A> {
     fn index(&self) -> usize { self.allocator.borrow().index() }
     fn peers(&self) -> usize { self.allocator.borrow().peers() }
     fn allocate<D: Data>(&mut self, identifier: usize, address: &[usize]) -> (Vec<Box<dyn Push<Message<D>>>>, Box<dyn Pull<Message<D>>>) {
+        self.allocate_with_budget(identifier, address, None)
+    }
+    fn allocate_with_budget<D: Data>(&mut self, identifier: usize, address: &[usize], budget: Option<usize>) -> (Vec<Box<dyn Push<Message<D>>>>, Box<dyn Pull<Message<D>>>) {
         if address.len() == 0 { panic!("Unacceptable address: Length zero"); }
         let mut paths = self.paths.borrow_mut();
         paths.insert(identifier, address.to_vec());
         self.temp_channel_ids.borrow_mut().push(identifier);
-        self.allocator.borrow_mut().allocate(identifier)
+        self.allocator.borrow_mut().allocate_with_budget(identifier, budget)
+    }
+    fn allocate_with_priority<D: Data>(&mut self, identifier: usize, address: &[usize], priority: Priority) -> (Vec<Box<dyn Push<Message<D>>>>, Box<dyn Pull<Message<D>>>) {
+        if address.is_empty() { panic!("Unacceptable address: Length zero"); }
+        let mut paths = self.paths.borrow_mut();
+        paths.insert(identifier, address.to_vec());
+        self.temp_channel_ids.borrow_mut().push(identifier);
+        self.allocator.borrow_mut().allocate_with_priority(identifier, priority)
     }
     fn pipeline<T: 'static>(&mut self, identifier: usize, address: &[usize]) -> (ThreadPusher<Message<T>>, ThreadPuller<Message<T>>) {
         if address.len() == 0 { panic!("Unacceptable address: Length zero"); }
@@ -92,6 +126,7 @@ impl<A: Allocate> AsWorker for Worker<A> {
     fn log_register(&self) -> RefMut<crate::logging_core::Registry<crate::logging::WorkerIdentifier>> {
         self.log_register()
     }
+    fn memory(&self) -> Rc<RefCell<MemoryRegistry>> { self.memory.clone() }
 }
 
 impl<A: Allocate> Scheduler for Worker<A> {
@@ -102,9 +137,10 @@ impl<A: Allocate> Scheduler for Worker<A> {
 
 impl<A: Allocate> Worker<A> {
     /// Allocates a new `Worker` bound to a channel allocator.
-    pub fn new(c: A) -> Worker<A> {
+    pub fn new(mut c: A) -> Worker<A> {
         let now = Instant::now();
         let index = c.index();
+        let teardown = Teardown::new(&mut c);
         Worker {
             timer: now.clone(),
             paths:  Default::default(),
@@ -113,12 +149,60 @@ impl<A: Allocate> Worker<A> {
             dataflows: Default::default(),
             dataflow_counter:  Default::default(),
             logging: Rc::new(RefCell::new(crate::logging_core::Registry::new(now, index))),
+            memory: Default::default(),
+            cancellation: Default::default(),
             activations: Default::default(),
             active_dataflows: Default::default(),
             temp_channel_ids:  Default::default(),
+            teardown: Rc::new(RefCell::new(teardown)),
         }
     }
 
+    /// Associates the worker with a token through which its computation can be cancelled.
+    ///
+    /// The `execute` methods supply the token that `WorkerGuards::shutdown` cancels.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// The token through which the worker's computation can be cancelled.
+    ///
+    /// Worker logic that does not otherwise complete, for example because it introduces input
+    /// until some condition holds, should observe the token and return once it is cancelled.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::InputHandle;
+    /// use timely::dataflow::operators::{Input, Exchange, Probe};
+    ///
+    /// let guards = timely::execute(timely::Configuration::Process(2), |worker| {
+    ///
+    ///     let mut input = InputHandle::new();
+    ///     let probe = worker.dataflow(|scope| scope.input_from(&mut input).exchange(|x: &u64| *x).probe());
+    ///
+    ///     // introduce input until the computation is cancelled.
+    ///     let mut round = 0;
+    ///     while !worker.cancellation().is_cancelled() {
+    ///         input.send(round);
+    ///         round += 1;
+    ///         input.advance_to(round);
+    ///         while probe.less_than(input.time()) && !worker.cancellation().is_cancelled() {
+    ///             worker.step();
+    ///         }
+    ///     }
+    ///     round
+    /// }).unwrap();
+    ///
+    /// std::thread::sleep(std::time::Duration::from_millis(100));
+    /// for result in guards.shutdown() {
+    ///     assert!(result.unwrap() > 0);
+    /// }
+    /// ```
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
     /// Performs one step of the computation.
     ///
     /// A step gives each dataflow operator a chance to run, and is the
@@ -153,6 +237,13 @@ impl<A: Allocate> Worker<A> {
     /// there is work to perform or until this timeout expires. A value of
     /// `None` allows the worker to park indefinitely, whereas a value of
     /// `Some(Duration::new(0, 0))` will return without parking the thread.
+    /// The worker parks for no longer than until the next delayed activation,
+    /// as scheduled by `Activator::activate_after`.
+    ///
+    /// Once the worker's `cancellation` token is cancelled, the method drops all
+    /// installed dataflows, discarding any data they hold, and returns false.
+    /// If instead the token records that a worker has panicked, or a worker of another
+    /// process announces a panic, the method panics, naming the worker that failed.
     ///
     /// # Examples
     ///
@@ -173,9 +264,24 @@ impl<A: Allocate> Worker<A> {
     /// ```
     pub fn step_or_park(&mut self, duration: Option<Duration>) -> bool {
 
+        if let Some(failure) = self.cancellation.failure() {
+            panic!("aborted after {}", failure);
+        }
+
+        if self.cancellation.is_cancelled() {
+            // All workers of the process are cancelled, so none need be told.
+            self.dataflows.borrow_mut().clear();
+            self.paths.borrow_mut().clear();
+            return false;
+        }
+
         {   // Process channel events. Activate responders.
             let mut allocator = self.allocator.borrow_mut();
             allocator.receive();
+            if let Some(failure) = allocator.remote_failure() {
+                self.cancellation.fail(failure.clone());
+                panic!("aborted after {}", failure);
+            }
             let events = allocator.events().clone();
             let mut borrow = events.borrow_mut();
             let paths = self.paths.borrow();
@@ -194,21 +300,42 @@ impl<A: Allocate> Worker<A> {
             }
         }
 
+        // Drop dataflows that other workers have dropped.
+        let dropped = self.teardown.borrow_mut().receive();
+        for index in dropped {
+            if self.dataflows.borrow().contains_key(&index) {
+                self.drop_dataflow(index);
+            }
+            // A dataflow that has already completed here has no channels left to release.
+            else if index < self.next_dataflow_index() {
+                self.retire(index, Vec::new());
+            }
+        }
+        self.release_retired();
+
         // Organize activations.
         self.activations
             .borrow_mut()
             .advance();
 
+        // Park no longer than until the next delayed activation.
+        let empty_for = self.activations.borrow().empty_for();
+        let delay = match (duration, empty_for) {
+            (Some(x), Some(y)) => Some(::std::cmp::min(x, y)),
+            (x, None) => x,
+            (None, y) => y,
+        };
+
         // Consider parking only if we have no pending events, some dataflows, and a non-zero duration.
-        if self.activations.borrow().is_empty() && !self.dataflows.borrow().is_empty() && duration != Some(Duration::new(0,0)) {
+        if self.activations.borrow().is_empty() && !self.dataflows.borrow().is_empty() && delay != Some(Duration::new(0,0)) {
 
             // Log parking and flush log.
-            self.logging().as_mut().map(|l| l.log(crate::logging::ParkEvent::park(duration)));
+            self.logging().as_mut().map(|l| l.log(crate::logging::ParkEvent::park(delay)));
             self.logging.borrow_mut().flush();
 
             self.allocator
                 .borrow()
-                .await_events(duration);
+                .await_events(delay);
 
             // Log return from unpark.
             self.logging().as_mut().map(|l| l.log(crate::logging::ParkEvent::unpark()));
@@ -220,6 +347,7 @@ impl<A: Allocate> Worker<A> {
                 .borrow_mut()
                 .for_extensions(&[], |index| active_dataflows.push(index));
 
+            let mut completed = Vec::new();
             let mut dataflows = self.dataflows.borrow_mut();
             for index in active_dataflows.drain(..) {
                 // Step dataflow if it exists, remove if not incomplete.
@@ -227,13 +355,27 @@ impl<A: Allocate> Worker<A> {
                     let incomplete = entry.get_mut().step();
                     if !incomplete {
                         let mut paths = self.paths.borrow_mut();
-                        for channel in entry.get_mut().channel_ids.drain(..) {
-                            paths.remove(&channel);
+                        let channels = entry.get_mut().channel_ids.drain(..).collect::<Vec<_>>();
+                        for channel in channels.iter() {
+                            paths.remove(channel);
                         }
                         entry.remove_entry();
+                        completed.push((index, channels));
                     }
                 }
             }
+            ::std::mem::drop(dataflows);
+            for (index, channels) in completed {
+                self.complete(index, channels);
+            }
+        }
+
+        // Log changes in memory usage.
+        self.account_queued_bytes();
+        if let Some(logger) = self.logging() {
+            for event in self.memory.borrow_mut().changes() {
+                logger.log(event);
+            }
         }
 
         // Clean up, indicate if dataflows remain.
@@ -297,6 +439,50 @@ impl<A: Allocate> Worker<A> {
     /// ```
     pub fn peers(&self) -> usize { self.allocator.borrow().peers() }
 
+    /// Indices of remote processes whose connections have been lost.
+    ///
+    /// Processes are only reported when the computation was configured to tolerate
+    /// failures (e.g. with the `--tolerate-failures` argument); otherwise the loss of a
+    /// process panics the communication threads. Dataflows that exchange data with the
+    /// workers of a lost process will not make further progress, and can be dropped
+    /// with `drop_dataflow`.
+    ///
+    /// # Examples
+    /// ```
+    /// timely::execute_from_args(::std::env::args(), |worker| {
+    ///
+    ///     use timely::dataflow::operators::{ToStream, Inspect, Probe};
+    ///
+    ///     let dataflow = worker.next_dataflow_index();
+    ///     let probe = worker.dataflow::<usize,_,_>(|scope| {
+    ///         (0 .. 10)
+    ///             .to_stream(scope)
+    ///             .inspect(|x| println!("{:?}", x))
+    ///             .probe()
+    ///     });
+    ///
+    ///     while probe.less_than(&0) {
+    ///         if !worker.lost_processes().is_empty() {
+    ///             worker.drop_dataflow(dataflow);
+    ///             break;
+    ///         }
+    ///         worker.step();
+    ///     }
+    /// });
+    /// ```
+    pub fn lost_processes(&self) -> Vec<usize> { self.allocator.borrow().lost_processes().to_vec() }
+
+    /// Records that the worker has panicked, and announces the first failure of the computation
+    /// to the workers of other processes.
+    pub(crate) fn fail(&mut self, payload: &(dyn Any+Send)) {
+        let index = self.index();
+        self.cancellation.fail(WorkerPanic::from_payload(index, payload));
+        if let Some(failure) = self.cancellation.failure() {
+            let mut allocator = self.allocator.borrow_mut();
+            allocator.announce_failure(&failure);
+        }
+    }
+
     /// A timer started at the initiation of the timely computation.
     ///
     /// # Examples
@@ -339,6 +525,25 @@ impl<A: Allocate> Worker<A> {
         self.logging.borrow_mut()
     }
 
+    /// Records the operators and channels of dataflows constructed after the call.
+    ///
+    /// The graph is assembled from the `"timely"` log, to which this method attaches an action
+    /// that updates the returned graph, keeping any existing binding. To compose the graph into
+    /// another logger action instead, use `DataflowGraph::observe`. See `DataflowGraph` for an
+    /// example.
+    pub fn dataflow_graph(&mut self) -> Rc<RefCell<crate::logging::DataflowGraph>> {
+        let graph = Rc::new(RefCell::new(crate::logging::DataflowGraph::new()));
+        let graph2 = graph.clone();
+        self.log_register()
+            .attach::<crate::logging::TimelyEvent,_>("timely", move |_time, data| {
+                let mut graph = graph2.borrow_mut();
+                for (_, _, event) in data.iter() {
+                    graph.observe(event);
+                }
+            });
+        graph
+    }
+
     /// Construct a new dataflow.
     ///
     /// # Examples
@@ -423,8 +628,7 @@ impl<A: Allocate> Worker<A> {
         operator.get_internal_summary();
         operator.set_external_summary();
 
-        let mut temp_channel_ids = self.temp_channel_ids.borrow_mut();
-        let channel_ids = temp_channel_ids.drain(..).collect::<Vec<_>>();
+        let channel_ids = self.temp_channel_ids.borrow_mut().drain(..).collect::<Vec<_>>();
 
         let wrapper = Wrapper {
             logging,
@@ -435,10 +639,203 @@ impl<A: Allocate> Worker<A> {
         };
         self.dataflows.borrow_mut().insert(dataflow_index, wrapper);
 
+        // Another worker may have dropped the dataflow before it was constructed here.
+        if self.teardown.borrow().is_dropped(dataflow_index) {
+            self.drop_dataflow(dataflow_index);
+        }
+
         result
 
     }
 
+    /// Drops an identified dataflow.
+    ///
+    /// This method removes the identified dataflow, which will no longer be scheduled.
+    /// The dataflow's operators and resources are dropped, and the paths of its channels
+    /// are retired.
+    ///
+    /// The drop is announced to the other workers, each of which drops the dataflow when it
+    /// next steps, or once it constructs the dataflow if it has not yet. It is safe for peers
+    /// to continue to send data to the dataflow; any data received on its channels after it
+    /// is dropped are discarded. Once every worker has dropped the dataflow, or had seen it
+    /// complete, its channels are released in the underlying allocator.
+    ///
+    /// # Examples
+    /// ```
+    /// timely::execute_from_args(::std::env::args(), |worker| {
+    ///
+    ///     use timely::dataflow::InputHandle;
+    ///     use timely::dataflow::operators::{Input, Exchange, Inspect};
+    ///
+    ///     let mut input = InputHandle::<usize, usize>::new();
+    ///
+    ///     let dataflow = worker.next_dataflow_index();
+    ///     worker.dataflow(|scope| {
+    ///         scope.input_from(&mut input)
+    ///              .exchange(|x| *x as u64)
+    ///              .inspect(|x| println!("{:?}", x));
+    ///     });
+    ///
+    ///     input.send(worker.index());
+    ///     worker.step();
+    ///
+    ///     assert_eq!(worker.installed_dataflows(), vec![dataflow]);
+    ///     worker.drop_dataflow(dataflow);
+    ///     assert!(worker.installed_dataflows().is_empty());
+    ///     assert!(!worker.step());
+    /// });
+    /// ```
+    pub fn drop_dataflow(&mut self, dataflow_identifier: usize) {
+        let removed = self.dataflows.borrow_mut().remove(&dataflow_identifier);
+        if let Some(mut entry) = removed {
+            // Garbage collect channel_id to path information.
+            let channels = entry.channel_ids.drain(..).collect::<Vec<_>>();
+            let mut paths = self.paths.borrow_mut();
+            for channel in channels.iter() {
+                paths.remove(channel);
+            }
+            ::std::mem::drop(paths);
+            ::std::mem::drop(entry);
+            self.retire(dataflow_identifier, channels);
+        }
+    }
+
+    // Releases the channels of a completed dataflow, which no worker will send to again,
+    // unless another worker has dropped it and awaits its retirement.
+    fn complete(&self, dataflow: usize, channels: Vec<usize>) {
+        if self.teardown.borrow().is_dropped(dataflow) {
+            self.retire(dataflow, channels);
+        }
+        else {
+            self.allocator.borrow_mut().retire_channels(&channels[..]);
+        }
+    }
+
+    // Announces that the worker has retired a dropped dataflow.
+    fn retire(&self, dataflow: usize, channels: Vec<usize>) {
+        let index = self.index();
+        self.teardown.borrow_mut().announce(index, dataflow, channels);
+        self.release_retired();
+    }
+
+    // Releases the channels of dataflows that all workers have retired.
+    fn release_retired(&self) {
+        let peers = self.peers();
+        let retired = self.teardown.borrow_mut().retired(peers);
+        for channels in retired {
+            self.allocator.borrow_mut().retire_channels(&channels[..]);
+        }
+    }
+
+    /// Returns the next index to be used for dataflow construction.
+    ///
+    /// This identifier will appear in the address of contained operators, and can
+    /// be used to drop the dataflow using `self.drop_dataflow()`.
+    pub fn next_dataflow_index(&self) -> usize {
+        *self.dataflow_counter.borrow()
+    }
+
+    /// List the current dataflow indices.
+    pub fn installed_dataflows(&self) -> Vec<usize> {
+        let mut indices = self.dataflows.borrow().keys().cloned().collect::<Vec<_>>();
+        indices.sort();
+        indices
+    }
+
+    /// Reports outstanding capabilities and in-flight messages in installed dataflows.
+    ///
+    /// With a `target`, the address of an operator and one of its input ports, only those that
+    /// hold back the frontier of the input are reported, from within the operator's scope. If
+    /// the frontier is held back by the inputs of an enclosing scope, the scope's own input can
+    /// be inspected in turn. Without a target, all outstanding pointstamps are reported.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::InputHandle;
+    /// use timely::dataflow::operators::{Input, Map, Probe};
+    ///
+    /// timely::execute_from_args(::std::env::args(), |worker| {
+    ///
+    ///     let mut input = InputHandle::new();
+    ///     let probe = worker.dataflow::<u64,_,_>(|scope| {
+    ///         scope.input_from(&mut input)
+    ///              .map(|x: u64| x + 1)
+    ///              .probe()
+    ///     });
+    ///
+    ///     input.send(0);
+    ///     for _ in 0 .. 10 { worker.step(); }
+    ///
+    ///     // The probe is stuck at time zero, as the input has not been advanced.
+    ///     assert!(probe.less_equal(&0));
+    ///     let map = vec![0, 2];
+    ///     let blockers = worker.report_pointstamps(Some((&map[..], 0)));
+    ///     assert_eq!(blockers.len(), 1);
+    ///     assert_eq!(blockers[0].name, "Input");
+    ///     assert_eq!(blockers[0].time, "0");
+    ///     for blocker in blockers { println!("{}", blocker); }
+    /// }).unwrap();
+    /// ```
+    pub fn report_pointstamps(&self, target: Option<(&[usize], usize)>) -> Vec<crate::progress::subgraph::PointstampReport> {
+        let mut report = Vec::new();
+        let dataflows = self.dataflows.borrow();
+        let mut indices = dataflows.keys().cloned().collect::<Vec<_>>();
+        indices.sort();
+        for index in indices {
+            if target.map(|(addr, _)| addr.first() == Some(&index)).unwrap_or(true) {
+                if let Some(operate) = dataflows[&index].operate.as_ref() {
+                    operate.report_pointstamps(target, &mut report);
+                }
+            }
+        }
+        report
+    }
+
+    /// Reports the bytes held on behalf of operators, largest first.
+    ///
+    /// The `memory` module describes which memory is accounted.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::InputHandle;
+    /// use timely::dataflow::operators::{Input, Map, Inspect};
+    /// use timely::logging::MemoryKind;
+    ///
+    /// timely::execute_from_args(::std::env::args(), |worker| {
+    ///
+    ///     let mut input = InputHandle::new();
+    ///     worker.dataflow::<u64,_,_>(|scope| {
+    ///         scope.input_from(&mut input)
+    ///              .map(|x: u64| x + 1)
+    ///              .inspect(|x| println!("seen: {:?}", x));
+    ///     });
+    ///
+    ///     // Records sent but not yet received are queued in the input's channel.
+    ///     for round in 0 .. 10 { input.send(round); }
+    ///     input.advance_to(1);
+    ///     let usage = worker.memory_usage();
+    ///     assert!(usage.iter().any(|event| if let MemoryKind::Channel(_) = event.kind { event.bytes == 80 } else { false }));
+    ///
+    ///     input.close();
+    ///     while worker.step() { }
+    ///     assert!(worker.memory_usage().iter().all(|event| if let MemoryKind::Channel(_) = event.kind { event.bytes == 0 } else { true }));
+    /// }).unwrap();
+    /// ```
+    pub fn memory_usage(&self) -> Vec<crate::logging::MemoryEvent> {
+        self.account_queued_bytes();
+        let mut usage = self.memory.borrow_mut().usage();
+        usage.sort_by_key(|event| ::std::cmp::Reverse(event.bytes));
+        usage
+    }
+
+    // Update the gauges of bytes queued between the worker and other processes.
+    fn account_queued_bytes(&self) {
+        let queued = self.allocator.borrow().queued_bytes();
+        if !queued.is_empty() {
+            self.memory.borrow_mut().set_queued_bytes(&queued);
+        }
+    }
+
     // Acquire a new distinct dataflow identifier.
     fn allocate_dataflow_index(&mut self) -> usize {
         *self.dataflow_counter.borrow_mut() += 1;
@@ -458,10 +855,109 @@ impl<A: Allocate> Clone for Worker<A> {
             dataflows: self.dataflows.clone(),
             dataflow_counter: self.dataflow_counter.clone(),
             logging: self.logging.clone(),
+            memory: self.memory.clone(),
+            cancellation: self.cancellation.clone(),
             activations: self.activations.clone(),
             active_dataflows: Vec::new(),
             temp_channel_ids: self.temp_channel_ids.clone(),
+            teardown: self.teardown.clone(),
+        }
+    }
+}
+
+/// The identifiers of the channels through which workers announce dropped dataflows.
+///
+/// Announcements are sent on a channel of each priority, so that they follow any data and any
+/// progress updates sent before them. Other channel identifiers count up from zero, and do not
+/// reach these, which lie below those the communication layer reserves.
+const TEARDOWN_CHANNELS: [(usize, Priority); 2] = [
+    (usize::MAX - 3, Priority::Control),
+    (usize::MAX - 2, Priority::Data),
+];
+
+/// The retirement of a dropped dataflow by a worker, as `(dataflow, worker)`.
+///
+/// A worker retires a dropped dataflow by dropping it too, or, if the dataflow had already
+/// completed there, by acknowledging the drop. Completed dataflows are otherwise not announced,
+/// as all workers see them complete.
+type Retirement = (usize, usize);
+
+/// The pushers to every worker, and the puller, of one teardown channel.
+type TeardownChannel = (Vec<Box<dyn Push<Message<Retirement>>>>, Box<dyn Pull<Message<Retirement>>>);
+
+/// Announcements of dropped dataflows, to and from other workers.
+struct Teardown {
+    channels: Vec<TeardownChannel>,
+    /// Dropped dataflows that some worker has retired, but not every worker, by identifier.
+    ///
+    /// Entries are removed once every worker has retired the dataflow, which requires this
+    /// worker to construct it, if it has not yet.
+    retiring: HashMap<usize, Retiring>,
+}
+
+#[derive(Default)]
+struct Retiring {
+    /// The channels of the dataflow, once this worker has retired it.
+    channels: Option<Vec<usize>>,
+    /// The number of announcements received on each teardown channel.
+    announced: [usize; 2],
+}
+
+impl Teardown {
+    fn new<A: Allocate>(allocator: &mut A) -> Self {
+        let channels =
+        TEARDOWN_CHANNELS
+            .iter()
+            .map(|&(identifier, priority)| allocator.allocate_with_priority(identifier, priority))
+            .collect();
+        Teardown { channels, retiring: HashMap::new() }
+    }
+
+    /// Announces to other workers that worker `index` has retired the dropped `dataflow`.
+    fn announce(&mut self, index: usize, dataflow: usize, channels: Vec<usize>) {
+        for (pushers, _) in self.channels.iter_mut() {
+            for (target, pusher) in pushers.iter_mut().enumerate() {
+                if target != index {
+                    pusher.push(&mut Some(Message::from_typed((dataflow, index))));
+                    pusher.done();
+                }
+            }
         }
+        self.retiring.entry(dataflow).or_default().channels = Some(channels);
+    }
+
+    /// Receives announcements, and returns the dataflows newly dropped by other workers.
+    fn receive(&mut self) -> Vec<usize> {
+        let mut dropped = Vec::new();
+        for (position, (_, puller)) in self.channels.iter_mut().enumerate() {
+            while let Some(message) = puller.pull() {
+                let (dataflow, _worker) = **message;
+                let retiring = self.retiring.entry(dataflow).or_insert_with(|| {
+                    dropped.push(dataflow);
+                    Retiring::default()
+                });
+                retiring.announced[position] += 1;
+            }
+        }
+        dropped
+    }
+
+    /// Indicates if `dataflow` has been dropped, and not yet retired by every worker.
+    fn is_dropped(&self, dataflow: usize) -> bool {
+        self.retiring.contains_key(&dataflow)
+    }
+
+    /// Removes and returns the channels of dataflows that all `peers` workers have retired.
+    fn retired(&mut self, peers: usize) -> Vec<Vec<usize>> {
+        let mut result = Vec::new();
+        self.retiring.retain(|_, retiring| {
+            if retiring.channels.is_some() && retiring.announced.iter().all(|&count| count + 1 == peers) {
+                result.push(retiring.channels.take().unwrap());
+                false
+            }
+            else { true }
+        });
+        result
     }
 }
 
@@ -0,0 +1,128 @@
+extern crate timely;
+
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use timely::Configuration;
+use timely::communication::{Allocate, ConnectionOptions};
+use timely::communication::logging::CommunicationEvent;
+use timely::dataflow::channels::pact::{Exchange, Pipeline};
+use timely::dataflow::operators::{ToStream, Inspect};
+use timely::dataflow::operators::generic::Operator;
+use timely::logging_core::Logger;
+use timely::scheduling::Scheduler;
+use timely::worker::Worker;
+
+/// Produces `0 .. 100_000` in batches, yielding whenever an exchange with a small budget
+/// reports a target over budget. Returns the number of records the worker received, and
+/// the number of times it yielded.
+fn produce<A: Allocate>(worker: &mut Worker<A>) -> (u64, usize) {
+    let received = Arc::new(AtomicUsize::new(0));
+    let yielded = Arc::new(AtomicUsize::new(0));
+    let received2 = received.clone();
+    let yielded2 = yielded.clone();
+    worker.dataflow::<u64,_,_>(|scope| {
+        Some(()).to_stream(scope)
+            .unary(Pipeline, "Producer", move |_cap, info| {
+                let activator = scope.activator_for(&info.address[..]);
+                let mut cap = None;
+                let mut next = 0u64;
+                move |input, output| {
+                    input.for_each(|time, _data| cap = Some(time.retain()));
+                    if let Some(time) = cap.as_ref() {
+                        while next < 100_000 && !output.over_budget() {
+                            let mut session = output.session(time);
+                            for datum in next .. next + 1_000 { session.give(datum); }
+                            next += 1_000;
+                        }
+                        if next < 100_000 {
+                            yielded2.fetch_add(1, Ordering::SeqCst);
+                            activator.activate();
+                        }
+                    }
+                    if next >= 100_000 { cap = None; }
+                }
+            })
+            .unary(Exchange::new(|x: &u64| *x).with_budget(4), "Consumer", |_cap, _info| |input, output| {
+                input.for_each(|time, data| output.session(&time).give_vec(&mut data.replace(Vec::new())));
+            })
+            .inspect(move |_| { received2.fetch_add(1, Ordering::SeqCst); });
+    });
+    while worker.step() { }
+    (received.load(Ordering::SeqCst) as u64, yielded.load(Ordering::SeqCst))
+}
+
+/// Runs `produce` on each worker of `config`.
+fn produce_with_budget(config: Configuration) -> Vec<(u64, usize)> {
+    timely::execute(config, produce)
+    .unwrap()
+    .join()
+    .into_iter()
+    .map(|result| result.unwrap())
+    .collect()
+}
+
+#[test]
+fn exchange_with_budget_in_process() {
+    let results = produce_with_budget(Configuration::Process(2));
+    // Each worker receives half of each worker's records.
+    assert!(results.iter().all(|&(received, _)| received == 100_000));
+    // Records sent to itself are not received until the producer yields.
+    assert!(results.iter().all(|&(_, yielded)| yielded > 0));
+}
+
+#[test]
+fn exchange_with_budget_between_processes() {
+
+    let configs = common::cluster(1, vec![ConnectionOptions::default(); 2]);
+
+    let processes = configs.into_iter().map(|config| {
+        std::thread::spawn(move || produce_with_budget(config))
+    }).collect::<Vec<_>>();
+
+    for process in processes {
+        let results = process.join().unwrap();
+        assert!(results.iter().all(|&(received, yielded)| received == 100_000 && yielded > 0));
+    }
+}
+
+// This test asserts that credits returned within a process are logged by the receiving worker,
+// as returned to the worker that sent the messages.
+#[test]
+fn credits_logged_in_process() {
+
+    let credits = Arc::new(AtomicUsize::new(0));
+    let credits2 = credits.clone();
+    let between = Arc::new(AtomicUsize::new(0));
+    let between2 = between.clone();
+    let config = Configuration::Cluster {
+        threads: 2,
+        process: 0,
+        addresses: vec!["127.0.0.1:0".to_owned()],
+        options: Box::default(),
+        log_fn: Box::new(move |setup| {
+            let credits = credits2.clone();
+            let between = between2.clone();
+            Some(Logger::new(Instant::now(), setup, move |_time, data| {
+                for (_, _, event) in data.drain(..) {
+                    if let CommunicationEvent::Credit(event) = event {
+                        assert!(!event.is_send);
+                        assert!(event.source < 2 && event.target < 2);
+                        credits.fetch_add(event.count, Ordering::SeqCst);
+                        if event.source != event.target {
+                            between.fetch_add(event.count, Ordering::SeqCst);
+                        }
+                    }
+                }
+            }))
+        }),
+    };
+
+    let results = produce_with_budget(config);
+    assert!(results.iter().all(|&(received, _)| received == 100_000));
+    assert!(credits.load(Ordering::SeqCst) > 0);
+    assert!(between.load(Ordering::SeqCst) > 0);
+}
@@ -0,0 +1,166 @@
+extern crate timely;
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use timely::Configuration;
+use timely::dataflow::InputHandle;
+use timely::dataflow::channels::pact::Exchange;
+use timely::dataflow::operators::{Input, Inspect, Probe};
+use timely::dataflow::operators::capture::Event;
+use timely::dataflow::operators::checkpoint::{Checkpoint, CheckpointStore};
+
+/// Sums the records of rounds `0 .. 8` at each worker, checkpointing at round 3, and reports
+/// the sums by round. The input advances through rounds 3 to 5 without stepping, so that the
+/// checkpoint is written with held back input. Worker 0 panics in round `crash`, if supplied.
+fn sums(path: &Path, crash: Option<u64>, sums: Arc<Mutex<Vec<(u64, u64)>>>) -> bool {
+    let path = path.to_path_buf();
+    timely::execute(Configuration::Process(2), move |worker| {
+
+        let store = CheckpointStore::<u64>::new(&path, worker.index(), worker.peers()).unwrap();
+        let resume = store.restored().map(|time| time + 1).unwrap_or(0);
+        store.checkpoint_at(3);
+
+        let sums = sums.clone();
+        let mut input = InputHandle::new();
+        let probe = worker.dataflow(|scope| {
+            scope.input_from(&mut input)
+                 .checkpointed(Exchange::new(|x: &u64| *x), "Sum", &store, |_time, data, sum: &mut u64| {
+                     *sum += data.iter().sum::<u64>();
+                     Some(*sum)
+                 })
+                 .inspect_time(move |time, sum| sums.lock().unwrap().push((*time, *sum)))
+                 .probe()
+        });
+
+        input.advance_to(resume);
+        for round in resume .. 8 {
+            if Some(round) == crash && worker.index() == 0 {
+                panic!("crash");
+            }
+            input.send(10 * round + worker.index() as u64);
+            input.advance_to(round + 1);
+            if round < 3 || round > 4 {
+                worker.step_while(|| probe.less_than(input.time()));
+            }
+        }
+    })
+    .unwrap()
+    .join()
+    .into_iter()
+    .all(|result| result.is_ok())
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = ::std::env::temp_dir().join(format!("timely-checkpoint-{}-{}", name, ::std::process::id()));
+    if path.exists() {
+        ::std::fs::remove_dir_all(&path).unwrap();
+    }
+    path
+}
+
+// This test asserts that a computation restored after a crash produces the same results as
+// one that did not crash, replaying the input held back when the checkpoint was written.
+#[test]
+fn restore_after_crash() {
+
+    let expected = Arc::new(Mutex::new(Vec::new()));
+    let path = temp_path("uninterrupted");
+    assert!(sums(&path, None, expected.clone()));
+    ::std::fs::remove_dir_all(&path).unwrap();
+
+    let observed = Arc::new(Mutex::new(Vec::new()));
+    let path = temp_path("crashed");
+    assert!(!sums(&path, Some(7), observed.clone()));
+    // Each worker's checkpoint includes its input at rounds 4 and 5, held back until it was written.
+    for index in 0 .. 2 {
+        let store = CheckpointStore::<u64>::new(&path, index, 2).unwrap();
+        assert_eq!(store.restored(), Some(3));
+        let events = store.register("Sum").restore_events::<u64>().unwrap().unwrap();
+        let mut times = events.iter().filter_map(|event| match event {
+            Event::Messages(time, _) => Some(*time),
+            Event::Progress(_) => None,
+        }).collect::<Vec<_>>();
+        times.sort();
+        assert_eq!(times, vec![4, 5]);
+    }
+    observed.lock().unwrap().retain(|&(time, _)| time <= 3);
+    assert!(sums(&path, None, observed.clone()));
+    ::std::fs::remove_dir_all(&path).unwrap();
+
+    let mut expected = expected.lock().unwrap().clone();
+    let mut observed = observed.lock().unwrap().clone();
+    expected.sort();
+    observed.sort();
+    assert_eq!(expected.len(), 16);
+    assert_eq!(observed, expected);
+}
+
+// This test asserts that an operator registered after a checkpoint completed does not take part
+// in it, but that checkpoints yet to complete await the operator.
+#[test]
+fn late_operators_join_incomplete_checkpoints() {
+
+    let path = temp_path("late");
+    let store = CheckpointStore::<u64>::new(&path, 0, 1).unwrap();
+    store.checkpoint_at(1);
+    store.checkpoint_at(2);
+
+    let mut early = store.register("Early");
+    early.write(&10u64).unwrap();
+    let mut late = store.register("Late");
+    assert_eq!(late.next(), Some(2));
+    early.write(&20u64).unwrap();
+
+    // The first checkpoint is complete, but the second awaits the late operator.
+    assert!(path.join("0").join("0").join("complete").exists());
+    assert!(!path.join("1").join("0").join("complete").exists());
+    late.write(&30u64).unwrap();
+
+    let restored = CheckpointStore::<u64>::new(&path, 0, 1).unwrap();
+    assert_eq!(restored.restored(), Some(2));
+    assert_eq!(restored.register("Early").restore::<u64>().unwrap(), Some(20));
+    assert_eq!(restored.register("Late").restore::<u64>().unwrap(), Some(30));
+    ::std::fs::remove_dir_all(path.join("1")).unwrap();
+
+    // Restoring from the first checkpoint, the late operator has no state.
+    let restored = CheckpointStore::<u64>::new(&path, 0, 1).unwrap();
+    assert_eq!(restored.restored(), Some(1));
+    assert_eq!(restored.register("Early").restore::<u64>().unwrap(), Some(10));
+    assert_eq!(restored.register("Late").restore::<u64>().unwrap(), None);
+
+    ::std::fs::remove_dir_all(&path).unwrap();
+}
+
+// This test asserts that state is restored by fewer workers than wrote it, and that their
+// stores remove the incomplete checkpoints of the workers that no longer exist.
+#[test]
+fn restore_by_fewer_workers() {
+
+    let path = temp_path("fewer");
+    for index in 0 .. 3 {
+        let store = CheckpointStore::<u64>::new(&path, index, 3).unwrap();
+        store.checkpoint_at(1);
+        store.checkpoint_at(2);
+        let mut checkpointer = store.register("Count");
+        checkpointer.write(&(index as u64 + 1)).unwrap();
+        if index == 2 {
+            checkpointer.write(&0u64).unwrap();
+        }
+    }
+    assert!(path.join("1").join("2").exists());
+
+    let stores = (0 .. 2).map(|index| CheckpointStore::<u64>::new(&path, index, 2).unwrap()).collect::<Vec<_>>();
+    assert!(!path.join("1").join("2").exists());
+    for store in stores.iter() {
+        assert_eq!(store.restored(), Some(1));
+    }
+
+    // Worker 0 restores the states of workers 0 and 2, and worker 1 that of worker 1.
+    let checkpointers = stores.iter().map(|store| store.register("Count")).collect::<Vec<_>>();
+    assert!(checkpointers[0].restore::<u64>().is_err());
+    assert_eq!(checkpointers[0].restore_merged(|x: u64, y| x + y).unwrap(), Some(4));
+    assert_eq!(checkpointers[1].restore::<u64>().unwrap(), Some(2));
+
+    ::std::fs::remove_dir_all(&path).unwrap();
+}
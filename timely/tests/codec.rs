@@ -0,0 +1,72 @@
+#![cfg(feature = "codec")]
+
+extern crate timely;
+#[macro_use]
+extern crate serde_derive;
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use timely::communication::ConnectionOptions;
+use timely::communication::codec::{Bincode, Coded};
+use timely::dataflow::operators::{ToStream, Map, Exchange, Inspect};
+
+// A type with boxed and recursive contents, which does not implement `Abomonation`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum Tree {
+    Leaf(String),
+    Node(Box<Tree>, Box<Tree>),
+}
+
+impl Bincode for Tree { }
+
+impl Tree {
+    fn new(value: u64) -> Self {
+        Tree::Node(Box::new(Tree::Leaf(value.to_string())), Box::new(Tree::Leaf((value + 1).to_string())))
+    }
+    fn value(&self) -> u64 {
+        match self {
+            Tree::Node(left, _) => match **left { Tree::Leaf(ref name) => name.parse().unwrap(), _ => panic!("unexpected tree") },
+            Tree::Leaf(_) => panic!("unexpected tree"),
+        }
+    }
+}
+
+#[test]
+fn exchange_coded_between_processes() {
+
+    let configs = common::cluster(1, vec![ConnectionOptions::default(); 2]);
+
+    let processes = configs.into_iter().map(|config| {
+        std::thread::spawn(move || {
+            timely::execute(config, |worker| {
+                let index = worker.index() as u64;
+                let received = Arc::new(Mutex::new(Vec::new()));
+                let received2 = received.clone();
+                worker.dataflow::<u64,_,_>(|scope| {
+                    (0 .. 1_000u64)
+                        .to_stream(scope)
+                        .map(|x| Coded::new(Tree::new(x)))
+                        .exchange(|tree| tree.get().value())
+                        .map(Coded::into_inner)
+                        .inspect(move |tree| received2.lock().unwrap().push(tree.clone()));
+                });
+                while worker.step() { }
+                let mut received = received.lock().unwrap().clone();
+                received.sort_by_key(|tree| tree.value());
+                let expected = (0 .. 1_000u64).filter(|x| x % 2 == index).flat_map(|x| vec![Tree::new(x); 2]).collect::<Vec<_>>();
+                assert_eq!(received, expected);
+            })
+            .unwrap()
+            .join()
+            .into_iter()
+            .map(|result| result.unwrap())
+            .count()
+        })
+    }).collect::<Vec<_>>();
+
+    for process in processes {
+        assert_eq!(process.join().unwrap(), 1);
+    }
+}
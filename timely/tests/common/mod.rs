@@ -0,0 +1,49 @@
+//! Configurations shared by tests that run several processes within one test.
+
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use timely::Configuration;
+use timely::communication::ConnectionOptions;
+
+/// Configurations for local processes of `threads` workers each, one for each of `options`.
+///
+/// Each process binds a port of its own choosing and publishes it through a fresh rendezvous
+/// directory, so that no port need be reserved in advance, where another test could take it.
+pub fn cluster(threads: usize, options: Vec<ConnectionOptions>) -> Vec<Configuration> {
+    static CLUSTERS: AtomicUsize = AtomicUsize::new(0);
+    let cluster = CLUSTERS.fetch_add(1, Ordering::SeqCst);
+    let directory = std::env::temp_dir().join(format!("timely-cluster-{}-{}", std::process::id(), cluster));
+    let _ = std::fs::remove_dir_all(&directory);
+    let addresses = vec!["127.0.0.1:0".to_owned(); options.len()];
+    options.into_iter().enumerate().map(|(process, options)| {
+        let options = ConnectionOptions { rendezvous: Some(directory.clone()), ..options };
+        Configuration::cluster(threads, process, addresses.clone(), options)
+    }).collect()
+}
+
+/// Impersonates process 1 of the cluster of `config`, connecting to process 0 once it has
+/// published its address, and completing the handshake without proposing compression.
+#[allow(dead_code)]
+pub fn impersonate(config: &Configuration) -> JoinHandle<TcpStream> {
+    let directory = match config {
+        Configuration::Cluster { options, .. } => options.rendezvous.clone().expect("no rendezvous directory"),
+        _ => panic!("not a cluster configuration"),
+    };
+    std::thread::spawn(move || {
+        let address = loop {
+            match std::fs::read_to_string(directory.join("process-0")) {
+                Ok(address) => break address,
+                Err(_) => std::thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        let mut stream = TcpStream::connect(&address[..]).unwrap();
+        stream.write_all(&0xc2f1fb770118add9u64.to_le_bytes()).unwrap();
+        stream.write_all(&1u64.to_le_bytes()).unwrap();
+        stream.write_all(&0u64.to_le_bytes()).unwrap();
+        stream
+    })
+}
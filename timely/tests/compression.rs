@@ -0,0 +1,118 @@
+#![cfg(feature = "compression")]
+
+extern crate timely;
+
+mod common;
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use timely::Configuration;
+use timely::communication::ConnectionOptions;
+use timely::communication::allocator::zero_copy::compression::{Compressed, Compression, MAX_FRAME};
+use timely::communication::logging::CommunicationEvent;
+use timely::dataflow::operators::{ToStream, Map, Exchange, Inspect};
+use timely::logging_core::Logger;
+
+// This test asserts that data exchanged over compressed connections arrive intact, and that
+// the compression of each frame is logged.
+#[test]
+fn exchange_compressed_between_processes() {
+
+    let options = ConnectionOptions { compression: Some(Compression::Lz4), ..ConnectionOptions::default() };
+    let configs = common::cluster(1, vec![options; 2]);
+
+    // Sent and received (compressed, uncompressed) byte counts.
+    let totals = Arc::new(Mutex::new([(0, 0), (0, 0)]));
+
+    let processes = configs.into_iter().map(|mut config| {
+        let totals = totals.clone();
+        if let Configuration::Cluster { ref mut log_fn, .. } = config {
+            *log_fn = Box::new(move |setup| {
+                let totals = totals.clone();
+                Some(Logger::new(Instant::now(), setup, move |_time, data| {
+                    let mut totals = totals.lock().unwrap();
+                    for (_, _, event) in data.drain(..) {
+                        if let CommunicationEvent::Compression(event) = event {
+                            let total = &mut totals[if event.is_send { 0 } else { 1 }];
+                            total.0 += event.compressed;
+                            total.1 += event.uncompressed;
+                        }
+                    }
+                }))
+            });
+        }
+        std::thread::spawn(move || {
+            timely::execute(config, |worker| {
+                let index = worker.index() as u64;
+                let received = Arc::new(Mutex::new(Vec::new()));
+                let received2 = received.clone();
+                worker.dataflow::<u64,_,_>(|scope| {
+                    (0 .. 1_000u64)
+                        .to_stream(scope)
+                        .map(|x| (x, vec![x; 100]))
+                        .exchange(|x| x.0)
+                        .inspect(move |x| received2.lock().unwrap().push(x.clone()));
+                });
+                while worker.step() { }
+                let mut received = received.lock().unwrap().clone();
+                received.sort();
+                let expected = (0 .. 1_000u64).filter(|x| x % 2 == index).flat_map(|x| vec![(x, vec![x; 100]); 2]).collect::<Vec<_>>();
+                assert_eq!(received, expected);
+            })
+            .unwrap()
+            .join()
+            .into_iter()
+            .map(|result| result.unwrap())
+            .count()
+        })
+    }).collect::<Vec<_>>();
+
+    for process in processes {
+        assert_eq!(process.join().unwrap(), 1);
+    }
+
+    let totals = totals.lock().unwrap();
+    assert_eq!(totals[0], totals[1]);
+    let (compressed, uncompressed) = totals[0];
+    assert!(uncompressed > 0);
+    assert!(compressed < uncompressed / 4);
+}
+
+fn connected_pair() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    (stream, listener.accept().unwrap().0)
+}
+
+// This test asserts that writes larger than a frame are split across frames.
+#[test]
+fn large_writes_span_frames() {
+    let (sender, receiver) = connected_pair();
+    let data = (0 .. 3 * MAX_FRAME).map(|x| (x % 251) as u8).collect::<Vec<_>>();
+    let data2 = data.clone();
+    let writer = std::thread::spawn(move || {
+        let mut sender = Compressed::new(sender, Compression::Lz4, None);
+        sender.write_all(&data2[..]).unwrap();
+    });
+    let mut receiver = Compressed::new(receiver, Compression::Lz4, None);
+    let mut received = vec![0u8; data.len()];
+    receiver.read_exact(&mut received[..]).unwrap();
+    writer.join().unwrap();
+    assert!(received == data);
+}
+
+// This test asserts that a frame claiming more than `MAX_FRAME` bytes is rejected before any
+// allocation of its size.
+#[test]
+fn oversized_frames_are_rejected() {
+    let (mut sender, receiver) = connected_pair();
+    sender.write_all(&16u64.to_le_bytes()).unwrap();
+    sender.write_all(&(1u64 << 40).to_le_bytes()).unwrap();
+    sender.write_all(&[0u8; 16]).unwrap();
+    let mut receiver = Compressed::new(receiver, Compression::Lz4, None);
+    let error = receiver.read(&mut [0u8; 16]).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
@@ -0,0 +1,132 @@
+extern crate timely;
+
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use timely::Configuration;
+use timely::communication::ConnectionOptions;
+use timely::communication::networking::Retry;
+use timely::dataflow::operators::{ToStream, Exchange, Inspect};
+
+fn cluster(process: usize, addresses: Vec<String>, retry: Retry, rendezvous: Option<std::path::PathBuf>) -> Configuration {
+    Configuration::cluster(1, process, addresses, ConnectionOptions { retry, rendezvous, ..ConnectionOptions::default() })
+}
+
+// This test asserts that processes binding port 0 discover each other's addresses through a
+// rendezvous directory, and clean it up once connected.
+#[test]
+fn rendezvous_through_directory() {
+
+    let directory = std::env::temp_dir().join(format!("timely-rendezvous-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    let processes = (0 .. 3).map(|process| {
+        let addresses = vec!["127.0.0.1:0".to_owned(); 3];
+        let retry = Retry { timeout: Some(Duration::from_secs(30)), ..Retry::default() };
+        let config = cluster(process, addresses, retry, Some(directory.clone()));
+        std::thread::spawn(move || {
+            timely::execute(config, |worker| {
+                let index = worker.index() as u64;
+                let received = Arc::new(Mutex::new(Vec::new()));
+                let received2 = received.clone();
+                worker.dataflow::<u64,_,_>(|scope| {
+                    (0 .. 100u64)
+                        .to_stream(scope)
+                        .exchange(|x| *x)
+                        .inspect(move |x| received2.lock().unwrap().push(*x));
+                });
+                while worker.step() { }
+                let mut received = received.lock().unwrap().clone();
+                received.sort();
+                let expected = (0 .. 100u64).filter(|x| x % 3 == index).flat_map(|x| vec![x; 3]).collect::<Vec<_>>();
+                assert_eq!(received, expected);
+            })
+            .unwrap()
+            .join()
+            .into_iter()
+            .map(|result| result.unwrap())
+            .count()
+        })
+    }).collect::<Vec<_>>();
+
+    for process in processes {
+        assert_eq!(process.join().unwrap(), 1);
+    }
+
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+/// Configures process `process` of two from arguments, with a rendezvous through `directory`.
+fn from_args(process: usize, directory: &Path, advertised_host: Option<&str>) -> Configuration {
+    let mut args = vec!["-n".to_owned(), "2".to_owned(), "-p".to_owned(), process.to_string()];
+    args.extend(vec!["--rendezvous".to_owned(), directory.to_string_lossy().into_owned()]);
+    if let Some(host) = advertised_host {
+        args.extend(vec!["--advertise-host".to_owned(), host.to_owned()]);
+    }
+    Configuration::from_args(args.into_iter()).unwrap()
+}
+
+// This test asserts that processes configured from arguments bind every interface, or the host
+// they advertise, and that the address published for the rendezvous is the one bound.
+#[test]
+fn rendezvous_from_args_publishes_bound_address() {
+
+    let directory = std::env::temp_dir().join(format!("timely-rendezvous-args-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    match from_args(0, &directory, None) {
+        Configuration::Cluster { addresses, .. } => assert_eq!(addresses, vec!["0.0.0.0:0".to_owned(); 2]),
+        _ => panic!("expected a cluster configuration"),
+    }
+
+    let run = |config: Configuration| std::thread::spawn(move || {
+        timely::execute(config, |_worker| ()).unwrap().join().into_iter().all(|result| result.is_ok())
+    });
+
+    let config = from_args(0, &directory, Some("127.0.0.1"));
+    match config {
+        Configuration::Cluster { ref addresses, .. } => assert_eq!(addresses[0], "127.0.0.1:0"),
+        _ => panic!("expected a cluster configuration"),
+    }
+    let first = run(config);
+
+    // Process 0 publishes its address, then awaits process 1 to connect to it.
+    let published = loop {
+        match std::fs::read_to_string(directory.join("process-0")) {
+            Ok(address) => break address,
+            Err(_) => std::thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    let published: SocketAddr = published.parse().unwrap();
+    assert_eq!(published.ip().to_string(), "127.0.0.1");
+    assert_ne!(published.port(), 0);
+
+    let second = run(from_args(1, &directory, Some("127.0.0.1")));
+    assert!(first.join().unwrap());
+    assert!(second.join().unwrap());
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+// This test asserts that a process that cannot reach its peers gives up once its timeout elapses.
+#[test]
+fn connect_times_out() {
+
+    // Reserve two local ports, with no process behind them.
+    let addresses = {
+        let listeners = (0 .. 2).map(|_| TcpListener::bind("127.0.0.1:0").unwrap()).collect::<Vec<_>>();
+        listeners.iter().map(|l| l.local_addr().unwrap().to_string()).collect::<Vec<_>>()
+    };
+
+    let retry = Retry { timeout: Some(Duration::from_millis(500)), ..Retry::default() };
+    let start = Instant::now();
+    let error = match timely::execute(cluster(1, addresses, retry, None), |_worker| ()) {
+        Ok(_) => panic!("connected to a process that does not exist"),
+        Err(error) => error,
+    };
+    assert!(error.contains("timed out connecting to process 0"), "unexpected error: {}", error);
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
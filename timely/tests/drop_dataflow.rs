@@ -0,0 +1,158 @@
+extern crate timely;
+
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use timely::Configuration;
+use timely::communication::ConnectionOptions;
+use timely::communication::logging::CommunicationEvent;
+use timely::dataflow::InputHandle;
+use timely::dataflow::operators::{Input, Exchange, Probe, ToStream};
+use timely::logging_core::Logger;
+
+#[test] fn drop_dataflow_1w() { drop_dataflow_helper(Configuration::Thread); }
+#[test] fn drop_dataflow_2w() { drop_dataflow_helper(Configuration::Process(2)); }
+#[test] fn drop_dataflow_3w() { drop_dataflow_helper(Configuration::Process(3)); }
+
+#[test] fn drop_at_one_worker_2w() { drop_at_one_worker_helper(Configuration::Process(2)); }
+#[test] fn drop_at_one_worker_3w() { drop_at_one_worker_helper(Configuration::Process(3)); }
+#[test] fn drop_at_one_worker_cluster() {
+
+    let configs = common::cluster(2, vec![ConnectionOptions::default(); 2]);
+
+    let processes = configs.into_iter().map(|config| {
+        std::thread::spawn(move || drop_at_one_worker_helper(config))
+    }).collect::<Vec<_>>();
+
+    for process in processes {
+        process.join().unwrap();
+    }
+}
+
+// This method asserts that dataflows can be dropped while peers continue to send data to them.
+fn drop_dataflow_helper(config: ::timely::Configuration) {
+    timely::execute(config, move |worker| {
+
+        let mut inputs = Vec::new();
+        let mut probes = Vec::new();
+        let mut dataflows = Vec::new();
+
+        for _ in 0 .. 3 {
+            let mut input = InputHandle::new();
+            dataflows.push(worker.next_dataflow_index());
+            let probe = worker.dataflow::<u64,_,_>(|scope| {
+                scope.input_from(&mut input)
+                     .exchange(|x: &u64| *x)
+                     .probe()
+            });
+            inputs.push(input);
+            probes.push(probe);
+        }
+
+        assert_eq!(worker.installed_dataflows(), dataflows);
+
+        for round in 0 .. 10 {
+            for input in inputs.iter_mut() {
+                for value in 0 .. 100 { input.send(value); }
+                input.advance_to(round + 1);
+            }
+            // Drop the middle dataflow while data are still in flight.
+            if round == 5 {
+                worker.drop_dataflow(dataflows[1]);
+                assert_eq!(worker.installed_dataflows(), vec![dataflows[0], dataflows[2]]);
+            }
+            worker.step();
+        }
+
+        for input in inputs.iter_mut() { input.advance_to(10); }
+        while probes[0].less_than(&10) || probes[2].less_than(&10) {
+            worker.step();
+        }
+    }).unwrap(); // asserts error-free execution;
+}
+
+// This method asserts that a dataflow dropped by one worker is dropped by all workers, even
+// though their inputs remain open and data remain in flight.
+fn drop_at_one_worker_helper(config: ::timely::Configuration) {
+    timely::execute(config, move |worker| {
+
+        let mut input = InputHandle::new();
+        let dataflow = worker.next_dataflow_index();
+        worker.dataflow::<u64,_,_>(|scope| {
+            scope.input_from(&mut input)
+                 .exchange(|x: &u64| *x)
+                 .probe();
+        });
+
+        for value in 0 .. 1_000 { input.send(value); }
+        input.advance_to(1);
+
+        if worker.index() == 0 {
+            worker.drop_dataflow(dataflow);
+        }
+        while worker.step() { }
+        assert!(worker.installed_dataflows().is_empty());
+    }).unwrap(); // asserts error-free execution;
+}
+
+// This test asserts that only dropped dataflows are announced to other workers, and that each
+// worker announces its retirement of a dropped dataflow once, on each teardown channel.
+#[test]
+fn only_drops_are_announced() {
+
+    let announced = Arc::new(AtomicUsize::new(0));
+
+    let processes = announcing_cluster(&announced).into_iter().map(|config| {
+        std::thread::spawn(move || {
+            timely::execute(config, |worker| {
+                for _ in 0 .. 10 {
+                    worker.dataflow::<u64,_,_>(|scope| {
+                        (0 .. 100u64).to_stream(scope)
+                                     .exchange(|x| *x)
+                                     .probe();
+                    });
+                }
+                while worker.step() { }
+            }).unwrap();
+        })
+    }).collect::<Vec<_>>();
+    for process in processes {
+        process.join().unwrap();
+    }
+    assert_eq!(announced.load(Ordering::SeqCst), 0);
+
+    let processes = announcing_cluster(&announced).into_iter().map(|config| {
+        std::thread::spawn(move || drop_at_one_worker_helper(config))
+    }).collect::<Vec<_>>();
+    for process in processes {
+        process.join().unwrap();
+    }
+    assert_eq!(announced.load(Ordering::SeqCst), 4);
+}
+
+/// A cluster of two single-worker processes, which count in `announced` the messages they
+/// send on the teardown channels, just below those reserved by the communication layer.
+fn announcing_cluster(announced: &Arc<AtomicUsize>) -> Vec<Configuration> {
+    common::cluster(1, vec![ConnectionOptions::default(); 2]).into_iter().map(|mut config| {
+        if let Configuration::Cluster { ref mut log_fn, .. } = config {
+            let announced = announced.clone();
+            *log_fn = Box::new(move |setup| {
+                let announced = announced.clone();
+                Some(Logger::new(Instant::now(), setup, move |_time, data| {
+                    for (_, _, event) in data.drain(..) {
+                        if let CommunicationEvent::Message(event) = event {
+                            let channel = event.header.channel;
+                            if event.is_send && (usize::MAX - 3 ..= usize::MAX - 2).contains(&channel) {
+                                announced.fetch_add(1, Ordering::SeqCst);
+                            }
+                        }
+                    }
+                }))
+            });
+        }
+        config
+    }).collect()
+}
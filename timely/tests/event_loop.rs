@@ -0,0 +1,100 @@
+#![cfg(feature = "event-loop")]
+
+extern crate timely;
+
+mod common;
+
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use timely::Configuration;
+use timely::communication::ConnectionOptions;
+use timely::dataflow::InputHandle;
+use timely::dataflow::operators::{ToStream, Input, Exchange, Inspect, Probe};
+
+/// Configurations for processes of two workers each, served by the numbers of network threads.
+fn cluster(network_threads: Vec<Option<NonZeroUsize>>) -> Vec<Configuration> {
+    let options = network_threads.into_iter().map(|network_threads| {
+        ConnectionOptions { network_threads, ..ConnectionOptions::default() }
+    });
+    common::cluster(2, options.collect())
+}
+
+// This test asserts that processes whose connections are served by event loops exchange data
+// with each other, and with a process whose connections are served by threads.
+#[test]
+fn exchange_through_event_loops() {
+
+    let configs = cluster(vec![NonZeroUsize::new(1), NonZeroUsize::new(2), NonZeroUsize::new(3), None]);
+
+    let processes = configs.into_iter().map(|config| {
+        std::thread::spawn(move || {
+            timely::execute(config, |worker| {
+                let index = worker.index() as u64;
+                let sum = Arc::new(Mutex::new(0));
+                let sum2 = sum.clone();
+                worker.dataflow::<u64,_,_>(|scope| {
+                    (0 .. 100_000u64)
+                        .to_stream(scope)
+                        .exchange(|x| *x)
+                        .inspect(move |x| {
+                            assert_eq!(*x % 8, index);
+                            *sum2.lock().unwrap() += *x;
+                        });
+                });
+                while worker.step() { }
+                let sum = *sum.lock().unwrap();
+                sum
+            })
+            .unwrap()
+            .join()
+            .into_iter()
+            .map(|result| result.unwrap())
+            .collect::<Vec<_>>()
+        })
+    }).collect::<Vec<_>>();
+
+    let sums = processes.into_iter().flat_map(|process| process.join().unwrap()).collect::<Vec<_>>();
+    let expected = (0 .. 8).map(|index| 8 * (index .. 100_000).step_by(8).sum::<u64>()).collect::<Vec<_>>();
+    assert_eq!(sums, expected);
+}
+
+// This test asserts that the panic of a worker aborts workers of processes served by event loops.
+#[test]
+fn panic_aborts_event_loop_processes() {
+
+    let configs = cluster(vec![NonZeroUsize::new(1); 2]);
+
+    let processes = configs.into_iter().map(|config| {
+        std::thread::spawn(move || {
+            timely::execute(config, |worker| {
+                let mut input = InputHandle::new();
+                let probe = worker.dataflow::<u64,_,_>(|scope| {
+                    scope.input_from(&mut input)
+                         .exchange(|x: &u64| *x)
+                         .probe()
+                });
+                if worker.index() == 3 {
+                    panic!("boom");
+                }
+                input.send(0);
+                while probe.less_than(&1) {
+                    worker.step();
+                }
+            })
+            .unwrap()
+            .join()
+            .into_iter()
+            .map(|result| result.unwrap_err())
+            .collect::<Vec<_>>()
+        })
+    }).collect::<Vec<_>>();
+
+    let errors = processes.into_iter().flat_map(|process| process.join().unwrap()).collect::<Vec<_>>();
+    assert_eq!(errors, vec![
+        "worker 0 panicked: aborted after worker 3 panicked: boom".to_owned(),
+        "worker 1 panicked: aborted after worker 3 panicked: boom".to_owned(),
+        "worker 2 panicked: aborted after worker 3 panicked: boom".to_owned(),
+        "worker 3 panicked: boom".to_owned(),
+    ]);
+}
@@ -0,0 +1,89 @@
+extern crate timely;
+
+use std::sync::{Arc, Mutex};
+
+use timely::Configuration;
+use timely::dataflow::InputHandle;
+use timely::dataflow::operators::{Input, Inspect, Probe};
+use timely::dataflow::operators::join::Join;
+
+// The records of each input at `round`: keys are shared by both inputs only in part, and
+// the second input has two records for some keys.
+fn names(round: u64) -> Vec<(u64, String)> {
+    (round .. round + 20).filter(|key| key % 2 == 0).map(|key| (key, format!("name{}", key))).collect()
+}
+fn counts(round: u64) -> Vec<(u64, u64)> {
+    (round .. round + 20).filter(|key| key % 3 == 0).flat_map(|key| vec![(key, key * 10), (key, key * 10 + 1)]).collect()
+}
+
+// This test asserts that the outer joins match records introduced at different workers, and
+// report the unmatched records of each timestamp exactly once.
+#[test]
+fn outer_joins_across_workers() {
+
+    let results = Arc::new(Mutex::new((Vec::new(), Vec::new())));
+    let results2 = results.clone();
+
+    timely::execute(Configuration::Process(4), move |worker| {
+        let index = worker.index() as u64;
+        let peers = worker.peers() as u64;
+        let results = results2.clone();
+        let results2 = results2.clone();
+        let mut input1 = InputHandle::new();
+        let mut input2 = InputHandle::new();
+        let probe = worker.dataflow::<u64,_,_>(|scope| {
+            let names = scope.input_from(&mut input1);
+            let counts = scope.input_from(&mut input2);
+            names.left_join(&counts)
+                 .inspect_time(move |time, record| results.lock().unwrap().0.push((*time, record.clone())));
+            names.outer_join(&counts)
+                 .inspect_time(move |time, record| results2.lock().unwrap().1.push((*time, record.clone())))
+                 .probe()
+        });
+        for round in 0 .. 5u64 {
+            // Each record is introduced by a worker other than the one it is routed to.
+            for (key, name) in names(round) {
+                if (key + 1) % peers == index { input1.send((key, name)); }
+            }
+            for (key, count) in counts(round) {
+                if (count + 2) % peers == index { input2.send((key, count)); }
+            }
+            input1.advance_to(round + 1);
+            input2.advance_to(round + 1);
+            while probe.less_than(input1.time()) {
+                worker.step();
+            }
+        }
+    }).unwrap();
+
+    let mut expected_left = Vec::new();
+    let mut expected_outer = Vec::new();
+    for round in 0 .. 5u64 {
+        let names = names(round);
+        let counts = counts(round);
+        for (key, name) in names.iter() {
+            let matches = counts.iter().filter(|(key2, _)| key2 == key).collect::<Vec<_>>();
+            for (_, count) in matches.iter() {
+                expected_left.push((round, (*key, name.clone(), Some(*count))));
+                expected_outer.push((round, (*key, Some(name.clone()), Some(*count))));
+            }
+            if matches.is_empty() {
+                expected_left.push((round, (*key, name.clone(), None)));
+                expected_outer.push((round, (*key, Some(name.clone()), None)));
+            }
+        }
+        for (key, count) in counts.iter() {
+            if !names.iter().any(|(key2, _)| key2 == key) {
+                expected_outer.push((round, (*key, None, Some(*count))));
+            }
+        }
+    }
+    expected_left.sort();
+    expected_outer.sort();
+
+    let mut results = results.lock().unwrap();
+    results.0.sort();
+    results.1.sort();
+    assert_eq!(results.0, expected_left);
+    assert_eq!(results.1, expected_outer);
+}
@@ -0,0 +1,54 @@
+extern crate timely;
+
+mod common;
+
+use std::io::Read;
+
+use timely::communication::ConnectionOptions;
+use timely::dataflow::InputHandle;
+use timely::dataflow::operators::{Input, Exchange, Probe};
+
+// This test asserts that a process whose connection fails without a clean shutdown is
+// reported to workers, rather than taking down the computation.
+#[test]
+fn lost_process_is_reported() {
+
+    let options = ConnectionOptions { tolerate_failures: true, ..ConnectionOptions::default() };
+    let config = common::cluster(2, vec![options; 2]).remove(0);
+
+    // Impersonate process 1: complete the handshake, then vanish without a clean shutdown.
+    let impostor = common::impersonate(&config);
+
+    let guards = timely::execute(config, |worker| {
+
+        let mut input = InputHandle::new();
+        let dataflow = worker.next_dataflow_index();
+        let probe = worker.dataflow::<u64,_,_>(|scope| {
+            scope.input_from(&mut input)
+                 .exchange(|x: &u64| *x)
+                 .probe()
+        });
+
+        // Data for the lost workers should be discarded.
+        for value in 0 .. 100 { input.send(value); }
+        input.advance_to(1);
+
+        // The dataflow cannot complete without the lost process.
+        while worker.lost_processes().is_empty() {
+            assert!(probe.less_than(&1));
+            worker.step();
+        }
+
+        worker.drop_dataflow(dataflow);
+        worker.lost_processes()
+    }).unwrap();
+
+    let mut stream = impostor.join().unwrap();
+    let mut agreed = [0u8; 8];
+    stream.read_exact(&mut agreed).unwrap();
+    drop(stream);
+
+    for result in guards.join() {
+        assert_eq!(result.unwrap(), vec![1]);
+    }
+}
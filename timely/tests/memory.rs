@@ -0,0 +1,64 @@
+extern crate timely;
+
+mod common;
+
+use std::time::Duration;
+
+use timely::communication::ConnectionOptions;
+use timely::dataflow::InputHandle;
+use timely::dataflow::operators::{Input, Exchange, Probe};
+use timely::logging::MemoryKind;
+
+// This test asserts that bytes received from another process are accounted until the worker
+// takes them from the network threads.
+#[test]
+fn received_bytes_are_accounted() {
+
+    let configs = common::cluster(1, vec![ConnectionOptions::default(); 2]);
+
+    let processes = configs.into_iter().map(|config| {
+        std::thread::spawn(move || {
+            timely::execute(config, |worker| {
+                let mut input = InputHandle::new();
+                let probe = worker.dataflow::<u64,_,_>(|scope| {
+                    scope.input_from(&mut input)
+                         .exchange(|_: &u64| 1)
+                         .probe()
+                });
+                if worker.index() == 0 {
+                    for record in 0 .. 10_000u64 { input.send(record); }
+                }
+                input.advance_to(1);
+
+                let received = |worker: &timely::worker::Worker<_>| {
+                    worker.memory_usage()
+                          .into_iter()
+                          .find(|event| event.kind == MemoryKind::Received(0))
+                          .map(|event| { assert!(event.addr.is_empty()); event.bytes })
+                };
+                if worker.index() == 1 {
+                    // Without stepping, the records from worker 0 remain with the network threads.
+                    while received(worker).unwrap_or(0) < 10_000 * 8 {
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+                }
+                while probe.less_than(input.time()) {
+                    worker.step();
+                }
+                if worker.index() == 1 {
+                    // Only progress updates may have arrived since.
+                    assert!(received(worker).unwrap() < 10_000 * 8);
+                }
+            })
+            .unwrap()
+            .join()
+            .into_iter()
+            .map(|result| result.unwrap())
+            .count()
+        })
+    }).collect::<Vec<_>>();
+
+    for process in processes {
+        assert_eq!(process.join().unwrap(), 1);
+    }
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "metrics")]
+
+extern crate timely;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use timely::Configuration;
+use timely::dataflow::InputHandle;
+use timely::dataflow::operators::{Input, Map, Probe};
+use timely::metrics::Metrics;
+
+/// Requests the metrics served at `addr`, and returns the body of the response.
+fn scrape(addr: SocketAddr) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    let body = response.find("\r\n\r\n").unwrap() + 4;
+    response[body ..].to_owned()
+}
+
+/// Indicates that `text` reports `value` for `metric` of the `Map` operator at `addr`.
+fn reports(text: &str, metric: &str, addr: &str, value: u64) -> bool {
+    text.lines().any(|line| {
+        line.starts_with(&format!("{}{{", metric)) &&
+        line.contains("operator=\"Map\"") &&
+        line.ends_with(&format!("addr=\"{}\"}} {}", addr, value))
+    })
+}
+
+// This test asserts that scrapes report the operators of running dataflows, and that their
+// metrics are removed once the dataflows are dropped or complete.
+#[test]
+fn scrape_reports_only_running_dataflows() {
+
+    let metrics = Metrics::new();
+    let addr = metrics.serve("127.0.0.1:0").unwrap();
+
+    let metrics2 = metrics.clone();
+    timely::execute(Configuration::Thread, move |worker| {
+
+        metrics2.install(&mut worker.log_register());
+
+        let mut inputs = Vec::new();
+        let mut probes = Vec::new();
+        let mut dataflows = Vec::new();
+        for _ in 0 .. 2 {
+            let mut input = InputHandle::new();
+            dataflows.push(worker.next_dataflow_index());
+            let probe = worker.dataflow::<u64,_,_>(|scope| {
+                scope.input_from(&mut input)
+                     .map(|x| x + 1)
+                     .probe()
+            });
+            inputs.push(input);
+            probes.push(probe);
+        }
+
+        for input in inputs.iter_mut() {
+            for value in 0 .. 10 { input.send(value); }
+            input.advance_to(1);
+        }
+        while probes.iter().any(|probe| probe.less_than(&1)) {
+            worker.step();
+        }
+
+        // Both dataflows are running, and their operators are reported.
+        let text = scrape(addr);
+        assert!(reports(&text, "timely_operator_records_in_total", "0,2", 10));
+        assert!(reports(&text, "timely_operator_records_in_total", "1,2", 10));
+
+        // The operators of a dropped dataflow are no longer reported.
+        worker.drop_dataflow(dataflows[1]);
+        worker.step();
+        let text = scrape(addr);
+        assert!(text.contains("addr=\"0,2\""));
+        assert!(!text.contains("addr=\"1"));
+
+    }).unwrap();
+
+    // The remaining dataflow has completed, and no operators are reported.
+    let text = scrape(addr);
+    assert!(text.contains("# TYPE timely_operator_records_in_total counter"));
+    assert!(!text.contains("operator=\""));
+}
@@ -0,0 +1,63 @@
+extern crate timely;
+
+mod common;
+
+use timely::Configuration;
+use timely::communication::{ConnectionOptions, WorkerGuards, WorkerPanic};
+use timely::dataflow::InputHandle;
+use timely::dataflow::operators::{Input, Exchange, Probe};
+
+/// Runs workers that await each other indefinitely, except for worker `failing`, which panics.
+fn run_until_panic(config: Configuration, failing: usize) -> WorkerGuards<()> {
+    timely::execute(config, move |worker| {
+        let mut input = InputHandle::new();
+        let probe = worker.dataflow::<u64,_,_>(|scope| {
+            scope.input_from(&mut input)
+                 .exchange(|x: &u64| *x)
+                 .probe()
+        });
+        if worker.index() == failing {
+            panic!("boom");
+        }
+        input.send(0);
+        while probe.less_than(&1) {
+            worker.step();
+        }
+    }).unwrap()
+}
+
+/// Asserts that the failing worker reports its panic, and that the others abort after it.
+fn assert_aborted(guards: WorkerGuards<()>, first: usize, failing: usize) {
+    let cancellation = guards.cancellation().clone();
+    for (index, result) in guards.join().into_iter().enumerate() {
+        let error = result.unwrap_err();
+        if first + index == failing {
+            assert_eq!(error, format!("worker {} panicked: boom", failing));
+        }
+        else {
+            assert_eq!(error, format!("worker {} panicked: aborted after worker {} panicked: boom", first + index, failing));
+        }
+    }
+    assert_eq!(cancellation.failure(), Some(WorkerPanic { worker: failing, message: "boom".to_owned() }));
+}
+
+// This test asserts that the panic of a worker aborts the other workers of its process.
+#[test]
+fn panic_aborts_local_workers() {
+    assert_aborted(run_until_panic(Configuration::Process(3), 1), 0, 1);
+}
+
+// This test asserts that the panic of a worker aborts the workers of other processes.
+#[test]
+fn panic_aborts_remote_workers() {
+
+    let configs = common::cluster(2, vec![ConnectionOptions::default(); 2]);
+
+    let processes = configs.into_iter().enumerate().map(|(process, config)| {
+        ::std::thread::spawn(move || assert_aborted(run_until_panic(config, 3), 2 * process, 3))
+    }).collect::<Vec<_>>();
+
+    for process in processes {
+        process.join().unwrap();
+    }
+}
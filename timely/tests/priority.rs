@@ -0,0 +1,163 @@
+extern crate timely;
+
+mod common;
+
+use std::io::Read;
+use std::sync::{Arc, Barrier, Mutex};
+use std::time::Duration;
+
+use timely::communication::ConnectionOptions;
+use timely::communication::networking::MessageHeader;
+use timely::dataflow::InputHandle;
+use timely::dataflow::operators::{Input, Exchange, Inspect, Probe};
+use timely::logging::TimelyEvent;
+
+// This test asserts that progress, sent ahead of data between processes, never lets a probe
+// pass a round whose data have not all been received.
+#[test]
+fn progress_awaits_overtaken_data() {
+
+    let configs = common::cluster(2, vec![ConnectionOptions::default(); 2]);
+
+    let processes = configs.into_iter().map(|config| {
+        std::thread::spawn(move || {
+            timely::execute(config, |worker| {
+                let index = worker.index() as u64;
+                let received = Arc::new(Mutex::new(Vec::new()));
+                let received2 = received.clone();
+                let mut input = InputHandle::new();
+                let probe = worker.dataflow::<u64,_,_>(|scope| {
+                    scope.input_from(&mut input)
+                         .exchange(|x: &Vec<u64>| x[0])
+                         .inspect_batch(move |time, data| {
+                             let mut received = received2.lock().unwrap();
+                             for datum in data.iter() {
+                                 received.push((*time, datum[0]));
+                             }
+                         })
+                         .probe()
+                });
+                for round in 0 .. 10u64 {
+                    for record in 0 .. 100u64 {
+                        input.send(vec![record; 10_000]);
+                    }
+                    input.advance_to(round + 1);
+                    while probe.less_than(input.time()) {
+                        worker.step();
+                    }
+                    // Each worker receives 25 records from each of the four workers.
+                    let received = received.lock().unwrap();
+                    assert_eq!(received.iter().filter(|(time, _)| *time == round).count(), 100);
+                    assert!(received.iter().all(|(_, record)| record % 4 == index));
+                }
+            })
+            .unwrap()
+            .join()
+            .into_iter()
+            .map(|result| result.unwrap())
+            .count()
+        })
+    }).collect::<Vec<_>>();
+
+    for process in processes {
+        assert_eq!(process.join().unwrap(), 2);
+    }
+}
+
+// This test asserts that progress sent after bulk data overtakes the data still queued for
+// the network. Process 1 is impersonated, and reads nothing until worker 0 has sent both, so
+// that the data back up behind the unread connection.
+#[test]
+fn progress_overtakes_queued_data() {
+
+    let options = ConnectionOptions { tolerate_failures: true, ..ConnectionOptions::default() };
+    let config = common::cluster(1, vec![options; 2]).remove(0);
+    let impostor = common::impersonate(&config);
+
+    // Times at which worker 0 sent data to worker 1, and progress updates.
+    let data_sent = Arc::new(Mutex::new(Vec::new()));
+    let progress_sent = Arc::new(Mutex::new(Vec::new()));
+    let sent = Arc::new(Barrier::new(2));
+
+    let data_sent2 = data_sent.clone();
+    let progress_sent2 = progress_sent.clone();
+    let sent2 = sent.clone();
+    let guards = timely::execute(config, move |worker| {
+
+        let data_sent = data_sent2.clone();
+        let progress_sent = progress_sent2.clone();
+        worker.log_register().insert::<TimelyEvent,_>("timely", move |_time, data| {
+            for (time, _, event) in data.drain(..) {
+                match event {
+                    TimelyEvent::Messages(event) if event.is_send && event.target == 1 => data_sent.lock().unwrap().push(time),
+                    TimelyEvent::Progress(event) if event.is_send => progress_sent.lock().unwrap().push(time),
+                    _ => { },
+                }
+            }
+        });
+
+        let mut input = InputHandle::new();
+        let dataflow = worker.next_dataflow_index();
+        worker.dataflow::<u64,_,_>(|scope| {
+            scope.input_from(&mut input)
+                 .exchange(|_: &Vec<u64>| 1)
+                 .probe();
+        });
+
+        // Far more data than the connection buffers, all of it sent before any progress.
+        for record in 0 .. 8_192u64 {
+            input.send(vec![record; 1_024]);
+        }
+        input.advance_to(1);
+        worker.step();
+        sent2.wait();
+
+        while worker.lost_processes().is_empty() {
+            worker.step();
+        }
+        worker.drop_dataflow(dataflow);
+    }).unwrap();
+
+    let mut stream = impostor.join().unwrap();
+    let mut agreed = [0u8; 8];
+    stream.read_exact(&mut agreed).unwrap();
+    sent.wait();
+
+    // Read messages in the order they were written, until one on the data channel follows
+    // one on another channel, or nothing more arrives.
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut buffer = Vec::new();
+    let mut offset = 0;
+    let mut data_channel = None;
+    let mut progress_seen = false;
+    let mut overtaken = false;
+    let mut chunk = vec![0u8; 1 << 16];
+    while !overtaken {
+        match stream.read(&mut chunk[..]) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => buffer.extend_from_slice(&chunk[.. read]),
+        }
+        while let Some(header) = MessageHeader::try_read(&mut buffer[offset ..]) {
+            offset += header.required_bytes();
+            if *data_channel.get_or_insert(header.channel) == header.channel {
+                overtaken = progress_seen;
+            }
+            else {
+                progress_seen = true;
+            }
+        }
+        buffer.drain(.. offset);
+        offset = 0;
+    }
+    drop(stream);
+
+    for result in guards.join() {
+        result.unwrap();
+    }
+
+    // Progress was sent after all of the data, yet some of the data arrived after it.
+    let last_data_sent = data_sent.lock().unwrap().iter().max().cloned().unwrap();
+    let first_progress_sent = progress_sent.lock().unwrap().iter().min().cloned().unwrap();
+    assert!(last_data_sent < first_progress_sent);
+    assert!(overtaken);
+}
@@ -0,0 +1,81 @@
+extern crate timely;
+
+mod common;
+
+use std::sync::{Arc, Barrier};
+use std::time::{Duration, Instant};
+
+use timely::Configuration;
+use timely::communication::{ConnectionOptions, WorkerGuards};
+use timely::dataflow::InputHandle;
+use timely::dataflow::operators::{Input, Exchange, Probe};
+
+/// Introduces and exchanges input until the computation is cancelled, returning the rounds
+/// introduced.
+fn run_until_cancelled(config: Configuration) -> WorkerGuards<u64> {
+    timely::execute(config, |worker| {
+        let mut input = InputHandle::new();
+        let probe = worker.dataflow::<u64,_,_>(|scope| {
+            scope.input_from(&mut input)
+                 .exchange(|x: &u64| *x)
+                 .probe()
+        });
+        let mut round = 0;
+        while !worker.cancellation().is_cancelled() {
+            input.send(round);
+            round += 1;
+            input.advance_to(round);
+            while probe.less_than(input.time()) && !worker.cancellation().is_cancelled() {
+                worker.step();
+            }
+        }
+        round
+    }).unwrap()
+}
+
+// This test asserts that processes shutting down together stop their workers and tear down
+// their connections without reporting failures.
+#[test]
+fn coordinated_shutdown() {
+
+    let barrier = Arc::new(Barrier::new(2));
+
+    let processes = common::cluster(2, vec![ConnectionOptions::default(); 2]).into_iter().map(|config| {
+        let barrier = barrier.clone();
+        ::std::thread::spawn(move || {
+            let guards = run_until_cancelled(config);
+            ::std::thread::sleep(Duration::from_millis(100));
+            barrier.wait();
+            guards.shutdown()
+        })
+    }).collect::<Vec<_>>();
+
+    for process in processes {
+        for result in process.join().unwrap() {
+            assert!(result.unwrap() > 0);
+        }
+    }
+}
+
+// This test asserts that a process shutting down alone does not wait indefinitely for its
+// peers to end their connections.
+#[test]
+fn shutdown_does_not_await_peers() {
+
+    let config = common::cluster(2, vec![ConnectionOptions::default(); 2]).remove(0);
+
+    // Impersonate process 1: complete the handshake, then hold the connection open.
+    let impostor = common::impersonate(&config);
+
+    let guards = run_until_cancelled(config);
+    let stream = impostor.join().unwrap();
+    ::std::thread::sleep(Duration::from_millis(100));
+
+    let start = Instant::now();
+    for result in guards.shutdown() {
+        result.unwrap();
+    }
+    assert!(start.elapsed() < Duration::from_secs(10));
+
+    drop(stream);
+}
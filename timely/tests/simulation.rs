@@ -0,0 +1,60 @@
+extern crate timely;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use timely::execute::Simulation;
+use timely::communication::allocator::simulation::{Chooser, Exhaustive, Seeded};
+use timely::dataflow::operators::{ToStream, Exchange, Inspect, Probe};
+
+/// Runs a dataflow exchanging records between three workers, and returns the order in
+/// which the workers observed the exchanged records.
+fn observed<C: Chooser+'static>(chooser: C) -> Vec<(usize, u64)> {
+    let observed = Rc::new(RefCell::new(Vec::new()));
+    let mut simulation = Simulation::new(3, chooser);
+    simulation.build(|worker| {
+        let index = worker.index();
+        let observed = observed.clone();
+        worker.dataflow::<u64,_,_>(|scope| {
+            (0 .. 30u64)
+                .to_stream(scope)
+                .exchange(|x| *x)
+                .inspect(move |x| observed.borrow_mut().push((index, *x)))
+                .probe();
+        });
+    });
+    simulation.run();
+    assert_eq!(simulation.network().in_flight(), 0);
+    let observed = observed.borrow().clone();
+    observed
+}
+
+#[test]
+fn seeded_schedules_are_reproducible() {
+    let mut distinct = Vec::new();
+    for seed in 0 .. 10 {
+        let first = observed(Seeded::new(seed));
+        assert_eq!(first, observed(Seeded::new(seed)));
+        assert_eq!(first.len(), 90);
+        if !distinct.contains(&first) {
+            distinct.push(first);
+        }
+    }
+    assert!(distinct.len() > 1);
+}
+
+#[test]
+fn exhaustive_schedules_complete() {
+    let exhaustive = Rc::new(RefCell::new(Exhaustive::new(4)));
+    let mut schedules = 0;
+    loop {
+        let mut observed = observed(exhaustive.clone());
+        observed.sort();
+        let mut expected = (0 .. 3).flat_map(|_| (0 .. 30u64).map(|x| ((x % 3) as usize, x))).collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(observed, expected);
+        schedules += 1;
+        if !exhaustive.borrow_mut().next_schedule() { break; }
+    }
+    assert!(schedules > 1);
+}
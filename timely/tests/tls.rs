@@ -0,0 +1,102 @@
+#![cfg(feature = "tls")]
+
+extern crate rcgen;
+extern crate timely;
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use rcgen::{BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+
+use timely::Configuration;
+use timely::communication::ConnectionOptions;
+use timely::communication::allocator::zero_copy::tls::TlsConfig;
+use timely::dataflow::operators::{ToStream, Exchange, Inspect};
+
+/// Writes a certificate authority, and a certificate and key for `127.0.0.1` that it signed.
+fn write_credentials(directory: &Path) -> TlsConfig {
+
+    std::fs::create_dir_all(directory).unwrap();
+
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+
+    let key = KeyPair::generate().unwrap();
+    let mut params = CertificateParams::new(vec!["127.0.0.1".to_owned()]).unwrap();
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth, ExtendedKeyUsagePurpose::ClientAuth];
+    let cert = params.signed_by(&key, &ca, &ca_key).unwrap();
+
+    let config = TlsConfig::new(directory.join("ca.pem"), directory.join("cert.pem"), directory.join("key.pem"));
+    std::fs::write(&config.ca, ca.pem()).unwrap();
+    std::fs::write(&config.cert, cert.pem()).unwrap();
+    std::fs::write(&config.key, key.serialize_pem()).unwrap();
+    config
+}
+
+fn scratch(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("timely-tls-{}-{}", name, std::process::id()))
+}
+
+/// Configurations for processes of two workers each, secured with the credentials.
+fn cluster(credentials: Vec<TlsConfig>) -> Vec<Configuration> {
+    let options = credentials.into_iter().map(|tls| {
+        ConnectionOptions { tls: Some(tls), ..ConnectionOptions::default() }
+    });
+    common::cluster(2, options.collect())
+}
+
+#[test]
+fn exchange_over_tls() {
+
+    let directory = scratch("exchange");
+    let tls = write_credentials(&directory);
+    let configs = cluster(vec![tls; 2]);
+
+    let processes = configs.into_iter().map(|config| {
+        std::thread::spawn(move || {
+            timely::execute(config, |worker| {
+                let index = worker.index();
+                worker.dataflow::<u64,_,_>(|scope| {
+                    (0 .. 100u64)
+                        .to_stream(scope)
+                        .exchange(|x| *x)
+                        .inspect(move |x| assert_eq!(*x as usize % 4, index));
+                });
+            })
+            .unwrap()
+            .join()
+            .into_iter()
+            .map(|result| result.unwrap())
+            .count()
+        })
+    }).collect::<Vec<_>>();
+
+    for process in processes {
+        assert_eq!(process.join().unwrap(), 2);
+    }
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn untrusted_peer_is_rejected() {
+
+    // Each process trusts only its own authority.
+    let directories = vec![scratch("reject-0"), scratch("reject-1")];
+    let configs = cluster(directories.iter().map(|directory| write_credentials(directory)).collect());
+
+    let processes = configs.into_iter().map(|config| {
+        std::thread::spawn(move || timely::execute(config, |_worker| ()).is_err())
+    }).collect::<Vec<_>>();
+
+    for process in processes {
+        assert!(process.join().unwrap());
+    }
+
+    for directory in directories {
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+#![cfg(unix)]
+
+extern crate timely;
+
+use std::sync::{Arc, Mutex};
+
+use timely::Configuration;
+use timely::communication::ConnectionOptions;
+use timely::communication::networking::Transport;
+use timely::dataflow::operators::{ToStream, Exchange, Inspect};
+
+/// Runs two processes of two workers each, which exchange enough data to wrap the
+/// shared-memory rings several times, and returns the sum of the data each worker received.
+fn exchange(transport: Transport, name: &str) -> Vec<u64> {
+
+    let directory = std::env::temp_dir().join(format!("timely-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let addresses = (0 .. 2).map(|index| directory.join(format!("{}.sock", index)).to_string_lossy().into_owned()).collect::<Vec<_>>();
+
+    let processes = (0 .. 2).map(|process| {
+        let options = ConnectionOptions { transport, ..ConnectionOptions::default() };
+        let config = Configuration::cluster(2, process, addresses.clone(), options);
+        std::thread::spawn(move || {
+            timely::execute(config, |worker| {
+                let index = worker.index();
+                let sum = Arc::new(Mutex::new(0));
+                let sum2 = sum.clone();
+                worker.dataflow::<u64,_,_>(|scope| {
+                    (0 .. 1_000_000u64)
+                        .to_stream(scope)
+                        .exchange(|x| *x)
+                        .inspect(move |x| {
+                            assert_eq!(*x as usize % 4, index);
+                            *sum2.lock().unwrap() += *x;
+                        });
+                });
+                while worker.step() { }
+                let sum = *sum.lock().unwrap();
+                sum
+            })
+            .unwrap()
+            .join()
+            .into_iter()
+            .map(|result| result.unwrap())
+            .collect::<Vec<_>>()
+        })
+    }).collect::<Vec<_>>();
+
+    let sums = processes.into_iter().flat_map(|process| process.join().unwrap()).collect();
+
+    // Socket and ring files are removed once connections are established.
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+    std::fs::remove_dir_all(directory).unwrap();
+    sums
+}
+
+fn expected() -> Vec<u64> {
+    // Each of four workers introduces 0 .. 1_000_000, and worker `i` receives the values
+    // congruent to `i` modulo four.
+    (0 .. 4).map(|index| 4 * (index .. 1_000_000).step_by(4).sum::<u64>()).collect()
+}
+
+#[test]
+fn exchange_over_unix_sockets() {
+    assert_eq!(exchange(Transport::Unix, "unix"), expected());
+}
+
+#[test]
+fn exchange_over_shared_memory() {
+    assert_eq!(exchange(Transport::SharedMemory, "shm"), expected());
+}